@@ -0,0 +1,185 @@
+use crate::strategy::{Move, Strategy};
+use crate::view::BoardView;
+use game_core::types::{
+  AttackRequest, DefendRequest, Error, GameStatus, Games, JoinExistingRequest, PlaceMineRequest, QueryGameRequest, Result,
+  SenderDetails, TeamRole,
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Schedule {
+  pub interval: Duration,
+  /// Stops after this many turns even if the game is still going.
+  pub max_turns: Option<usize>,
+}
+
+impl Default for Schedule {
+  fn default() -> Self {
+    Self {
+      interval: Duration::from_secs(1),
+      max_turns: None,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TurnOutcome {
+  /// The host hasn't started the game yet.
+  Waiting,
+  /// The strategy had nothing to do this turn.
+  Skipped,
+  Played {
+    action: Move,
+    rejection: Option<Error>,
+  },
+  /// The game has ended or the bot has no requests left.
+  Finished,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct BotReport {
+  pub turns: usize,
+  pub moves_played: usize,
+  pub moves_rejected: usize,
+}
+
+/// Plays a game through the public `Games` API with its own team credentials.
+#[derive(Debug)]
+pub struct Bot {
+  games: Games,
+  game_id: i32,
+  team_id: i32,
+  team_key: String,
+  strategy: Box<dyn Strategy>,
+  rng: ChaCha8Rng,
+}
+
+impl Bot {
+  pub fn new(games: Games, game_id: i32, team_id: i32, team_key: String, strategy: Box<dyn Strategy>, seed: u64) -> Self {
+    Self {
+      games,
+      game_id,
+      team_id,
+      team_key,
+      strategy,
+      rng: ChaCha8Rng::seed_from_u64(seed),
+    }
+  }
+
+  /// Registers a new team for the bot in a game that hasn't started yet.
+  pub async fn try_join(
+    mut games: Games,
+    game_id: i32,
+    display_name: impl Into<String>,
+    team_role: TeamRole,
+    strategy: Box<dyn Strategy>,
+    seed: u64,
+  ) -> Result<Self> {
+    let joined = games
+      .try_join_an_existing_game(JoinExistingRequest {
+        game_id,
+        display_name: display_name.into(),
+        team_role,
+      })
+      .await?;
+
+    Ok(Self::new(games, game_id, joined.team_id, joined.team_key, strategy, seed))
+  }
+
+  pub fn team_id(&self) -> i32 {
+    self.team_id
+  }
+
+  pub fn strategy_name(&self) -> &'static str {
+    self.strategy.name()
+  }
+
+  fn sender(&self) -> SenderDetails {
+    SenderDetails {
+      team_id: self.team_id,
+      team_key: self.team_key.clone(),
+    }
+  }
+
+  pub async fn take_turn(&mut self) -> Result<TurnOutcome> {
+    let game = self
+      .games
+      .try_query_game(QueryGameRequest { game_id: self.game_id })
+      .await?
+      .game;
+
+    let view = BoardView::from_game(&game, self.team_id).ok_or(Error::InvalidTeamId { team_id: self.team_id })?;
+
+    match view.status {
+      GameStatus::WaitingForRegistrations => return Ok(TurnOutcome::Waiting),
+      GameStatus::Ended => return Ok(TurnOutcome::Finished),
+      GameStatus::Started if !view.can_act() => return Ok(TurnOutcome::Finished),
+      GameStatus::Started => {}
+    }
+
+    let Some(action) = self.strategy.next_move(&view, &mut self.rng) else {
+      return Ok(TurnOutcome::Skipped);
+    };
+
+    let (game_id, sender) = (self.game_id, self.sender());
+    let result = match action {
+      Move::Attack { row, column } => self
+        .games
+        .try_attack_a_square(AttackRequest {
+          game_id,
+          sender,
+          row_index: row,
+          column_index: column,
+        })
+        .await
+        .map(|_| ()),
+      Move::Defend { row, column } => self
+        .games
+        .try_defend_a_square(DefendRequest {
+          game_id,
+          sender,
+          row_index: row,
+          column_index: column,
+        })
+        .await
+        .map(|_| ()),
+      Move::PlaceMine { row, column } => self
+        .games
+        .try_place_a_mine(PlaceMineRequest {
+          game_id,
+          sender,
+          row_index: row,
+          column_index: column,
+        })
+        .await
+        .map(|_| ()),
+    };
+
+    Ok(TurnOutcome::Played {
+      action,
+      rejection: result.err(),
+    })
+  }
+
+  /// Takes a turn every `schedule.interval` until the bot is finished or runs out of turns.
+  pub async fn run(mut self, schedule: Schedule) -> Result<BotReport> {
+    let mut interval = tokio::time::interval(schedule.interval);
+    let mut report = BotReport::default();
+
+    while schedule.max_turns.is_none_or(|max_turns| report.turns < max_turns) {
+      interval.tick().await;
+      report.turns += 1;
+
+      match self.take_turn().await? {
+        TurnOutcome::Finished => break,
+        TurnOutcome::Played { rejection: None, .. } => report.moves_played += 1,
+        TurnOutcome::Played { rejection: Some(_), .. } => report.moves_rejected += 1,
+        TurnOutcome::Waiting | TurnOutcome::Skipped => {}
+      }
+    }
+
+    Ok(report)
+  }
+}
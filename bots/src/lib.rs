@@ -0,0 +1,7 @@
+mod bot;
+mod strategy;
+mod view;
+
+pub use bot::{Bot, BotReport, Schedule, TurnOutcome};
+pub use strategy::{GreedyConqueror, Minelayer, Move, RandomAttacker, Strategy, TerritoryDefender};
+pub use view::{BoardView, SquareView};
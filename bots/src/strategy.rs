@@ -0,0 +1,118 @@
+use crate::view::{BoardView, SquareView};
+use game_core::types::TeamRole;
+use rand::seq::IteratorRandom;
+use rand::RngCore;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Move {
+  Attack { row: i32, column: i32 },
+  Defend { row: i32, column: i32 },
+  PlaceMine { row: i32, column: i32 },
+}
+
+pub trait Strategy: std::fmt::Debug + Send {
+  fn name(&self) -> &'static str;
+
+  /// Picks the next move, or `None` to skip this turn. Only called while the bot can act.
+  fn next_move(&mut self, view: &BoardView, rng: &mut dyn RngCore) -> Option<Move>;
+}
+
+fn attack(square: &SquareView) -> Move {
+  Move::Attack {
+    row: square.row,
+    column: square.column,
+  }
+}
+
+/// Weakest square the team doesn't own yet, ties broken at random.
+fn weakest_target<'a>(view: &'a BoardView, rng: &mut dyn RngCore) -> Option<&'a SquareView> {
+  let weakest = view.not_owned().map(|square| square.health).min()?;
+  view.not_owned().filter(|square| square.health == weakest).choose(rng)
+}
+
+/// Attacks any square it doesn't own.
+#[derive(Debug, Default)]
+pub struct RandomAttacker;
+
+impl Strategy for RandomAttacker {
+  fn name(&self) -> &'static str {
+    "random_attacker"
+  }
+
+  fn next_move(&mut self, view: &BoardView, rng: &mut dyn RngCore) -> Option<Move> {
+    view.not_owned().choose(rng).map(attack)
+  }
+}
+
+/// Keeps attacking the weakest square it doesn't own, to conquer as much as possible.
+#[derive(Debug, Default)]
+pub struct GreedyConqueror;
+
+impl Strategy for GreedyConqueror {
+  fn name(&self) -> &'static str {
+    "greedy_conqueror"
+  }
+
+  fn next_move(&mut self, view: &BoardView, rng: &mut dyn RngCore) -> Option<Move> {
+    weakest_target(view, rng).map(attack)
+  }
+}
+
+/// Conquers a foothold, then keeps its own territory topped up, only attacking once everything it owns is at full health.
+#[derive(Debug)]
+pub struct TerritoryDefender {
+  pub full_health: i32,
+}
+
+impl Default for TerritoryDefender {
+  fn default() -> Self {
+    Self {
+      full_health: game_core::commands::CONQUERED_SQUARE_HEALTH,
+    }
+  }
+}
+
+impl Strategy for TerritoryDefender {
+  fn name(&self) -> &'static str {
+    "territory_defender"
+  }
+
+  fn next_move(&mut self, view: &BoardView, rng: &mut dyn RngCore) -> Option<Move> {
+    let damaged = view
+      .owned()
+      .filter(|square| square.health < self.full_health)
+      .min_by_key(|square| square.health);
+
+    match damaged {
+      Some(square) => Some(Move::Defend {
+        row: square.row,
+        column: square.column,
+      }),
+      None => weakest_target(view, rng).map(attack),
+    }
+  }
+}
+
+/// Places its single mine on an unowned square first, then plays like `GreedyConqueror`.
+#[derive(Debug, Default)]
+pub struct Minelayer;
+
+impl Strategy for Minelayer {
+  fn name(&self) -> &'static str {
+    "minelayer"
+  }
+
+  fn next_move(&mut self, view: &BoardView, rng: &mut dyn RngCore) -> Option<Move> {
+    if view.role == TeamRole::Minelayer && !view.role_used {
+      let square = view.squares.iter().filter(|square| square.owner_id.is_none()).choose(rng);
+      if let Some(square) = square {
+        return Some(Move::PlaceMine {
+          row: square.row,
+          column: square.column,
+        });
+      }
+    }
+
+    weakest_target(view, rng).map(attack)
+  }
+}
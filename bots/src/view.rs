@@ -0,0 +1,85 @@
+use game_core::replay::ReplayState;
+use game_core::types::{Game, GameStatus, TeamRole};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SquareView {
+  pub row: i32,
+  pub column: i32,
+  pub owner_id: Option<i32>,
+  pub health: i32,
+}
+
+/// What a bot gets to see of the game before picking its next move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardView {
+  pub team_id: i32,
+  pub role: TeamRole,
+  pub role_used: bool,
+  pub requests_left: i32,
+  pub status: GameStatus,
+  pub squares: Vec<SquareView>,
+}
+
+impl BoardView {
+  /// Returns `None` if the team isn't part of the game.
+  pub fn from_game(game: &Game, team_id: i32) -> Option<Self> {
+    let team = game.teams.iter().find(|team| team.id == team_id)?;
+
+    let squares = game
+      .grid
+      .iter()
+      .map(|square| SquareView {
+        row: square.row,
+        column: square.column,
+        owner_id: square.owner_id,
+        health: square.health,
+      })
+      .collect();
+
+    Some(Self {
+      team_id,
+      role: team.role,
+      role_used: team.role_used,
+      requests_left: team.requests_left,
+      status: game.status,
+      squares,
+    })
+  }
+
+  /// Same view of an in-memory game. Roles aren't part of the replayed state, so the caller passes it in.
+  pub fn from_state(state: &ReplayState, team_id: i32, role: TeamRole) -> Option<Self> {
+    let team = state.teams.get(&team_id)?;
+
+    let squares = state
+      .grid
+      .iter()
+      .map(|square| SquareView {
+        row: square.row,
+        column: square.column,
+        owner_id: square.owner_id,
+        health: square.health,
+      })
+      .collect();
+
+    Some(Self {
+      team_id,
+      role,
+      role_used: team.role_used,
+      requests_left: team.requests_left,
+      status: state.status,
+      squares,
+    })
+  }
+
+  pub fn owned(&self) -> impl Iterator<Item = &SquareView> {
+    self.squares.iter().filter(|square| square.owner_id == Some(self.team_id))
+  }
+
+  pub fn not_owned(&self) -> impl Iterator<Item = &SquareView> {
+    self.squares.iter().filter(|square| square.owner_id != Some(self.team_id))
+  }
+
+  pub fn can_act(&self) -> bool {
+    self.status == GameStatus::Started && self.requests_left > 0
+  }
+}
@@ -0,0 +1,379 @@
+use crate::error::{ClientError, Result};
+use game_core::protocol::{RequestEnvelope, ResponseEnvelope};
+use game_core::types::{
+  AttackRequest, AttackResponse, BatchMode, BatchOperation, BatchRequest, BatchResponse, Command, CommandResponse,
+  CreateAndJoinRequest, CreateAndJoinResponse, DefendRequest, DefendResponse, GameOptions, JoinExistingRequest,
+  JoinExistingResponse, PlaceMineRequest, PlaceMineResponse, QueryAuthFailuresRequest, QueryAuthFailuresResponse,
+  QueryFlagsRequest, QueryFlagsResponse, QueryGameRequest, QueryGameResponse, QueryGridChangesRequest, QueryGridChangesResponse,
+  QueryGridRequest, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse, SenderDetails, StartRequest,
+  StartResponse, TeamRole,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+/// Unwraps the expected variant of a `CommandResponse`.
+macro_rules! expect_response {
+  ($response:expr, $variant:ident) => {
+    match $response {
+      CommandResponse::$variant(response) => Ok(response),
+      other => Err(ClientError::UnexpectedResponse {
+        expected: stringify!($variant),
+        received: (&other).into(),
+      }),
+    }
+  };
+}
+
+/// Everything needed to send commands on behalf of a team.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Credentials {
+  pub game_id: i32,
+  pub team_id: i32,
+  pub team_key: String,
+}
+
+impl Credentials {
+  fn sender(&self) -> SenderDetails {
+    SenderDetails {
+      team_id: self.team_id,
+      team_key: self.team_key.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ReconnectPolicy {
+  /// Connection attempts before giving up, including the first one.
+  pub attempts: usize,
+  pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+  fn default() -> Self {
+    Self {
+      attempts: 3,
+      delay: Duration::from_millis(200),
+    }
+  }
+}
+
+#[derive(Debug)]
+struct Connection {
+  reader: Lines<BufReader<OwnedReadHalf>>,
+  writer: OwnedWriteHalf,
+}
+
+impl Connection {
+  async fn open(address: &str) -> std::io::Result<Self> {
+    let (reader, writer) = TcpStream::connect(address).await?.into_split();
+    Ok(Self {
+      reader: BufReader::new(reader).lines(),
+      writer,
+    })
+  }
+}
+
+/// Typed client for the line-delimited JSON protocol.
+///
+/// A lost connection is reopened on the next command. Commands are never resent once they have been
+/// written, since the server may already have applied them, so a command that was cut off mid-flight
+/// fails with `ClientError::Disconnected` and it's up to the caller whether to retry it. Commands sent
+/// with `execute_idempotent` are the exception, the server recognises them when they arrive twice.
+#[derive(Debug)]
+pub struct Client {
+  address: String,
+  connection: Option<Connection>,
+  next_id: u64,
+  credentials: Option<Credentials>,
+  reconnect: ReconnectPolicy,
+}
+
+impl Client {
+  pub async fn connect(address: impl Into<String>) -> Result<Self> {
+    let mut client = Self {
+      address: address.into(),
+      connection: None,
+      next_id: 1,
+      credentials: None,
+      reconnect: ReconnectPolicy::default(),
+    };
+
+    client.reconnect().await?;
+    Ok(client)
+  }
+
+  pub fn with_reconnect_policy(mut self, reconnect: ReconnectPolicy) -> Self {
+    self.reconnect = reconnect;
+    self
+  }
+
+  /// Plays as a team that joined earlier, e.g. from credentials saved to disk.
+  pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+    self.credentials = Some(credentials);
+    self
+  }
+
+  pub fn credentials(&self) -> Option<&Credentials> {
+    self.credentials.as_ref()
+  }
+
+  async fn reconnect(&mut self) -> Result<&mut Connection> {
+    let mut attempt = 1;
+
+    loop {
+      match Connection::open(&self.address).await {
+        Ok(connection) => return Ok(self.connection.insert(connection)),
+        Err(error) if attempt >= self.reconnect.attempts => return Err(error.into()),
+        Err(_) => {
+          attempt += 1;
+          tokio::time::sleep(self.reconnect.delay).await;
+        }
+      }
+    }
+  }
+
+  /// Sends any command and waits for its response, skipping responses to earlier requests that were abandoned.
+  pub async fn execute(&mut self, command: Command) -> Result<CommandResponse> {
+    let (id, line) = self.encode(command, None)?;
+    self.send(id, &line).await
+  }
+
+  /// Like `execute`, but the server applies the command at most once for `idempotency_key`, so a command
+  /// cut off mid-flight is sent again on a new connection instead of failing with `Disconnected`. Use a
+  /// new key, e.g. a UUID, for every command that should be applied.
+  pub async fn execute_idempotent(&mut self, command: Command, idempotency_key: impl Into<String>) -> Result<CommandResponse> {
+    let (id, line) = self.encode(command, Some(idempotency_key.into()))?;
+
+    match self.send(id, &line).await {
+      Err(ClientError::Disconnected | ClientError::Io(_)) => self.send(id, &line).await,
+      result => result,
+    }
+  }
+
+  fn encode(&mut self, command: Command, idempotency_key: Option<String>) -> Result<(u64, String)> {
+    let id = self.next_id;
+    self.next_id += 1;
+
+    let mut line = serde_json::to_string(&RequestEnvelope {
+      id,
+      command,
+      idempotency_key,
+    })?;
+    line.push('\n');
+
+    Ok((id, line))
+  }
+
+  async fn send(&mut self, id: u64, line: &str) -> Result<CommandResponse> {
+    let connection = match self.connection.take() {
+      Some(connection) => self.connection.insert(connection),
+      None => self.reconnect().await?,
+    };
+
+    // a failed write means the server never got the whole command, so it's safe to send it again
+    if connection.writer.write_all(line.as_bytes()).await.is_err() {
+      let connection = self.reconnect().await?;
+      if let Err(error) = connection.writer.write_all(line.as_bytes()).await {
+        self.connection = None;
+        return Err(error.into());
+      }
+    }
+
+    loop {
+      let connection = self.connection.as_mut().ok_or(ClientError::Disconnected)?;
+
+      let line = match connection.reader.next_line().await {
+        Ok(Some(line)) => line,
+        Ok(None) => {
+          self.connection = None;
+          return Err(ClientError::Disconnected);
+        }
+        Err(error) => {
+          self.connection = None;
+          return Err(error.into());
+        }
+      };
+
+      let response: ResponseEnvelope = serde_json::from_str(&line)?;
+
+      match response.id {
+        Some(response_id) if response_id != id => continue,
+        _ => return response.result.map_err(ClientError::from),
+      }
+    }
+  }
+
+  fn require_credentials(&self) -> Result<&Credentials> {
+    self.credentials.as_ref().ok_or(ClientError::MissingCredentials)
+  }
+
+  /// Creates a new game and keeps the host team's credentials for later commands.
+  pub async fn create_and_join(&mut self, display_name: impl Into<String>, team_role: TeamRole) -> Result<CreateAndJoinResponse> {
+    self
+      .create_and_join_with_options(display_name, team_role, GameOptions::default())
+      .await
+  }
+
+  /// Like `create_and_join`, for a game with non-default options.
+  pub async fn create_and_join_with_options(
+    &mut self,
+    display_name: impl Into<String>,
+    team_role: TeamRole,
+    options: GameOptions,
+  ) -> Result<CreateAndJoinResponse> {
+    let command = Command::CreateAndJoin(CreateAndJoinRequest {
+      display_name: display_name.into(),
+      team_role,
+      options,
+    });
+    let response = expect_response!(self.execute(command).await?, CreateAndJoin)?;
+
+    self.credentials = Some(Credentials {
+      game_id: response.game_id,
+      team_id: response.team_id,
+      team_key: response.team_key.clone(),
+    });
+
+    Ok(response)
+  }
+
+  /// Joins an existing game and keeps the new team's credentials for later commands.
+  pub async fn join(
+    &mut self,
+    game_id: i32,
+    display_name: impl Into<String>,
+    team_role: TeamRole,
+  ) -> Result<JoinExistingResponse> {
+    let command = Command::JoinExisting(JoinExistingRequest {
+      game_id,
+      display_name: display_name.into(),
+      team_role,
+    });
+    let response = expect_response!(self.execute(command).await?, JoinExisting)?;
+
+    self.credentials = Some(Credentials {
+      game_id,
+      team_id: response.team_id,
+      team_key: response.team_key.clone(),
+    });
+
+    Ok(response)
+  }
+
+  pub async fn start(&mut self) -> Result<StartResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::Start(StartRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+    });
+
+    expect_response!(self.execute(command).await?, Start)
+  }
+
+  pub async fn attack(&mut self, row: i32, column: i32) -> Result<AttackResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::Attack(AttackRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+      row_index: row,
+      column_index: column,
+    });
+
+    expect_response!(self.execute(command).await?, Attack)
+  }
+
+  pub async fn defend(&mut self, row: i32, column: i32) -> Result<DefendResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::Defend(DefendRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+      row_index: row,
+      column_index: column,
+    });
+
+    expect_response!(self.execute(command).await?, Defend)
+  }
+
+  pub async fn place_mine(&mut self, row: i32, column: i32) -> Result<PlaceMineResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::PlaceMine(PlaceMineRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+      row_index: row,
+      column_index: column,
+    });
+
+    expect_response!(self.execute(command).await?, PlaceMine)
+  }
+
+  pub async fn query_game(&mut self) -> Result<QueryGameResponse> {
+    let game_id = self.require_credentials()?.game_id;
+    expect_response!(
+      self.execute(Command::QueryGame(QueryGameRequest { game_id })).await?,
+      QueryGame
+    )
+  }
+
+  pub async fn query_grid(&mut self) -> Result<QueryGridResponse> {
+    let game_id = self.require_credentials()?.game_id;
+    expect_response!(
+      self.execute(Command::QueryGrid(QueryGridRequest { game_id })).await?,
+      QueryGrid
+    )
+  }
+
+  /// Squares that changed since `since_version`, pass the returned `version` next time to keep polling.
+  pub async fn query_grid_changes(&mut self, since_version: i64) -> Result<QueryGridChangesResponse> {
+    let game_id = self.require_credentials()?.game_id;
+    let command = Command::QueryGridChanges(QueryGridChangesRequest { game_id, since_version });
+
+    expect_response!(self.execute(command).await?, QueryGridChanges)
+  }
+
+  /// Where the flags of a capture the flag game are and how long every team has held them for.
+  pub async fn query_flags(&mut self) -> Result<QueryFlagsResponse> {
+    let game_id = self.require_credentials()?.game_id;
+    expect_response!(
+      self.execute(Command::QueryFlags(QueryFlagsRequest { game_id })).await?,
+      QueryFlags
+    )
+  }
+
+  pub async fn query_square(&mut self, row: i32, column: i32) -> Result<QueryGridSquareResponse> {
+    let game_id = self.require_credentials()?.game_id;
+    let command = Command::QueryGridSquare(QueryGridSquareRequest {
+      game_id,
+      row_index: row,
+      column_index: column,
+    });
+
+    expect_response!(self.execute(command).await?, QueryGridSquare)
+  }
+
+  /// Failed attempts to authenticate as a team of the game, only the host is allowed to see them.
+  pub async fn query_auth_failures(&mut self) -> Result<QueryAuthFailuresResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::QueryAuthFailures(QueryAuthFailuresRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+    });
+
+    expect_response!(self.execute(command).await?, QueryAuthFailures)
+  }
+
+  /// Runs several attacks and defends in one request, see `BatchMode` for what happens when one fails.
+  pub async fn batch(&mut self, operations: Vec<BatchOperation>, mode: BatchMode) -> Result<BatchResponse> {
+    let credentials = self.require_credentials()?;
+    let command = Command::Batch(BatchRequest {
+      game_id: credentials.game_id,
+      sender: credentials.sender(),
+      mode,
+      operations,
+    });
+
+    expect_response!(self.execute(command).await?, Batch)
+  }
+}
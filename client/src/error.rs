@@ -0,0 +1,60 @@
+use game_core::protocol::ErrorPayload;
+use game_core::types::ErrorCode;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+  /// The server rejected the command, `code` names the `game_core` error it failed with.
+  #[error("{message}")]
+  Server {
+    code: ErrorCode,
+    message: String,
+    /// Set when the command was rate limited.
+    retry_after: Option<Duration>,
+  },
+
+  #[error("Connection error {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Connection closed before a response arrived.")]
+  Disconnected,
+
+  #[error("Failed to encode or decode a message {0}")]
+  Json(#[from] serde_json::Error),
+
+  #[error("Expected a {expected} response, received {received}.")]
+  UnexpectedResponse { expected: &'static str, received: &'static str },
+
+  #[error("No credentials, create or join a game first.")]
+  MissingCredentials,
+}
+
+impl ClientError {
+  pub fn code(&self) -> Option<ErrorCode> {
+    match self {
+      ClientError::Server { code, .. } => Some(*code),
+      _ => None,
+    }
+  }
+
+  /// How long the server asked to wait before trying again, if it rate limited the command.
+  pub fn retry_after(&self) -> Option<Duration> {
+    match self {
+      ClientError::Server { retry_after, .. } => *retry_after,
+      _ => None,
+    }
+  }
+}
+
+impl From<ErrorPayload> for ClientError {
+  fn from(payload: ErrorPayload) -> Self {
+    ClientError::Server {
+      code: payload.code,
+      message: payload.message,
+      retry_after: payload.retry_after_ms.map(Duration::from_millis),
+    }
+  }
+}
+
+pub type Result<T> = core::result::Result<T, ClientError>;
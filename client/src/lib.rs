@@ -0,0 +1,6 @@
+mod client;
+mod error;
+
+pub use client::{Client, Credentials, ReconnectPolicy};
+pub use error::{ClientError, Result};
+pub use game_core::protocol::DEFAULT_ADDRESS;
@@ -0,0 +1,186 @@
+use crate::types::{CommandKind, Error, PgPool, Result, SenderDetails};
+use postgres_syntax::sql;
+use std::time::Duration;
+
+/// How failed authentication attempts are punished. Teams and sources each count their failures
+/// until their next successful command, and once a count reaches its threshold every further failure
+/// locks them out for twice as long as the one before, starting at `base_lockout`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LockoutPolicy {
+  /// Failures at which a team is locked out. Higher than `source_threshold`, since locking out a team
+  /// also locks out its rightful owner, not just whoever is guessing its key.
+  pub team_threshold: i32,
+  pub source_threshold: i32,
+  pub base_lockout: Duration,
+  pub max_lockout: Duration,
+}
+
+impl Default for LockoutPolicy {
+  fn default() -> Self {
+    Self {
+      team_threshold: 10,
+      source_threshold: 5,
+      base_lockout: Duration::from_secs(1),
+      max_lockout: Duration::from_secs(15 * 60),
+    }
+  }
+}
+
+impl LockoutPolicy {
+  fn lockout_after(&self, failures: i32, threshold: i32) -> Option<Duration> {
+    let doublings = u32::try_from(failures.checked_sub(threshold)?).ok()?;
+    let lockout = self
+      .base_lockout
+      .checked_mul(2_u32.checked_pow(doublings).unwrap_or(u32::MAX))
+      .unwrap_or(self.max_lockout);
+
+    Some(lockout.min(self.max_lockout))
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Scope {
+  Team,
+  Source,
+}
+
+impl Scope {
+  fn name(self) -> &'static str {
+    match self {
+      Scope::Team => "team",
+      Scope::Source => "source",
+    }
+  }
+}
+
+fn scopes<'a>(team_id: i32, source: Option<&'a str>) -> impl Iterator<Item = (Scope, String)> + 'a {
+  std::iter::once((Scope::Team, team_id.to_string())).chain(source.map(|source| (Scope::Source, source.to_string())))
+}
+
+/// Fails with `TooManyFailedAttempts` while the team or the source is locked out.
+pub(crate) async fn check_lockout(pool: &PgPool, team_id: i32, source: Option<&str>) -> Result<()> {
+  let query = sql!(
+    "
+      SELECT CEIL(EXTRACT(EPOCH FROM MAX(locked_until) - NOW()) * 1000)::BIGINT
+      FROM auth_lockout
+      WHERE
+        ((scope = 'team' AND key = $1) OR (scope = 'source' AND key = $2))
+        AND locked_until > NOW();
+    "
+  );
+
+  let (remaining_ms,): (Option<i64>,) = sqlx::query_as(query)
+    .bind(team_id.to_string())
+    .bind(source)
+    .fetch_one(pool)
+    .await?;
+
+  match remaining_ms {
+    Some(remaining_ms) => Err(Error::TooManyFailedAttempts {
+      retry_after_ms: u64::try_from(remaining_ms).unwrap_or(0).max(1),
+    }),
+    None => Ok(()),
+  }
+}
+
+/// Whether the sender's key is the one of a team in the game. Commands check it again themselves, this
+/// is only for work that shouldn't be done on behalf of anyone who can't prove who they are.
+pub(crate) async fn credentials_match(pool: &PgPool, game_id: i32, sender: &SenderDetails) -> Result<bool> {
+  let query = sql!(
+    "
+      SELECT EXISTS (
+        SELECT 1
+        FROM team
+        WHERE id = $1 AND game_id = $2 AND key = $3
+      );
+    "
+  );
+
+  let (matches,): (bool,) = sqlx::query_as(query)
+    .bind(sender.team_id)
+    .bind(game_id)
+    .bind(&sender.team_key)
+    .fetch_one(pool)
+    .await?;
+
+  Ok(matches)
+}
+
+/// Writes the failure to the audit log and locks the team and source out if they have failed too often.
+pub(crate) async fn record_failure(
+  pool: &PgPool,
+  policy: &LockoutPolicy,
+  game_id: i32,
+  team_id: i32,
+  source: Option<&str>,
+  command: CommandKind,
+) -> Result<()> {
+  let mut tx = pool.begin().await?;
+
+  sqlx::query(sql!(
+    "
+      INSERT INTO auth_failure (game_id, team_id, source, command)
+      VALUES ($1, $2, $3, $4);
+    "
+  ))
+  .bind(game_id)
+  .bind(team_id)
+  .bind(source)
+  .bind::<&'static str>(command.into())
+  .execute(&mut *tx)
+  .await?;
+
+  for (scope, key) in scopes(team_id, source) {
+    let (failures,): (i32,) = sqlx::query_as(sql!(
+      "
+        INSERT INTO auth_lockout (scope, key, failures)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (scope, key) DO UPDATE SET failures = auth_lockout.failures + 1
+        RETURNING failures;
+      "
+    ))
+    .bind(scope.name())
+    .bind(&key)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let threshold = match scope {
+      Scope::Team => policy.team_threshold,
+      Scope::Source => policy.source_threshold,
+    };
+
+    if let Some(lockout) = policy.lockout_after(failures, threshold) {
+      sqlx::query(sql!(
+        "
+          UPDATE auth_lockout
+          SET locked_until = NOW() + $3 * INTERVAL '1 millisecond'
+          WHERE scope = $1 AND key = $2;
+        "
+      ))
+      .bind(scope.name())
+      .bind(&key)
+      .bind(i64::try_from(lockout.as_millis()).unwrap_or(i64::MAX))
+      .execute(&mut *tx)
+      .await?;
+    }
+  }
+
+  tx.commit().await?;
+  Ok(())
+}
+
+/// A successful command wipes the slate clean for its team and source.
+pub(crate) async fn record_success(pool: &PgPool, team_id: i32, source: Option<&str>) -> Result<()> {
+  sqlx::query(sql!(
+    "
+      DELETE FROM auth_lockout
+      WHERE (scope = 'team' AND key = $1) OR (scope = 'source' AND key = $2);
+    "
+  ))
+  .bind(team_id.to_string())
+  .bind(source)
+  .execute(pool)
+  .await?;
+
+  Ok(())
+}
@@ -1,7 +1,18 @@
-use crate::types::{DateTimeUtc, Error, GameStatus, GridSquare, Json, PgPool, Result, SenderDetails};
+use crate::commands::cooldown::check_cooldown;
+use crate::commands::elimination::check_not_eliminated;
+use crate::commands::{CONQUERED_SQUARE_HEALTH, MINE_PENALTY};
+use crate::event_bus::{EventBus, GameEvent};
+use crate::event_log::{commit_with_event_and_notify, NewEvent};
+use crate::rules::MinePenalty;
+use crate::types::{
+  AttackRange, DatabaseErrorKind, DateTimeUtc, Error, EventKind, GameStatus, GridSquare, Json, Mine, PgPool, Result,
+  SenderDetails,
+};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AttackRequest {
   pub game_id: i32,
   pub sender: SenderDetails,
@@ -9,141 +20,288 @@ pub struct AttackRequest {
   pub column_index: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AttackResponse {
   pub square: GridSquare,
   pub conquered: bool,
   pub requests_left: i32,
 }
 
-pub async fn try_attack_a_square(pool: &PgPool, request: AttackRequest) -> Result<AttackResponse> {
+pub async fn try_attack_a_square(pool: &PgPool, bus: &EventBus, request: AttackRequest) -> Result<AttackResponse> {
   let mut tx = pool.begin().await?;
+  let result = attack(&mut tx, &request).await;
+  let event = attack_event(&request, &result);
 
-  let query = sql!(
-    "
-      SELECT
-        game.id AS game_id,
-        to_json(game.status) AS game_status,
-        team.id AS team_id,
-        key,
-        requests_left
-      FROM team
-      INNER JOIN game ON team.id = $1 AND team.game_id = game.id
-      LIMIT 1;
-    "
-  );
-
-  let (game_id, Json(game_status), team_id, team_key, requests_left): (i32, Json<GameStatus>, i32, String, i32) =
-    sqlx::query_as(query)
-      .bind(request.sender.team_id)
-      .fetch_optional(&mut *tx)
-      .await?
-      .ok_or(Error::InvalidTeamId {
-        team_id: request.sender.team_id,
-      })?;
-
-  debug_assert_eq!(team_id, request.sender.team_id);
-
-  Some(&team_key)
-    .filter(|key| key.as_str() == request.sender.team_key)
-    .ok_or(Error::InvalidCredentials)?;
-
-  Some(game_id)
-    .filter(|game_id| *game_id == request.game_id)
-    .ok_or(Error::InvalidGameId {
-      game_id: request.game_id,
-    })?;
+  commit_with_event_and_notify(pool, tx, result, event, bus, |response| {
+    GameEvent::attacked(request.game_id, request.sender.team_id, response)
+  })
+  .await
+}
 
-  Some(requests_left)
-    .filter(|count| count > &0)
-    .ok_or(Error::NoMoreRequestsLeft)?;
+pub(super) fn attack_event(request: &AttackRequest, result: &Result<AttackResponse>) -> NewEvent {
+  let event = NewEvent::new(EventKind::SquareAttacked, request.game_id, Some(request.sender.team_id))
+    .at(request.row_index, request.column_index);
+  match result {
+    Ok(response) => event.with_square(&response.square).with_requests_left(response.requests_left),
+    Err(_) => event,
+  }
+}
 
-  Some(game_status)
-    .filter(|status| *status == GameStatus::Started)
-    .ok_or(Error::InvalidGameStatus {
-      current: game_status,
-      required: GameStatus::Started,
-      action: "attack square",
-    })?;
+async fn attack(conn: &mut PgConnection, request: &AttackRequest) -> Result<AttackResponse> {
+  check_cooldown(conn, request.game_id, &request.sender).await?;
+  check_not_eliminated(conn, request.game_id, &request.sender).await?;
+  apply_attack(conn, request).await
+}
 
+/// The attack itself, without the cooldown, for batches that check it once for all their operations.
+pub(super) async fn apply_attack(conn: &mut PgConnection, request: &AttackRequest) -> Result<AttackResponse> {
+  // lock the square before looking for a mine, so a mine placed concurrently is either seen here
+  // or placed after this attack, matching the order of the event log. It has to be a statement of its
+  // own, a statement can't see rows inserted after it started, even once it has waited for their lock
   let query = sql!(
     "
-      UPDATE team
-      SET requests_left = requests_left - 1
-      WHERE game_id = $1 AND id = $2 AND key = $3
-      RETURNING requests_left;
+      SELECT id
+      FROM grid_square
+      WHERE game_id = $1 AND row_index = $2 AND column_index = $3
+      FOR UPDATE;
     "
   );
 
-  let (requests_left,): (i32,) = sqlx::query_as(query)
+  sqlx::query(query)
     .bind(request.game_id)
-    .bind(request.sender.team_id)
-    .bind(&request.sender.team_key)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|e| {
-      eprintln!("{e:?}");
-      Error::FailedToAttackSquare
-    })?;
-
-  debug_assert!(requests_left >= 0);
+    .bind(request.row_index)
+    .bind(request.column_index)
+    .execute(&mut *conn)
+    .await?;
 
+  // then validates and mutates in one statement. The game and team are locked as they're read, so
+  // concurrent commands wait their turn and this one sees what they did, rather than acting on stale counts.
+  //
+  // in games where attacks only reach adjacent squares, the square has to border one the team owns,
+  // or be the team's home square or next to it, so a team that lost its home can always win it back.
+  //
+  // another team's mine takes the hit instead of the square, and costs the attacker their remaining requests.
+  // Otherwise the square loses a point of health, or is conquered if it had one left.
   let query = sql!(
     "
-      UPDATE grid_square
-      SET
-        owner_id = (CASE WHEN health > 1 THEN owner_id ELSE $1 END),
-        health = (CASE WHEN health > 1 THEN health - 1 ELSE 120 END)
-      WHERE game_id = $2 AND row_index = $3 AND column_index = $4
-      RETURNING
-        id,
-        game_id,
-        owner_id,
-        created_at,
-        row_index,
-        column_index,
-        bonus,
-        health;
+      WITH
+        found_game AS (
+          SELECT id, status, attack_range
+          FROM game
+          WHERE id = $1
+          LIMIT 1
+          FOR SHARE
+        ),
+        found_team AS (
+          SELECT id, game_id, key, requests_left, home_row, home_column
+          FROM team
+          WHERE id = $2
+          LIMIT 1
+          FOR UPDATE
+        ),
+        found_square AS (
+          SELECT id, health
+          FROM grid_square
+          WHERE game_id = $1 AND row_index = $3 AND column_index = $4
+          LIMIT 1
+          FOR UPDATE
+        ),
+        reachable AS (
+          SELECT
+            found_game.attack_range <> $15
+            OR NOT EXISTS (
+              SELECT 1
+              FROM grid_square
+              WHERE game_id = $1 AND owner_id = $2
+            )
+            OR EXISTS (
+              SELECT 1
+              FROM grid_square
+              WHERE game_id = $1 AND owner_id = $2 AND ABS(row_index - $3) + ABS(column_index - $4) = 1
+            )
+            OR EXISTS (
+              SELECT 1
+              FROM found_team
+              WHERE ABS(home_row - $3) + ABS(home_column - $4) <= 1
+            ) AS reachable
+          FROM found_game
+        ),
+        err AS (
+          SELECT
+            to_json(
+              CASE
+                WHEN found_game.id IS NULL THEN $5
+                WHEN found_team.id IS NULL OR found_team.game_id <> $1 OR found_team.key <> $6 THEN $7
+                WHEN 0 = found_team.requests_left THEN $8
+                WHEN found_square IS NULL THEN $9
+                WHEN found_game.status <> $10 THEN $11
+                WHEN NOT reachable.reachable THEN $14
+                ELSE NULL
+              END
+            ) AS error_kind
+          FROM found_game
+          FULL JOIN found_team ON TRUE
+          FULL JOIN found_square ON TRUE
+          FULL JOIN reachable ON TRUE
+        ),
+        triggered_mine AS (
+          UPDATE mine
+          SET triggerer_id = $2
+          FROM found_square
+          WHERE
+            mine.square_id = found_square.id
+            AND mine.triggerer_id IS NULL AND mine.owner_id <> $2
+            AND (SELECT error_kind IS NULL FROM err)
+          RETURNING mine.owner_id
+        ),
+        updated_team AS (
+          UPDATE team
+          SET requests_left = (
+            CASE
+              WHEN EXISTS (SELECT FROM triggered_mine) THEN GREATEST(team.requests_left - 1 - COALESCE($12, team.requests_left), 0)
+              ELSE team.requests_left - 1
+            END
+          ),
+          time_of_last_command = NOW(),
+          first_conquest_at = (
+            CASE
+              WHEN team.first_conquest_at IS NULL AND NOT EXISTS (SELECT FROM triggered_mine) AND found_square.health <= 1 THEN NOW()
+              ELSE team.first_conquest_at
+            END
+          )
+          FROM found_team, found_square
+          WHERE team.id = found_team.id AND (SELECT error_kind IS NULL FROM err)
+          RETURNING team.requests_left
+        ),
+        updated_square AS (
+          UPDATE grid_square
+          SET
+            owner_id = (CASE WHEN EXISTS (SELECT FROM triggered_mine) OR grid_square.health > 1 THEN grid_square.owner_id ELSE $2 END),
+            health = (
+              CASE
+                WHEN EXISTS (SELECT FROM triggered_mine) THEN grid_square.health
+                WHEN grid_square.health > 1 THEN grid_square.health - 1
+                ELSE $13
+              END
+            )
+          FROM found_square
+          WHERE grid_square.id = found_square.id AND (SELECT error_kind IS NULL FROM err)
+          RETURNING grid_square.*, found_square.health <= 1 AND NOT EXISTS (SELECT FROM triggered_mine) AS conquered
+        ),
+        collated AS (
+          SELECT
+            to_json(err.error_kind) AS error_kind,
+            to_json(found_game.status) AS status,
+            updated_team.requests_left,
+            triggered_mine.owner_id AS mine_placed_by,
+            updated_square.id,
+            updated_square.owner_id,
+            updated_square.created_at,
+            updated_square.bonus,
+            updated_square.health,
+            updated_square.conquered
+          FROM err
+          FULL JOIN found_game ON TRUE
+          FULL JOIN updated_team ON TRUE
+          FULL JOIN triggered_mine ON TRUE
+          FULL JOIN updated_square ON TRUE
+        )
+      SELECT *
+      FROM collated;
     "
   );
 
-  let (square_id, game_id, owner_id, created_at, row_index, column_index, bonus, health): (
-    i32,
-    i32,
+  type Row = (
+    Option<Json<DatabaseErrorKind>>,
+    Option<Json<GameStatus>>,
     Option<i32>,
-    DateTimeUtc,
-    i32,
-    i32,
-    i32,
-    i32,
-  ) = sqlx::query_as(query)
-    .bind(request.sender.team_id)
-    .bind(request.game_id)
-    .bind(request.row_index)
-    .bind(request.column_index)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|e| {
-      eprintln!("{e:?}");
-      Error::FailedToAttackSquare
-    })?;
+    Option<i32>,
+    Option<i32>,
+    Option<i32>,
+    Option<DateTimeUtc>,
+    Option<i32>,
+    Option<i32>,
+    Option<bool>,
+  );
+
+  // `None` takes every request the team has left
+  let mine_penalty = match MINE_PENALTY {
+    MinePenalty::AllRequests => None,
+    MinePenalty::Requests(count) => Some(count),
+  };
+
+  let (error_kind, game_status, requests_left, mine_placed_by, square_id, owner_id, created_at, bonus, health, conquered): Row =
+    sqlx::query_as(query)
+      .bind(request.game_id)
+      .bind(request.sender.team_id)
+      .bind(request.row_index)
+      .bind(request.column_index)
+      .bind::<&'static str>(DatabaseErrorKind::InvalidGameId.into())
+      .bind(&request.sender.team_key)
+      .bind::<&'static str>(DatabaseErrorKind::InvalidCredentials.into())
+      .bind::<&'static str>(DatabaseErrorKind::NoMoreRequestsLeft.into())
+      .bind::<&'static str>(DatabaseErrorKind::InvalidCoordinates.into())
+      .bind::<&'static str>(GameStatus::Started.into())
+      .bind::<&'static str>(DatabaseErrorKind::InvalidGameStatus.into())
+      .bind(mine_penalty)
+      .bind(CONQUERED_SQUARE_HEALTH)
+      .bind::<&'static str>(DatabaseErrorKind::SquareNotReachable.into())
+      .bind::<&'static str>(AttackRange::Adjacent.into())
+      .fetch_one(&mut *conn)
+      .await
+      .map_err(|e| Error::FailedToAttackSquare { source: e.into() })?;
 
-  tx.commit().await?;
+  error_kind
+    .map(|Json(error_kind)| match error_kind {
+      DatabaseErrorKind::InvalidGameId => Error::InvalidGameId {
+        game_id: request.game_id,
+      },
+      DatabaseErrorKind::InvalidCoordinates => Error::InvalidCoordinates {
+        row: request.row_index,
+        column: request.column_index,
+      },
+      DatabaseErrorKind::InvalidCredentials => Error::InvalidCredentials,
+      DatabaseErrorKind::SquareNotReachable => Error::SquareNotReachable {
+        row: request.row_index,
+        column: request.column_index,
+      },
+      DatabaseErrorKind::NoMoreRequestsLeft => Error::NoMoreRequestsLeft,
+      DatabaseErrorKind::InvalidGameStatus => Error::InvalidGameStatus {
+        current: game_status
+          .map(|Json(game_status)| game_status)
+          .unwrap_or(GameStatus::WaitingForRegistrations),
+        required: GameStatus::Started,
+        action: "attack square",
+      },
+      _ => Error::Unexpected {
+        message: "failed to attack",
+      },
+    })
+    .map_or(Ok(()), Err)?;
+
+  let (requests_left, square_id, created_at, bonus, health, conquered) = requests_left
+    .and_then(|requests_left| Some((requests_left, square_id?, created_at?, bonus?, health?, conquered?)))
+    .ok_or(Error::Unexpected {
+      message: "failed to attack",
+    })?;
 
   let square = GridSquare {
     id: square_id,
-    game_id,
+    game_id: request.game_id,
     owner_id,
-    row: row_index,
-    column: column_index,
+    row: request.row_index,
+    column: request.column_index,
     created_at,
     bonus,
     health,
-    mine: None,
+    mine: mine_placed_by.map(|placed_by| Mine {
+      placed_by,
+      triggered_by: Some(request.sender.team_id),
+    }),
   };
 
   Ok(AttackResponse {
-    conquered: square.health == 120,
+    conquered,
     square,
     requests_left,
   })
@@ -0,0 +1,66 @@
+use crate::types::{AuthFailure, Error, Json, PgPool, Result, SenderDetails};
+use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryAuthFailuresRequest {
+  pub game_id: i32,
+  pub sender: SenderDetails,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryAuthFailuresResponse {
+  pub failures: Vec<AuthFailure>,
+}
+
+/// Lists every failed attempt to authenticate as a team of the game, oldest first. Only the host can see them.
+pub async fn try_query_auth_failures(pool: &PgPool, request: QueryAuthFailuresRequest) -> Result<QueryAuthFailuresResponse> {
+  let query = sql!(
+    "
+      WITH
+        host_team AS (
+          SELECT id
+          FROM team
+          WHERE team.game_id = $1
+          ORDER BY id
+          LIMIT 1
+        ),
+        failures AS (
+          SELECT json_agg(auth_failure.* ORDER BY auth_failure.id) AS failures
+          FROM auth_failure
+          WHERE game_id = $1
+        )
+      SELECT host_team.id, failures.failures
+      FROM host_team, failures;
+    "
+  );
+
+  let (host_team_id, failures): (i32, Option<Json<Vec<AuthFailure>>>) = sqlx::query_as(query)
+    .bind(request.game_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::InvalidGameId {
+      game_id: request.game_id,
+    })?;
+
+  // the key is checked first, so only a team that has proven who it is learns that it isn't the host
+  let team_key: Option<(String,)> = sqlx::query_as("SELECT key FROM team WHERE id = $1 AND game_id = $2;")
+    .bind(request.sender.team_id)
+    .bind(request.game_id)
+    .fetch_optional(pool)
+    .await?;
+
+  match team_key {
+    Some((team_key,)) if team_key == request.sender.team_key => {}
+    _ => return Err(Error::InvalidCredentials),
+  }
+  if host_team_id != request.sender.team_id {
+    return Err(Error::OnlyHostCanQueryAuthFailures {
+      team_id: request.sender.team_id,
+    });
+  }
+
+  Ok(QueryAuthFailuresResponse {
+    failures: failures.map_or_else(Vec::new, |Json(failures)| failures),
+  })
+}
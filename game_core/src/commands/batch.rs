@@ -0,0 +1,253 @@
+use crate::commands::attack::{apply_attack, attack_event};
+use crate::commands::cooldown::check_cooldown;
+use crate::commands::defend::{apply_defend, defend_event};
+use crate::commands::elimination::check_not_eliminated;
+use crate::commands::{GRID_SIZE, MAX_BATCH_OPERATIONS};
+use crate::event_bus::{self, EventBus, GameEvent};
+use crate::event_log::{append_event, append_rejected_event, NewEvent};
+use crate::idempotency;
+use crate::types::{
+  AttackRequest, AttackResponse, DefendRequest, DefendResponse, Error, ErrorCode, EventKind, GameStatus, Json, PgPool, Result,
+  SenderDetails,
+};
+use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, PgConnection};
+
+/// How a batch deals with an operation that fails.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+  /// The first failing operation rolls back the whole batch.
+  #[default]
+  AllOrNothing,
+  /// Failing operations are rolled back on their own and the rest of the batch carries on.
+  BestEffort,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOperation {
+  Attack { row_index: i32, column_index: i32 },
+  Defend { row_index: i32, column_index: i32 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchRequest {
+  pub game_id: i32,
+  pub sender: SenderDetails,
+  #[serde(default)]
+  pub mode: BatchMode,
+  pub operations: Vec<BatchOperation>,
+}
+
+/// The outcome of one operation, in the same order as the operations of the request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchItemResult {
+  Attacked(AttackResponse),
+  Defended(DefendResponse),
+  /// Only in best effort batches, the operation was rolled back and cost nothing.
+  Failed {
+    code: ErrorCode,
+    message: String,
+  },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+  pub results: Vec<BatchItemResult>,
+}
+
+impl BatchOperation {
+  fn coordinates(&self) -> (i32, i32) {
+    match *self {
+      BatchOperation::Attack { row_index, column_index } | BatchOperation::Defend { row_index, column_index } => {
+        (row_index, column_index)
+      }
+    }
+  }
+
+  /// Runs the operation like the command of the same name, returning its result along with its event.
+  async fn apply(&self, conn: &mut PgConnection, game_id: i32, sender: &SenderDetails) -> (Result<BatchItemResult>, NewEvent) {
+    let (row_index, column_index) = self.coordinates();
+    match self {
+      BatchOperation::Attack { .. } => {
+        let request = AttackRequest {
+          game_id,
+          sender: sender.clone(),
+          row_index,
+          column_index,
+        };
+        let result = apply_attack(conn, &request).await;
+        let event = attack_event(&request, &result);
+        (result.map(BatchItemResult::Attacked), event)
+      }
+      BatchOperation::Defend { .. } => {
+        let request = DefendRequest {
+          game_id,
+          sender: sender.clone(),
+          row_index,
+          column_index,
+        };
+        let result = apply_defend(conn, &request).await;
+        let event = defend_event(&request, &result);
+        (result.map(BatchItemResult::Defended), event)
+      }
+    }
+  }
+}
+
+fn validate(request: &BatchRequest) -> Result<()> {
+  if request.operations.is_empty() {
+    return Err(Error::InvalidRequest {
+      reason: "a batch needs at least one operation".to_string(),
+    });
+  }
+
+  if request.operations.len() > MAX_BATCH_OPERATIONS {
+    return Err(Error::InvalidRequest {
+      reason: format!("a batch can have at most {MAX_BATCH_OPERATIONS} operations"),
+    });
+  }
+
+  let outside_grid = request
+    .operations
+    .iter()
+    .map(BatchOperation::coordinates)
+    .find(|(row, column)| !(0..GRID_SIZE).contains(row) || !(0..GRID_SIZE).contains(column));
+  if let Some((row, column)) = outside_grid {
+    return Err(Error::InvalidCoordinates { row, column });
+  }
+
+  Ok(())
+}
+
+/// Checks everything the operations have in common once, locking the game and the team for the rest
+/// of the batch so nothing else changes them in between operations.
+async fn prepare(conn: &mut PgConnection, request: &BatchRequest) -> Result<()> {
+  let query = sql!(
+    "
+      SELECT to_json(game.status), team.requests_left
+      FROM game
+      INNER JOIN team ON team.game_id = game.id
+      WHERE game.id = $1 AND team.id = $2 AND team.key = $3
+      FOR SHARE OF game
+      FOR UPDATE OF team;
+    "
+  );
+
+  let found: Option<(Json<GameStatus>, i32)> = sqlx::query_as(query)
+    .bind(request.game_id)
+    .bind(request.sender.team_id)
+    .bind(&request.sender.team_key)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  let Some((Json(status), requests_left)) = found else {
+    let query = sql!("SELECT EXISTS (SELECT 1 FROM game WHERE id = $1);");
+    let (game_exists,): (bool,) = sqlx::query_as(query).bind(request.game_id).fetch_one(&mut *conn).await?;
+
+    return Err(if game_exists {
+      Error::InvalidCredentials
+    } else {
+      Error::InvalidGameId {
+        game_id: request.game_id,
+      }
+    });
+  };
+
+  if status != GameStatus::Started {
+    return Err(Error::InvalidGameStatus {
+      current: status,
+      required: GameStatus::Started,
+      action: "run batch",
+    });
+  }
+
+  check_cooldown(conn, request.game_id, &request.sender).await?;
+  check_not_eliminated(conn, request.game_id, &request.sender).await?;
+
+  // a batch that can't be paid for in full would only fail part way through
+  if request.mode == BatchMode::AllOrNothing && usize::try_from(requests_left).unwrap_or(0) < request.operations.len() {
+    return Err(Error::NoMoreRequestsLeft);
+  }
+
+  Ok(())
+}
+
+/// Runs attacks and defends for one team in a single transaction, each charged like the command of
+/// the same name and recorded as its own event. Batches turned away as a whole, before any operation
+/// ran, are recorded as a single rejected `BatchRun` event.
+pub async fn try_run_batch(pool: &PgPool, bus: &EventBus, request: BatchRequest) -> Result<BatchResponse> {
+  if let Err(error) = validate(&request) {
+    return reject(pool, &request, error).await;
+  }
+
+  let mut tx = pool.begin().await?;
+  if let Err(error) = prepare(&mut tx, &request).await {
+    tx.rollback().await?;
+    return reject(pool, &request, error).await;
+  }
+
+  let mut results = Vec::with_capacity(request.operations.len());
+  for (index, operation) in request.operations.iter().enumerate() {
+    match request.mode {
+      BatchMode::AllOrNothing => match operation.apply(&mut tx, request.game_id, &request.sender).await {
+        (Ok(result), event) => {
+          append_event(&mut tx, &event, None).await?;
+          results.push(result);
+        }
+        (Err(error), _) if error.is_internal() => return Err(error),
+        (Err(error), event) => {
+          tx.rollback().await?;
+          append_rejected_event(pool, &event, &error).await?;
+          return Err(Error::BatchOperationFailed {
+            index,
+            error: Box::new(error),
+          });
+        }
+      },
+      BatchMode::BestEffort => {
+        let mut savepoint = tx.begin().await?;
+        match operation.apply(&mut savepoint, request.game_id, &request.sender).await {
+          (Ok(result), event) => {
+            append_event(&mut savepoint, &event, None).await?;
+            savepoint.commit().await?;
+            results.push(result);
+          }
+          (Err(error), _) if error.is_internal() => return Err(error),
+          (Err(error), event) => {
+            savepoint.rollback().await?;
+            append_event(&mut tx, &event, Some(&error)).await?;
+            results.push(BatchItemResult::Failed {
+              code: error.code(),
+              message: error.to_string(),
+            });
+          }
+        }
+      }
+    }
+  }
+
+  for result in &results {
+    let event = match result {
+      BatchItemResult::Attacked(attacked) => GameEvent::attacked(request.game_id, request.sender.team_id, attacked),
+      BatchItemResult::Defended(defended) => GameEvent::defended(request.game_id, request.sender.team_id, defended),
+      BatchItemResult::Failed { .. } => continue,
+    };
+    event_bus::notify(&mut tx, bus, &event).await?;
+  }
+
+  let response = BatchResponse { results };
+  idempotency::store_response(&mut tx, &response).await?;
+  tx.commit().await?;
+
+  Ok(response)
+}
+
+async fn reject<T>(pool: &PgPool, request: &BatchRequest, error: Error) -> Result<T> {
+  let event = NewEvent::new(EventKind::BatchRun, request.game_id, Some(request.sender.team_id));
+  append_rejected_event(pool, &event, &error).await?;
+  Err(error)
+}
@@ -0,0 +1,111 @@
+use crate::types::{
+  AttackRequest, AttackResponse, BatchRequest, BatchResponse, CreateAndJoinRequest, CreateAndJoinResponse, DefendRequest,
+  DefendResponse, JoinExistingRequest, JoinExistingResponse, PlaceMineRequest, PlaceMineResponse, QueryAuthFailuresRequest,
+  QueryAuthFailuresResponse, QueryFlagsRequest, QueryFlagsResponse, QueryGameRequest, QueryGameResponse, QueryGridChangesRequest,
+  QueryGridChangesResponse, QueryGridRequest, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse, SenderDetails,
+  StartRequest, StartResponse,
+};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumDiscriminants, EnumString, IntoStaticStr};
+
+/// Every command a team can send, so transports can accept them without knowing about each one.
+/// `CommandKind` is its fieldless twin, for configuring transports per type of command.
+#[derive(Debug, Serialize, Deserialize, IntoStaticStr, EnumDiscriminants)]
+#[strum_discriminants(name(CommandKind), derive(Hash, IntoStaticStr, EnumString, Serialize, Deserialize))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+  CreateAndJoin(CreateAndJoinRequest),
+  JoinExisting(JoinExistingRequest),
+  Start(StartRequest),
+  Attack(AttackRequest),
+  Defend(DefendRequest),
+  PlaceMine(PlaceMineRequest),
+  QueryGame(QueryGameRequest),
+  QueryGrid(QueryGridRequest),
+  QueryGridChanges(QueryGridChangesRequest),
+  QueryGridSquare(QueryGridSquareRequest),
+  QueryFlags(QueryFlagsRequest),
+  QueryAuthFailures(QueryAuthFailuresRequest),
+  Batch(BatchRequest),
+}
+
+impl Command {
+  pub fn kind(&self) -> CommandKind {
+    self.into()
+  }
+
+  /// The team sending the command, `None` for commands anyone can send.
+  pub fn sender(&self) -> Option<&SenderDetails> {
+    match self {
+      Command::Start(request) => Some(&request.sender),
+      Command::Attack(request) => Some(&request.sender),
+      Command::Defend(request) => Some(&request.sender),
+      Command::PlaceMine(request) => Some(&request.sender),
+      Command::QueryAuthFailures(request) => Some(&request.sender),
+      Command::Batch(request) => Some(&request.sender),
+      Command::CreateAndJoin(_)
+      | Command::JoinExisting(_)
+      | Command::QueryGame(_)
+      | Command::QueryGrid(_)
+      | Command::QueryGridChanges(_)
+      | Command::QueryGridSquare(_)
+      | Command::QueryFlags(_) => None,
+    }
+  }
+
+  /// The game the command is about, `None` when it creates one.
+  pub fn game_id(&self) -> Option<i32> {
+    match self {
+      Command::CreateAndJoin(_) => None,
+      Command::JoinExisting(request) => Some(request.game_id),
+      Command::Start(request) => Some(request.game_id),
+      Command::Attack(request) => Some(request.game_id),
+      Command::Defend(request) => Some(request.game_id),
+      Command::PlaceMine(request) => Some(request.game_id),
+      Command::QueryGame(request) => Some(request.game_id),
+      Command::QueryGrid(request) => Some(request.game_id),
+      Command::QueryGridChanges(request) => Some(request.game_id),
+      Command::QueryGridSquare(request) => Some(request.game_id),
+      Command::QueryFlags(request) => Some(request.game_id),
+      Command::QueryAuthFailures(request) => Some(request.game_id),
+      Command::Batch(request) => Some(request.game_id),
+    }
+  }
+
+  /// Whether the command changes the game, as opposed to only reading it.
+  pub fn changes_state(&self) -> bool {
+    match self {
+      Command::CreateAndJoin(_)
+      | Command::JoinExisting(_)
+      | Command::Start(_)
+      | Command::Attack(_)
+      | Command::Defend(_)
+      | Command::PlaceMine(_)
+      | Command::Batch(_) => true,
+      Command::QueryGame(_)
+      | Command::QueryGrid(_)
+      | Command::QueryGridChanges(_)
+      | Command::QueryGridSquare(_)
+      | Command::QueryFlags(_)
+      | Command::QueryAuthFailures(_) => false,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoStaticStr)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandResponse {
+  CreateAndJoin(CreateAndJoinResponse),
+  JoinExisting(JoinExistingResponse),
+  Start(StartResponse),
+  Attack(AttackResponse),
+  Defend(DefendResponse),
+  PlaceMine(PlaceMineResponse),
+  QueryGame(QueryGameResponse),
+  QueryGrid(QueryGridResponse),
+  QueryGridChanges(QueryGridChangesResponse),
+  QueryGridSquare(QueryGridSquareResponse),
+  QueryFlags(QueryFlagsResponse),
+  QueryAuthFailures(QueryAuthFailuresResponse),
+  Batch(BatchResponse),
+}
@@ -0,0 +1,40 @@
+use crate::types::{Error, Result, SenderDetails};
+use postgres_syntax::sql;
+use sqlx::PgConnection;
+
+/// Turns the sender away if their game has a minimum interval between commands and their last one
+/// was too recent. The team is locked until the end of the transaction, so two commands sent at once
+/// can't both pass the check before either of them is recorded.
+///
+/// Unknown games, teams and keys pass, the command itself reports them.
+pub(crate) async fn check_cooldown(conn: &mut PgConnection, game_id: i32, sender: &SenderDetails) -> Result<()> {
+  let query = sql!(
+    "
+      SELECT
+        CEIL(
+          EXTRACT(
+            EPOCH FROM team.time_of_last_command + game.command_interval_ms * INTERVAL '1 millisecond' - NOW()
+          ) * 1000
+        )::BIGINT
+      FROM game
+      INNER JOIN team ON team.game_id = game.id
+      WHERE game.id = $1 AND team.id = $2 AND team.key = $3
+      FOR SHARE OF game
+      FOR UPDATE OF team;
+    "
+  );
+
+  let remaining: Option<(Option<i64>,)> = sqlx::query_as(query)
+    .bind(game_id)
+    .bind(sender.team_id)
+    .bind(&sender.team_key)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  match remaining {
+    Some((Some(remaining_ms),)) if remaining_ms > 0 => Err(Error::CommandTooSoon {
+      retry_after_ms: remaining_ms as u64,
+    }),
+    _ => Ok(()),
+  }
+}
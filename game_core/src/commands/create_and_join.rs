@@ -1,56 +1,73 @@
-use crate::commands::{GRID_SQUARE_DEFAULT_HEALTH, REQUESTS_COUNT};
-use crate::games::create_random_hex;
-use crate::types::{GameStatus, PgPool, Result, TeamRole};
+use crate::commands::flags::{place_flags, resolve_flag_options};
+use crate::commands::{GRID_SIZE, GRID_SQUARE_DEFAULT_HEALTH, REQUESTS_COUNT};
+use crate::event_log::{commit_with_event, NewEvent};
+use crate::keys::KeyGenerator;
+use crate::types::{EventKind, GameOptions, GameStatus, PgPool, Result, TeamRole};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateAndJoinRequest {
   pub display_name: String,
   pub team_role: TeamRole,
+  #[serde(default)]
+  pub options: GameOptions,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateAndJoinResponse {
   pub game_id: i32,
   pub team_id: i32,
   pub team_key: String,
 }
 
-pub async fn try_create_and_join_a_game(pool: &PgPool, request: CreateAndJoinRequest) -> Result<CreateAndJoinResponse> {
+pub async fn try_create_and_join_a_game(
+  pool: &PgPool,
+  key_generator: &dyn KeyGenerator,
+  request: CreateAndJoinRequest,
+) -> Result<CreateAndJoinResponse> {
   let role: &'static str = request.team_role.into();
-  let team_key = create_random_hex().await?;
+  let team_key = key_generator.generate_key()?;
   let status: &'static str = GameStatus::WaitingForRegistrations.into();
+  let options = resolve_flag_options(request.options)?;
+  let flags = place_flags(&options);
 
-  let squares = (0..5)
+  let squares = (0..GRID_SIZE)
     .flat_map(|row_index| {
-      (0..5).map(move |column_index| {
+      let flags = &flags;
+      (0..GRID_SIZE).map(move |column_index| {
         serde_json::json!({
           "row_index": row_index,
           "column_index": column_index,
           "bonus": 0,
           "health": GRID_SQUARE_DEFAULT_HEALTH,
+          "flag": flags.contains(&(row_index, column_index)),
         })
       })
     })
     .collect::<Vec<_>>();
 
   let squares = serde_json::Value::Array(squares);
+  let mut tx = pool.begin().await?;
 
   let query = sql!(
     "
       WITH
         created_game AS (
-          INSERT INTO game (status)
-          VALUES ($5)
+          INSERT INTO game (
+            status, command_interval_ms, attack_range, home_squares_seed, elimination_grace_ms, mode, flag_count, flag_hold_ms,
+            flags_seed
+          )
+          VALUES ($5, $7, $8, $9, $10, $11, $12, $13, $14)
           RETURNING id
         ),
         parsed AS (
           SELECT *
-          FROM jsonb_to_recordset($6) AS X(row_index INTEGER, column_index INTEGER, bonus INTEGER, health INTEGER)
+          FROM jsonb_to_recordset($6) AS X(row_index INTEGER, column_index INTEGER, bonus INTEGER, health INTEGER, flag BOOLEAN)
         ),
         created_grid_squares AS (
-          INSERT INTO grid_square (game_id, row_index, column_index, bonus, health)
-          SELECT created_game.id, parsed.row_index, parsed.column_index, parsed.bonus, parsed.health
+          INSERT INTO grid_square (game_id, row_index, column_index, bonus, health, flag)
+          SELECT created_game.id, parsed.row_index, parsed.column_index, parsed.bonus, parsed.health, parsed.flag
           FROM parsed, created_game
         ),
         created_team AS (
@@ -74,12 +91,23 @@ pub async fn try_create_and_join_a_game(pool: &PgPool, request: CreateAndJoinReq
     .bind(REQUESTS_COUNT)
     .bind(status)
     .bind(squares)
-    .fetch_one(pool)
+    .bind(options.command_interval_ms.map(i64::from))
+    .bind::<&'static str>(options.attack_range.into())
+    .bind(options.home_squares_seed.map(i64::from))
+    .bind(options.elimination_grace_ms.map(i64::from))
+    .bind::<&'static str>(options.mode.into())
+    .bind(options.flag_count.map(i64::from))
+    .bind(options.flag_hold_ms.map(i64::from))
+    .bind(options.flags_seed.map(i64::from))
+    .fetch_one(&mut *tx)
     .await?;
 
-  Ok(CreateAndJoinResponse {
+  let event = NewEvent::new(EventKind::GameCreated, game_id, Some(team_id)).with_flags(flags);
+  let response = CreateAndJoinResponse {
     game_id,
     team_id,
     team_key,
-  })
+  };
+
+  commit_with_event(pool, tx, Ok(response), event).await
 }
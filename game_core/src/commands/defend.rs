@@ -1,8 +1,16 @@
+use crate::commands::cooldown::check_cooldown;
+use crate::commands::elimination::check_not_eliminated;
 use crate::commands::GRID_SQUARE_DEFAULT_HEALTH;
-use crate::types::{DatabaseErrorKind, DateTimeUtc, Error, GameStatus, GridSquare, Json, PgPool, Result, SenderDetails};
+use crate::event_bus::{EventBus, GameEvent};
+use crate::event_log::{commit_with_event_and_notify, NewEvent};
+use crate::types::{
+  DatabaseErrorKind, DateTimeUtc, Error, EventKind, GameStatus, GridSquare, Json, PgPool, Result, SenderDetails,
+};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DefendRequest {
   pub game_id: i32,
   pub sender: SenderDetails,
@@ -10,21 +18,50 @@ pub struct DefendRequest {
   pub column_index: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DefendResponse {
   pub square: GridSquare,
   pub requests_left: i32,
 }
 
-pub async fn try_defend_a_square(pool: &PgPool, request: DefendRequest) -> Result<DefendResponse> {
+pub async fn try_defend_a_square(pool: &PgPool, bus: &EventBus, request: DefendRequest) -> Result<DefendResponse> {
+  let mut tx = pool.begin().await?;
+  let result = defend(&mut tx, &request).await;
+  let event = defend_event(&request, &result);
+
+  commit_with_event_and_notify(pool, tx, result, event, bus, |response| {
+    GameEvent::defended(request.game_id, request.sender.team_id, response)
+  })
+  .await
+}
+
+pub(super) fn defend_event(request: &DefendRequest, result: &Result<DefendResponse>) -> NewEvent {
+  let event = NewEvent::new(EventKind::SquareDefended, request.game_id, Some(request.sender.team_id))
+    .at(request.row_index, request.column_index);
+  match result {
+    Ok(response) => event.with_square(&response.square).with_requests_left(response.requests_left),
+    Err(_) => event,
+  }
+}
+
+async fn defend(conn: &mut PgConnection, request: &DefendRequest) -> Result<DefendResponse> {
+  check_cooldown(conn, request.game_id, &request.sender).await?;
+  check_not_eliminated(conn, request.game_id, &request.sender).await?;
+  apply_defend(conn, request).await
+}
+
+/// The defend itself, without the cooldown, for batches that check it once for all their operations.
+pub(super) async fn apply_defend(conn: &mut PgConnection, request: &DefendRequest) -> Result<DefendResponse> {
   // if creds are ok AND game id is ok AND game status is "started" AND row is ok AND column is ok AND requests_left is greater than 0:
   //    update grid_square
-  //    set health =  MAX(
+  //    set health =  MIN(
   //      health + 1,
   //      60 if owner_id is null otherwise 120
-  //    ),
+  //    ), leaving home squares that still have more than that alone,
   //    requests_left--
   //    where game_id, row, column all match
+  //
+  // the game, team and square are locked as they're read, so concurrent commands can't spend the same request
 
   type Row = (
     Option<Json<DatabaseErrorKind>>,
@@ -48,12 +85,14 @@ pub async fn try_defend_a_square(pool: &PgPool, request: DefendRequest) -> Resul
         FROM game
         WHERE game.id = $1
         LIMIT 1
+        FOR SHARE
       ),
       found_team AS (
         SELECT id, key, requests_left
         FROM team
         WHERE team.id = $2
         LIMIT 1
+        FOR UPDATE
       ),
       found_square AS (
         SELECT id, row_index, column_index, bonus, health
@@ -63,6 +102,7 @@ pub async fn try_defend_a_square(pool: &PgPool, request: DefendRequest) -> Resul
           AND row_index = $4
           AND column_index = $5
         LIMIT 1
+        FOR UPDATE
       ),
       err AS (
         SELECT
@@ -92,9 +132,12 @@ pub async fn try_defend_a_square(pool: &PgPool, request: DefendRequest) -> Resul
       updated_square AS (
         UPDATE grid_square
         SET
-          health = LEAST(
-            grid_square.health + 1,
-            CASE WHEN grid_square.owner_id IS NULL THEN $3 ELSE 120 END
+          health = GREATEST(
+            grid_square.health,
+            LEAST(
+              grid_square.health + 1,
+              CASE WHEN grid_square.owner_id IS NULL THEN $3 ELSE 120 END
+            )
           )
         FROM found_square
         WHERE grid_square.id = found_square.id AND (SELECT error_kind IS NULL FROM err)
@@ -151,7 +194,7 @@ pub async fn try_defend_a_square(pool: &PgPool, request: DefendRequest) -> Resul
     .bind::<&'static str>(DatabaseErrorKind::InvalidCoordinates.into())
     .bind::<&'static str>(GameStatus::Started.into())
     .bind::<&'static str>(DatabaseErrorKind::InvalidGameStatus.into())
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
   error_kind
@@ -0,0 +1,194 @@
+use crate::event_bus::{self, EventBus, GameEvent};
+use crate::event_log::{append_event, NewEvent};
+use crate::types::{Error, EventKind, GameMode, GameStatus, PgPool, Result, SenderDetails};
+use postgres_syntax::sql;
+use sqlx::PgConnection;
+
+/// Teams knocked out by a sweep, and whether that left a single team standing and ended the game.
+#[derive(Debug, Default)]
+pub(crate) struct Eliminations {
+  pub(crate) team_ids: Vec<i32>,
+  pub(crate) game_ended: bool,
+}
+
+/// Turns the sender away if their team has been eliminated. Relies on the caller having locked the
+/// team already, see `check_cooldown`.
+///
+/// Unknown games, teams and keys pass, the command itself reports them.
+pub(crate) async fn check_not_eliminated(conn: &mut PgConnection, game_id: i32, sender: &SenderDetails) -> Result<()> {
+  let query = sql!(
+    "
+      SELECT eliminated_at IS NOT NULL
+      FROM team
+      WHERE game_id = $1 AND id = $2 AND key = $3;
+    "
+  );
+
+  let eliminated: Option<(bool,)> = sqlx::query_as(query)
+    .bind(game_id)
+    .bind(sender.team_id)
+    .bind(&sender.team_key)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  match eliminated {
+    Some((true,)) => Err(Error::TeamEliminated { team_id: sender.team_id }),
+    _ => Ok(()),
+  }
+}
+
+/// Started games with an elimination rule or flags, which can be decided by time passing alone.
+pub(crate) async fn games_to_settle(pool: &PgPool) -> Result<Vec<i32>> {
+  let query = sql!(
+    "
+      SELECT id
+      FROM game
+      WHERE status = $1 AND (elimination_grace_ms IS NOT NULL OR mode = $2)
+      ORDER BY id;
+    "
+  );
+
+  let game_ids = sqlx::query_scalar(query)
+    .bind::<&'static str>(GameStatus::Started.into())
+    .bind::<&'static str>(GameMode::CaptureTheFlag.into())
+    .fetch_all(pool)
+    .await?;
+
+  Ok(game_ids)
+}
+
+/// Eliminates the teams of a started game that own no squares once the grace period after their first
+/// conquest is over, and ends the game when at most one team is left. Does nothing in games without
+/// the elimination rule. Teams are recorded as eliminated when the grace period ran out, or when they
+/// lost their last square if that was later, however long it took for the game to be settled.
+///
+/// Runs before the commands that could be affected, in a transaction of its own. The game is locked
+/// before any team, like every other command does, and only when there's someone to eliminate, so
+/// most commands get away with a single read.
+pub(crate) async fn eliminate_teams(pool: &PgPool, bus: &EventBus, game_id: i32) -> Result<Eliminations> {
+  let started: &'static str = GameStatus::Started.into();
+
+  // team.first_conquest_at + game.elimination_grace_ms is NULL for teams that haven't conquered
+  // anything yet and in games without the rule, so neither ever has candidates
+  let query = sql!(
+    "
+      SELECT EXISTS (
+        SELECT 1
+        FROM team
+        INNER JOIN game ON game.id = team.game_id
+        WHERE
+          game.id = $1
+          AND game.status = $2
+          AND team.eliminated_at IS NULL
+          AND team.first_conquest_at + game.elimination_grace_ms * INTERVAL '1 millisecond' <= NOW()
+          AND NOT EXISTS (SELECT 1 FROM grid_square WHERE game_id = $1 AND owner_id = team.id)
+      );
+    "
+  );
+
+  let (any,): (bool,) = sqlx::query_as(query).bind(game_id).bind(started).fetch_one(pool).await?;
+  if !any {
+    return Ok(Eliminations::default());
+  }
+
+  let mut tx = pool.begin().await?;
+
+  // a statement of its own, so the checks below see every command that finished while waiting for the lock
+  let query = sql!(
+    "
+      SELECT elimination_grace_ms
+      FROM game
+      WHERE id = $1 AND status = $2
+      FOR UPDATE;
+    "
+  );
+
+  let Some((Some(grace_ms),)): Option<(Option<i64>,)> = sqlx::query_as(query)
+    .bind(game_id)
+    .bind(started)
+    .fetch_optional(&mut *tx)
+    .await?
+  else {
+    return Ok(Eliminations::default());
+  };
+
+  let query = sql!(
+    "
+      UPDATE team
+      SET eliminated_at = GREATEST(
+        first_conquest_at + $2 * INTERVAL '1 millisecond',
+        (SELECT lost_at FROM square_loss WHERE game_id = $1 AND team_id = team.id)
+      )
+      WHERE
+        game_id = $1
+        AND eliminated_at IS NULL
+        AND first_conquest_at + $2 * INTERVAL '1 millisecond' <= NOW()
+        AND NOT EXISTS (SELECT 1 FROM grid_square WHERE game_id = $1 AND owner_id = team.id)
+      RETURNING id;
+    "
+  );
+
+  let mut team_ids: Vec<i32> = sqlx::query_scalar(query)
+    .bind(game_id)
+    .bind(grace_ms)
+    .fetch_all(&mut *tx)
+    .await?;
+  team_ids.sort_unstable();
+
+  for team_id in &team_ids {
+    let event = NewEvent::new(EventKind::TeamEliminated, game_id, Some(*team_id));
+    append_event(&mut tx, &event, None).await?;
+    event_bus::notify(
+      &mut tx,
+      bus,
+      &GameEvent::TeamEliminated {
+        game_id,
+        team_id: *team_id,
+      },
+    )
+    .await?;
+  }
+
+  let query = sql!(
+    "
+      SELECT array_agg(id)
+      FROM team
+      WHERE game_id = $1 AND eliminated_at IS NULL;
+    "
+  );
+
+  let (remaining,): (Option<Vec<i32>>,) = sqlx::query_as(query).bind(game_id).fetch_one(&mut *tx).await?;
+  let remaining = remaining.unwrap_or_default();
+
+  let game_ended = !team_ids.is_empty() && remaining.len() <= 1;
+  if game_ended {
+    let ended: &'static str = GameStatus::Ended.into();
+    let winner = remaining.first().copied();
+    let query = sql!("UPDATE game SET status = $2, winner_id = $3 WHERE id = $1;");
+    sqlx::query(query)
+      .bind(game_id)
+      .bind(ended)
+      .bind(winner)
+      .execute(&mut *tx)
+      .await?;
+
+    // flags stop counting once the game is over
+    let query = sql!(
+      "
+        UPDATE grid_square
+        SET flag_held_since = NULL
+        WHERE game_id = $1 AND flag_held_since IS NOT NULL;
+      "
+    );
+    sqlx::query(query).bind(game_id).execute(&mut *tx).await?;
+
+    // the event names the winner, if anyone is left
+    let event = NewEvent::new(EventKind::GameEnded, game_id, winner);
+    append_event(&mut tx, &event, None).await?;
+    event_bus::notify(&mut tx, bus, &GameEvent::GameEnded { game_id }).await?;
+  }
+
+  tx.commit().await?;
+
+  Ok(Eliminations { team_ids, game_ended })
+}
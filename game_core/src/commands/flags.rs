@@ -0,0 +1,260 @@
+use crate::commands::{DEFAULT_FLAG_COUNT, DEFAULT_FLAG_HOLD_MS, GRID_SIZE};
+use crate::event_bus::{self, EventBus, GameEvent};
+use crate::event_log::{append_event, NewEvent};
+use crate::home_squares;
+use crate::types::{DateTimeUtc, Error, EventKind, GameMode, GameOptions, GameStatus, Json, PgPool, Result};
+use postgres_syntax::sql;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryFlagsRequest {
+  pub game_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flag {
+  pub row_index: i32,
+  pub column_index: i32,
+  pub owner_id: Option<i32>,
+  /// When the owner took the flag, `None` once the game has ended.
+  pub held_since: Option<DateTimeUtc>,
+}
+
+/// How long a team has held flags for, adding up every flag it held at the same time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagStanding {
+  pub team_id: i32,
+  pub flags_held: i64,
+  pub held_ms: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryFlagsResponse {
+  pub flags: Vec<Flag>,
+  /// Every team of the game, in the order of their ids.
+  pub standings: Vec<FlagStanding>,
+  /// Total flag time a team needs to win.
+  pub hold_ms: i64,
+}
+
+/// Fills in the defaults of capture the flag games, and turns flag settings away in other modes.
+pub(super) fn resolve_flag_options(options: GameOptions) -> Result<GameOptions> {
+  match options.mode {
+    GameMode::Conquest if options.flag_count.is_some() || options.flag_hold_ms.is_some() || options.flags_seed.is_some() => {
+      Err(Error::InvalidRequest {
+        reason: "flag_count, flag_hold_ms and flags_seed only apply to capture the flag games".to_string(),
+      })
+    }
+    GameMode::Conquest => Ok(options),
+    GameMode::CaptureTheFlag => {
+      let squares = (GRID_SIZE * GRID_SIZE) as u32;
+      let flag_count = options.flag_count.unwrap_or(DEFAULT_FLAG_COUNT);
+      if !(1..=squares).contains(&flag_count) {
+        return Err(Error::InvalidRequest {
+          reason: format!("flag_count must be between 1 and {squares}"),
+        });
+      }
+
+      Ok(GameOptions {
+        flag_count: Some(flag_count),
+        flag_hold_ms: Some(options.flag_hold_ms.unwrap_or(DEFAULT_FLAG_HOLD_MS)),
+        // kept with the game, so the same flags can be placed again
+        flags_seed: Some(options.flags_seed.unwrap_or_else(rand::random)),
+        ..options
+      })
+    }
+  }
+}
+
+/// Where the flags of a new game go, spread out across the grid by the game's seed. Only capture the
+/// flag games have any.
+pub(super) fn place_flags(options: &GameOptions) -> Vec<(i32, i32)> {
+  let (GameMode::CaptureTheFlag, Some(flag_count), Some(seed)) = (options.mode, options.flag_count, options.flags_seed) else {
+    return Vec::new();
+  };
+
+  let squares = (0..GRID_SIZE)
+    .flat_map(|row| (0..GRID_SIZE).map(move |column| (row, column)))
+    .collect();
+  home_squares::spread(squares, flag_count as usize, &mut ChaCha20Rng::seed_from_u64(u64::from(seed)))
+}
+
+async fn standings(conn: &mut PgConnection, game_id: i32) -> Result<Vec<FlagStanding>> {
+  // flags that are still held count up to now, on top of what was banked for the ones lost since
+  let query = sql!(
+    "
+      SELECT COALESCE(
+        json_agg(
+          json_build_object(
+            'team_id', standing.team_id,
+            'flags_held', standing.flags_held,
+            'held_ms', standing.held_ms
+          )
+          ORDER BY standing.team_id
+        ),
+        '[]'
+      )
+      FROM (
+        SELECT
+          team.id AS team_id,
+          COUNT(grid_square.id) AS flags_held,
+          COALESCE(MAX(flag_hold.held_ms), 0)
+            + COALESCE(FLOOR(SUM(EXTRACT(EPOCH FROM NOW() - grid_square.flag_held_since) * 1000)), 0)::BIGINT AS held_ms
+        FROM team
+        LEFT JOIN grid_square ON grid_square.game_id = team.game_id AND grid_square.flag AND grid_square.owner_id = team.id
+        LEFT JOIN flag_hold ON flag_hold.game_id = team.game_id AND flag_hold.team_id = team.id
+        WHERE team.game_id = $1
+        GROUP BY team.id
+      ) AS standing;
+    "
+  );
+
+  let (Json(standings),): (Json<Vec<FlagStanding>>,) = sqlx::query_as(query).bind(game_id).fetch_one(&mut *conn).await?;
+  Ok(standings)
+}
+
+pub async fn try_query_flags(pool: &PgPool, request: QueryFlagsRequest) -> Result<QueryFlagsResponse> {
+  let mut conn = pool.acquire().await?;
+
+  let query = sql!(
+    "
+      SELECT
+        game.flag_hold_ms,
+        COALESCE(
+          (
+            SELECT json_agg(
+              json_build_object(
+                'row_index', row_index,
+                'column_index', column_index,
+                'owner_id', owner_id,
+                'held_since', flag_held_since
+              )
+              ORDER BY row_index, column_index
+            )
+            FROM grid_square
+            WHERE game_id = $1 AND flag
+          ),
+          '[]'
+        )
+      FROM game
+      WHERE id = $1;
+    "
+  );
+
+  let (hold_ms, Json(flags)): (Option<i64>, Json<Vec<Flag>>) = sqlx::query_as(query)
+    .bind(request.game_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::InvalidGameId {
+      game_id: request.game_id,
+    })?;
+
+  let hold_ms = hold_ms.ok_or_else(|| Error::InvalidRequest {
+    reason: format!("game {} isn't a capture the flag game", request.game_id),
+  })?;
+
+  let standings = standings(&mut conn, request.game_id).await?;
+
+  Ok(QueryFlagsResponse {
+    flags,
+    standings,
+    hold_ms,
+  })
+}
+
+/// The team that has won a capture the flag game, by holding every flag or by holding flags for long
+/// enough. The longest total wins if several teams got there, then the lowest id.
+fn winner(standings: &[FlagStanding], flag_count: i64, hold_ms: i64) -> Option<i32> {
+  if let Some(standing) = standings.iter().find(|standing| standing.flags_held == flag_count) {
+    return Some(standing.team_id);
+  }
+
+  standings
+    .iter()
+    .filter(|standing| standing.held_ms >= hold_ms)
+    .max_by_key(|standing| (standing.held_ms, -standing.team_id))
+    .map(|standing| standing.team_id)
+}
+
+/// Ends a started capture the flag game once a team has won it, stopping every flag's clock and
+/// recording the winner. Returns whether the game ended.
+///
+/// Like eliminations, it's checked around the commands that could decide the game, and the game is
+/// only locked when a read without locks finds a winner.
+pub(crate) async fn settle_capture_the_flag(pool: &PgPool, bus: &EventBus, game_id: i32) -> Result<bool> {
+  let started: &'static str = GameStatus::Started.into();
+  let capture_the_flag: &'static str = GameMode::CaptureTheFlag.into();
+
+  let query = sql!(
+    "
+      SELECT flag_count, flag_hold_ms
+      FROM game
+      WHERE id = $1 AND status = $2 AND mode = $3;
+    "
+  );
+
+  let mut conn = pool.acquire().await?;
+  let found: Option<(i64, i64)> = sqlx::query_as(query)
+    .bind(game_id)
+    .bind(started)
+    .bind(capture_the_flag)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+  let Some((flag_count, hold_ms)) = found else {
+    return Ok(false);
+  };
+
+  if winner(&standings(&mut conn, game_id).await?, flag_count, hold_ms).is_none() {
+    return Ok(false);
+  }
+  drop(conn);
+
+  let mut tx = pool.begin().await?;
+
+  // a statement of its own, so the standings below include every command that finished while waiting for the lock
+  let query = sql!("SELECT id FROM game WHERE id = $1 AND status = $2 FOR UPDATE;");
+  if sqlx::query(query)
+    .bind(game_id)
+    .bind(started)
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_none()
+  {
+    return Ok(false);
+  }
+
+  let Some(winner) = winner(&standings(&mut tx, game_id).await?, flag_count, hold_ms) else {
+    return Ok(false);
+  };
+
+  let ended: &'static str = GameStatus::Ended.into();
+  let query = sql!("UPDATE game SET status = $2, winner_id = $3 WHERE id = $1;");
+  sqlx::query(query)
+    .bind(game_id)
+    .bind(ended)
+    .bind(winner)
+    .execute(&mut *tx)
+    .await?;
+
+  // the time flags were held for up to now is banked as their clocks stop
+  let query = sql!(
+    "
+      UPDATE grid_square
+      SET flag_held_since = NULL
+      WHERE game_id = $1 AND flag_held_since IS NOT NULL;
+    "
+  );
+  sqlx::query(query).bind(game_id).execute(&mut *tx).await?;
+
+  let event = NewEvent::new(EventKind::GameEnded, game_id, Some(winner));
+  append_event(&mut tx, &event, None).await?;
+  event_bus::notify(&mut tx, bus, &GameEvent::GameEnded { game_id }).await?;
+
+  tx.commit().await?;
+
+  Ok(true)
+}
@@ -1,22 +1,42 @@
 use crate::commands::REQUESTS_COUNT;
-use crate::games::create_random_hex;
-use crate::types::{Error, GameStatus, Json, PgPool, Result, TeamRole};
+use crate::event_log::{commit_with_event, NewEvent};
+use crate::keys::KeyGenerator;
+use crate::types::{Error, EventKind, GameStatus, Json, PgPool, Result, TeamRole};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct JoinExistingRequest {
   pub game_id: i32,
   pub display_name: String,
   pub team_role: TeamRole,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct JoinExistingResponse {
   pub team_id: i32,
   pub team_key: String,
 }
 
-pub async fn try_join_an_existing_game(pool: &PgPool, request: JoinExistingRequest) -> Result<JoinExistingResponse> {
+pub async fn try_join_an_existing_game(
+  pool: &PgPool,
+  key_generator: &dyn KeyGenerator,
+  request: JoinExistingRequest,
+) -> Result<JoinExistingResponse> {
+  let mut tx = pool.begin().await?;
+  let result = join_existing(&mut tx, key_generator, &request).await;
+  let team_id = result.as_ref().ok().map(|response| response.team_id);
+  let event = NewEvent::new(EventKind::TeamJoined, request.game_id, team_id);
+
+  commit_with_event(pool, tx, result, event).await
+}
+
+async fn join_existing(
+  conn: &mut PgConnection,
+  key_generator: &dyn KeyGenerator,
+  request: &JoinExistingRequest,
+) -> Result<JoinExistingResponse> {
   // try find game (otherwise err_invalid_game_id)
   // proposed = create new team
   // if game.status != waiting_for_reg:
@@ -26,7 +46,7 @@ pub async fn try_join_an_existing_game(pool: &PgPool, request: JoinExistingReque
 
   let expected_status: &'static str = GameStatus::WaitingForRegistrations.into();
   let role: &'static str = request.team_role.into();
-  let team_key = create_random_hex().await?;
+  let team_key = key_generator.generate_key()?;
 
   let query = sql!(
     "
@@ -60,12 +80,12 @@ pub async fn try_join_an_existing_game(pool: &PgPool, request: JoinExistingReque
 
   let row: (Option<i32>, Option<String>, Option<Json<GameStatus>>) = sqlx::query_as(query)
     .bind(request.game_id)
-    .bind(request.display_name)
+    .bind(&request.display_name)
     .bind(team_key.as_str())
     .bind(role)
     .bind(REQUESTS_COUNT)
     .bind(expected_status)
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
   match row {
@@ -1,21 +1,55 @@
+use crate::rules::MinePenalty;
+
 mod attack;
+mod auth_failures;
+mod batch;
+mod command;
+mod cooldown;
 mod create_and_join;
 mod defend;
+mod elimination;
+mod flags;
 mod join_existing;
 mod place_mine;
 mod query;
+mod replay;
+mod snapshot;
 mod start;
 
 pub use attack::{try_attack_a_square, AttackRequest, AttackResponse};
+pub use auth_failures::{try_query_auth_failures, QueryAuthFailuresRequest, QueryAuthFailuresResponse};
+pub use batch::{try_run_batch, BatchItemResult, BatchMode, BatchOperation, BatchRequest, BatchResponse};
+pub use command::{Command, CommandKind, CommandResponse};
 pub use create_and_join::{try_create_and_join_a_game, CreateAndJoinRequest, CreateAndJoinResponse};
 pub use defend::{try_defend_a_square, DefendRequest, DefendResponse};
+pub(crate) use elimination::{eliminate_teams, games_to_settle};
+pub(crate) use flags::settle_capture_the_flag;
+pub use flags::{try_query_flags, Flag, FlagStanding, QueryFlagsRequest, QueryFlagsResponse};
 pub use join_existing::{try_join_an_existing_game, JoinExistingRequest, JoinExistingResponse};
 pub use place_mine::{try_place_a_mine, PlaceMineRequest, PlaceMineResponse};
 pub use query::{
-  try_query_game, try_query_grid_square, QueryGameRequest, QueryGameResponse, QueryGridResponse, QueryGridSquareRequest,
-  QueryGridSquareResponse,
+  try_query_events, try_query_game, try_query_grid, try_query_grid_changes, try_query_grid_square, try_query_load,
+  QueryEventsRequest, QueryEventsResponse, QueryGameRequest, QueryGameResponse, QueryGridChangesRequest,
+  QueryGridChangesResponse, QueryGridRequest, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse,
+  QueryLoadResponse,
+};
+pub use replay::{
+  try_replay_game, try_verify_game, ReplayGameRequest, ReplayGameResponse, VerifyGameRequest, VerifyGameResponse,
+};
+pub use snapshot::{
+  try_export_game, try_import_game, ExportGameRequest, ExportGameResponse, ImportGameRequest, ImportGameResponse, ImportedTeam,
 };
 pub use start::{try_start, StartRequest, StartResponse};
 
 pub const REQUESTS_COUNT: i32 = 30;
+pub const GRID_SIZE: i32 = 5;
 pub const GRID_SQUARE_DEFAULT_HEALTH: i32 = 60;
+pub const CONQUERED_SQUARE_HEALTH: i32 = 120;
+/// Home squares start out sturdier than conquered ones, so a team isn't knocked out of the game as
+/// soon as it starts. Defends don't take them past `CONQUERED_SQUARE_HEALTH` again once attacked.
+pub const HOME_SQUARE_HEALTH: i32 = 2 * CONQUERED_SQUARE_HEALTH;
+pub const DEFAULT_FLAG_COUNT: u32 = 3;
+pub const DEFAULT_FLAG_HOLD_MS: u32 = 2 * 60 * 1000;
+pub const MINE_PENALTY: MinePenalty = MinePenalty::AllRequests;
+pub const MAX_EVENTS_PER_PAGE: i64 = 100;
+pub const MAX_BATCH_OPERATIONS: usize = REQUESTS_COUNT as usize;
@@ -1,9 +1,15 @@
+use crate::commands::cooldown::check_cooldown;
+use crate::commands::elimination::check_not_eliminated;
+use crate::event_bus::{EventBus, GameEvent};
+use crate::event_log::{commit_with_event_and_notify, NewEvent};
 use crate::types::{
-  DatabaseErrorKind, DateTimeUtc, Error, GameStatus, GridSquare, Json, Mine, PgPool, Result, SenderDetails, TeamRole,
+  DatabaseErrorKind, DateTimeUtc, Error, EventKind, GameStatus, GridSquare, Json, Mine, PgPool, Result, SenderDetails, TeamRole,
 };
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlaceMineRequest {
   pub game_id: i32,
   pub sender: SenderDetails,
@@ -11,13 +17,32 @@ pub struct PlaceMineRequest {
   pub column_index: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlaceMineResponse {
   pub square: GridSquare,
   pub requests_left: i32,
 }
 
-pub async fn try_place_a_mine(pool: &PgPool, request: PlaceMineRequest) -> Result<PlaceMineResponse> {
+pub async fn try_place_a_mine(pool: &PgPool, bus: &EventBus, request: PlaceMineRequest) -> Result<PlaceMineResponse> {
+  let mut tx = pool.begin().await?;
+  let result = place_mine(&mut tx, &request).await;
+
+  let mut event = NewEvent::new(EventKind::MinePlaced, request.game_id, Some(request.sender.team_id))
+    .at(request.row_index, request.column_index);
+  if let Ok(response) = &result {
+    event = event.with_square(&response.square).with_requests_left(response.requests_left);
+  }
+
+  commit_with_event_and_notify(pool, tx, result, event, bus, |response| {
+    GameEvent::mine_placed(request.game_id, request.sender.team_id, response)
+  })
+  .await
+}
+
+async fn place_mine(conn: &mut PgConnection, request: &PlaceMineRequest) -> Result<PlaceMineResponse> {
+  check_cooldown(conn, request.game_id, &request.sender).await?;
+  check_not_eliminated(conn, request.game_id, &request.sender).await?;
+
   // if team found and creds ok and game found and game status is started:
   //    and team_role is minelayer
   //    and role not used
@@ -32,7 +57,7 @@ pub async fn try_place_a_mine(pool: &PgPool, request: PlaceMineRequest) -> Resul
           FROM game
           WHERE id = $1
           LIMIT 1
-          FOR UPDATE
+          FOR SHARE
         ),
         found_team AS (
           SELECT *
@@ -83,7 +108,7 @@ pub async fn try_place_a_mine(pool: &PgPool, request: PlaceMineRequest) -> Resul
           FROM found_square
           WHERE (SELECT error_kind IS NULL FROM err)
           ON CONFLICT (game_id, square_id) DO UPDATE
-          SET owner_id = $2
+          SET owner_id = $2, triggerer_id = NULL
         ),
         collated AS (
           SELECT
@@ -131,7 +156,7 @@ pub async fn try_place_a_mine(pool: &PgPool, request: PlaceMineRequest) -> Resul
     .bind::<&'static str>(TeamRole::Minelayer.into())
     .bind::<&'static str>(DatabaseErrorKind::InvalidTeamRole.into())
     .bind::<&'static str>(DatabaseErrorKind::RoleAlreadyUsed.into())
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
   error_kind
@@ -156,6 +181,9 @@ pub async fn try_place_a_mine(pool: &PgPool, request: PlaceMineRequest) -> Resul
         team_role: team_role.map_or(TeamRole::Spy, |Json(team_role)| team_role),
       },
       DatabaseErrorKind::RoleAlreadyUsed => Error::RoleAlreadyUsed,
+      DatabaseErrorKind::SquareNotReachable => Error::Unexpected {
+        message: "failed to place mine",
+      },
     })
     .map_or(Ok(()), Err)?;
 
@@ -1,30 +1,118 @@
-use crate::types::{DateTimeUtc, Error, Game, GameStatus, GridSquare, Json, PgPool, Result, Team};
+use crate::commands::MAX_EVENTS_PER_PAGE;
+use crate::types::{DateTimeUtc, Error, Event, Game, GameOptions, GameStatus, GridSquare, Json, PgPool, Result, Team};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QueryGameRequest {
   pub game_id: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QueryGameResponse {
   pub game: Game,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QueryGridSquareRequest {
   pub game_id: i32,
   pub row_index: i32,
   pub column_index: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QueryGridSquareResponse {
   pub square: GridSquare,
 }
 
-#[derive(Debug)]
-pub struct QueryGridResponse {}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryGridRequest {
+  pub game_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryGridResponse {
+  pub grid: Vec<GridSquare>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryGridChangesRequest {
+  pub game_id: i32,
+  /// Only squares that changed after this version are returned, pass `0` to get every square.
+  pub since_version: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryGridChangesResponse {
+  pub squares: Vec<GridSquare>,
+  /// Version of the grid as of this response, to pass as `since_version` next time.
+  pub version: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryEventsRequest {
+  pub game_id: i32,
+  /// Only events with a greater sequence number are returned, pass `0` to start from the beginning.
+  pub after_sequence: i64,
+  /// Capped at `MAX_EVENTS_PER_PAGE`.
+  pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryEventsResponse {
+  pub events: Vec<Event>,
+  /// Sequence number to pass as `after_sequence` to fetch the next page.
+  pub last_sequence: i64,
+}
+
+pub async fn try_query_grid(pool: &PgPool, request: QueryGridRequest) -> Result<QueryGridResponse> {
+  let QueryGameResponse { game } = try_query_game(
+    pool,
+    QueryGameRequest {
+      game_id: request.game_id,
+    },
+  )
+  .await?;
+  Ok(QueryGridResponse { grid: game.grid })
+}
+
+pub async fn try_query_grid_changes(pool: &PgPool, request: QueryGridChangesRequest) -> Result<QueryGridChangesResponse> {
+  let query = sql!(
+    "
+      SELECT
+        COALESCE(
+          (
+            SELECT grid_version.version
+            FROM grid_version
+            WHERE grid_version.game_id = $1
+          ),
+          0
+        ),
+        COALESCE(
+          (
+            SELECT json_agg(grid_square.* ORDER BY grid_square.row_index, grid_square.column_index)
+            FROM grid_square
+            INNER JOIN grid_square_version ON grid_square_version.square_id = grid_square.id
+            WHERE grid_square_version.game_id = $1 AND grid_square_version.version > $2
+          ),
+          '[]'
+        )
+      FROM game
+      WHERE game.id = $1;
+    "
+  );
+
+  let (version, Json(squares)): (i64, Json<Vec<GridSquare>>) = sqlx::query_as(query)
+    .bind(request.game_id)
+    .bind(request.since_version)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::InvalidGameId {
+      game_id: request.game_id,
+    })?;
+
+  Ok(QueryGridChangesResponse { squares, version })
+}
 
 pub async fn try_query_grid_square(pool: &PgPool, request: QueryGridSquareRequest) -> Result<QueryGridSquareResponse> {
   let query = sql!(
@@ -87,7 +175,18 @@ pub async fn try_query_game(pool: &PgPool, request: QueryGameRequest) -> Result<
             game.id,
             game.created_at,
             to_json(game.status) AS status,
-            current_teams.teams AS teams, 
+            json_build_object(
+              'command_interval_ms', game.command_interval_ms,
+              'attack_range', game.attack_range,
+              'home_squares_seed', game.home_squares_seed,
+              'elimination_grace_ms', game.elimination_grace_ms,
+              'mode', game.mode,
+              'flag_count', game.flag_count,
+              'flag_hold_ms', game.flag_hold_ms,
+              'flags_seed', game.flags_seed
+            ) AS options,
+            game.winner_id,
+            current_teams.teams AS teams,
             grid.grid_squares AS grid
           FROM game, current_teams, grid
           WHERE game.id = $1
@@ -97,14 +196,24 @@ pub async fn try_query_game(pool: &PgPool, request: QueryGameRequest) -> Result<
     "
   );
 
-  type Row = (i32, DateTimeUtc, Json<GameStatus>, Json<Vec<Team>>, Json<Vec<GridSquare>>);
+  type Row = (
+    i32,
+    DateTimeUtc,
+    Json<GameStatus>,
+    Json<GameOptions>,
+    Option<i32>,
+    Json<Vec<Team>>,
+    Json<Vec<GridSquare>>,
+  );
 
-  let (game_id, created_at, Json(status), Json(teams), Json(grid)): Row =
+  let (game_id, created_at, Json(status), Json(options), winner_id, Json(teams), Json(grid)): Row =
     sqlx::query_as(query).bind(request.game_id).fetch_one(pool).await?;
 
   let game = Game {
     id: game_id,
     created_at,
+    options,
+    winner_id,
     grid,
     status,
     teams,
@@ -112,3 +221,82 @@ pub async fn try_query_game(pool: &PgPool, request: QueryGameRequest) -> Result<
 
   Ok(QueryGameResponse { game })
 }
+
+pub async fn try_query_events(pool: &PgPool, request: QueryEventsRequest) -> Result<QueryEventsResponse> {
+  let query = sql!(
+    "
+      WITH
+        found_game AS (
+          SELECT id
+          FROM game
+          WHERE id = $1
+        ),
+        page AS (
+          SELECT json_agg(selected.* ORDER BY selected.sequence) AS events
+          FROM (
+            SELECT *
+            FROM event
+            WHERE game_id = $1 AND sequence > $2
+            ORDER BY sequence
+            LIMIT $3
+          ) AS selected
+        )
+      SELECT found_game.id, page.events
+      FROM found_game, page;
+    "
+  );
+
+  let limit = request.limit.unwrap_or(MAX_EVENTS_PER_PAGE).clamp(1, MAX_EVENTS_PER_PAGE);
+
+  let (_, events): (i32, Option<Json<Vec<Event>>>) = sqlx::query_as(query)
+    .bind(request.game_id)
+    .bind(request.after_sequence)
+    .bind(limit)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::InvalidGameId {
+      game_id: request.game_id,
+    })?;
+
+  let events = events.map_or_else(Vec::new, |Json(events)| events);
+  let last_sequence = events.last().map_or(request.after_sequence, |event| event.sequence);
+
+  Ok(QueryEventsResponse { events, last_sequence })
+}
+
+/// How busy the server is, for monitoring rather than for teams.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryLoadResponse {
+  /// Games that haven't ended yet, whether or not they have started.
+  pub active_games: i64,
+  /// Teams in those games.
+  pub active_teams: i64,
+  pub db_connections: u32,
+  pub db_idle_connections: usize,
+  pub db_max_connections: u32,
+}
+
+pub async fn try_query_load(pool: &PgPool) -> Result<QueryLoadResponse> {
+  let query = sql!(
+    "
+      SELECT
+        COUNT(DISTINCT game.id),
+        COUNT(team.id)
+      FROM game
+      LEFT JOIN team
+      ON team.game_id = game.id
+      WHERE game.status <> $1;
+    "
+  );
+
+  let ended: &'static str = GameStatus::Ended.into();
+  let (active_games, active_teams): (i64, i64) = sqlx::query_as(query).bind(ended).fetch_one(pool).await?;
+
+  Ok(QueryLoadResponse {
+    active_games,
+    active_teams,
+    db_connections: pool.size(),
+    db_idle_connections: pool.num_idle(),
+    db_max_connections: pool.options().get_max_connections(),
+  })
+}
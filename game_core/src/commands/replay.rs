@@ -0,0 +1,98 @@
+use crate::commands::{
+  try_query_events, try_query_flags, try_query_game, QueryEventsRequest, QueryFlagsRequest, QueryGameRequest, MAX_EVENTS_PER_PAGE,
+};
+use crate::replay::{Replay, ReplayState};
+use crate::types::{GameMode, PgPool, Result};
+
+#[derive(Debug)]
+pub struct ReplayGameRequest {
+  pub game_id: i32,
+  /// Replays the whole event log when `None`.
+  pub until_sequence: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct ReplayGameResponse {
+  pub state: ReplayState,
+}
+
+#[derive(Debug)]
+pub struct VerifyGameRequest {
+  pub game_id: i32,
+}
+
+#[derive(Debug)]
+pub struct VerifyGameResponse {
+  pub state: ReplayState,
+  pub events_replayed: usize,
+}
+
+async fn load_replay(pool: &PgPool, game_id: i32) -> Result<Replay> {
+  let mut events = Vec::new();
+  let mut after_sequence = 0;
+
+  loop {
+    let page = try_query_events(
+      pool,
+      QueryEventsRequest {
+        game_id,
+        after_sequence,
+        limit: Some(MAX_EVENTS_PER_PAGE),
+      },
+    )
+    .await?;
+
+    if page.events.is_empty() {
+      break;
+    }
+
+    after_sequence = page.last_sequence;
+    events.extend(page.events);
+  }
+
+  Ok(Replay::new(game_id, events))
+}
+
+pub async fn try_replay_game(pool: &PgPool, request: ReplayGameRequest) -> Result<ReplayGameResponse> {
+  let replay = load_replay(pool, request.game_id).await?;
+  let state = replay.state_at(request.until_sequence.unwrap_or(i64::MAX))?;
+
+  Ok(ReplayGameResponse { state })
+}
+
+/// Replays the whole event log and compares the result against the stored game.
+/// Commands issued while verifying will show up as a mismatch, so this is meant for finished or paused games.
+pub async fn try_verify_game(pool: &PgPool, request: VerifyGameRequest) -> Result<VerifyGameResponse> {
+  let replay = load_replay(pool, request.game_id).await?;
+  let state = replay.final_state()?;
+  let game = try_query_game(
+    pool,
+    QueryGameRequest {
+      game_id: request.game_id,
+    },
+  )
+  .await?
+  .game;
+
+  let flags = match game.options.mode {
+    GameMode::CaptureTheFlag => try_query_flags(
+      pool,
+      QueryFlagsRequest {
+        game_id: request.game_id,
+      },
+    )
+    .await?
+    .flags
+    .iter()
+    .map(|flag| (flag.row_index, flag.column_index))
+    .collect(),
+    GameMode::Conquest => Vec::new(),
+  };
+
+  state.verify(&game, &flags)?;
+
+  Ok(VerifyGameResponse {
+    state,
+    events_replayed: replay.events().len(),
+  })
+}
@@ -0,0 +1,472 @@
+use crate::commands::{GRID_SQUARE_DEFAULT_HEALTH, HOME_SQUARE_HEALTH};
+use crate::event_log::{append_event, NewEvent};
+use crate::keys::KeyGenerator;
+use crate::snapshot::{GameDetailsSnapshot, GameSnapshot, GridSquareSnapshot, MineSnapshot, TeamSnapshot, SNAPSHOT_VERSION};
+use crate::types::{DateTimeUtc, Error, EventKind, GameOptions, GameStatus, Json, PgPool, Result};
+use postgres_syntax::sql;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct ExportGameRequest {
+  pub game_id: i32,
+}
+
+#[derive(Debug)]
+pub struct ExportGameResponse {
+  pub snapshot: GameSnapshot,
+}
+
+#[derive(Debug)]
+pub struct ImportGameRequest {
+  pub snapshot: GameSnapshot,
+}
+
+#[derive(Debug)]
+pub struct ImportedTeam {
+  pub previous_team_id: i32,
+  pub team_id: i32,
+  pub team_key: String,
+}
+
+#[derive(Debug)]
+pub struct ImportGameResponse {
+  pub game_id: i32,
+  pub teams: Vec<ImportedTeam>,
+}
+
+pub async fn try_export_game(pool: &PgPool, request: ExportGameRequest) -> Result<ExportGameResponse> {
+  let query = sql!(
+    "
+      WITH
+        current_teams AS (
+          SELECT COALESCE(
+            json_agg(
+              json_build_object(
+                'id', id,
+                'display_name', display_name,
+                'role', role,
+                'role_used', role_used,
+                'requests_left', requests_left,
+                'created_at', created_at,
+                'time_of_last_command', time_of_last_command,
+                'home_row', home_row,
+                'home_column', home_column,
+                'first_conquest_at', first_conquest_at,
+                'eliminated_at', eliminated_at,
+                'last_square_lost_at', square_loss.lost_at,
+                'flag_held_ms', COALESCE(flag_hold.held_ms, 0)
+              )
+              ORDER BY id
+            ),
+            '[]'
+          ) AS teams
+          FROM team
+          LEFT JOIN flag_hold ON flag_hold.game_id = team.game_id AND flag_hold.team_id = team.id
+          LEFT JOIN square_loss ON square_loss.game_id = team.game_id AND square_loss.team_id = team.id
+          WHERE team.game_id = $1
+        ),
+        grid AS (
+          SELECT COALESCE(
+            json_agg(
+              json_build_object(
+                'row_index', row_index,
+                'column_index', column_index,
+                'owner_id', owner_id,
+                'bonus', bonus,
+                'health', health,
+                'created_at', created_at,
+                'flag', flag,
+                'flag_held_since', flag_held_since
+              )
+              ORDER BY row_index, column_index
+            ),
+            '[]'
+          ) AS grid_squares
+          FROM grid_square
+          WHERE game_id = $1
+        ),
+        mines AS (
+          SELECT COALESCE(
+            json_agg(
+              json_build_object(
+                'row_index', grid_square.row_index,
+                'column_index', grid_square.column_index,
+                'owner_id', mine.owner_id,
+                'triggerer_id', mine.triggerer_id
+              )
+              ORDER BY mine.id
+            ),
+            '[]'
+          ) AS mines
+          FROM mine
+          INNER JOIN grid_square ON grid_square.id = mine.square_id
+          WHERE mine.game_id = $1
+        )
+      SELECT
+        game.id,
+        to_json(game.status),
+        game.created_at,
+        json_build_object(
+          'command_interval_ms', game.command_interval_ms,
+          'attack_range', game.attack_range,
+          'home_squares_seed', game.home_squares_seed,
+          'elimination_grace_ms', game.elimination_grace_ms,
+          'mode', game.mode,
+          'flag_count', game.flag_count,
+          'flag_hold_ms', game.flag_hold_ms,
+          'flags_seed', game.flags_seed
+        ),
+        game.winner_id,
+        current_teams.teams,
+        grid.grid_squares,
+        mines.mines
+      FROM game, current_teams, grid, mines
+      WHERE game.id = $1;
+    "
+  );
+
+  type Row = (
+    i32,
+    Json<GameStatus>,
+    DateTimeUtc,
+    Json<GameOptions>,
+    Option<i32>,
+    Json<Vec<TeamSnapshot>>,
+    Json<Vec<GridSquareSnapshot>>,
+    Json<Vec<MineSnapshot>>,
+  );
+
+  let (id, Json(status), created_at, Json(options), winner_id, Json(teams), Json(grid), Json(mines)): Row = sqlx::query_as(query)
+    .bind(request.game_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::InvalidGameId {
+      game_id: request.game_id,
+    })?;
+
+  let snapshot = GameSnapshot {
+    version: SNAPSHOT_VERSION,
+    game: GameDetailsSnapshot {
+      id,
+      status,
+      created_at,
+      options,
+      winner_id,
+    },
+    teams,
+    grid,
+    mines,
+  };
+
+  Ok(ExportGameResponse { snapshot })
+}
+
+pub async fn try_import_game(
+  pool: &PgPool,
+  key_generator: &dyn KeyGenerator,
+  request: ImportGameRequest,
+) -> Result<ImportGameResponse> {
+  let snapshot = request.snapshot;
+  snapshot.validate()?;
+
+  let mut tx = pool.begin().await?;
+
+  let query = sql!(
+    "
+      INSERT INTO game (
+        status, created_at, command_interval_ms, attack_range, home_squares_seed, elimination_grace_ms, mode, flag_count,
+        flag_hold_ms, flags_seed
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+      RETURNING id;
+    "
+  );
+
+  let (game_id,): (i32,) = sqlx::query_as(query)
+    .bind::<&'static str>(snapshot.game.status.into())
+    .bind(snapshot.game.created_at)
+    .bind(snapshot.game.options.command_interval_ms.map(i64::from))
+    .bind::<&'static str>(snapshot.game.options.attack_range.into())
+    .bind(snapshot.game.options.home_squares_seed.map(i64::from))
+    .bind(snapshot.game.options.elimination_grace_ms.map(i64::from))
+    .bind::<&'static str>(snapshot.game.options.mode.into())
+    .bind(snapshot.game.options.flag_count.map(i64::from))
+    .bind(snapshot.game.options.flag_hold_ms.map(i64::from))
+    .bind(snapshot.game.options.flags_seed.map(i64::from))
+    .fetch_one(&mut *tx)
+    .await?;
+
+  let query = sql!(
+    "
+      INSERT INTO team (
+        game_id, display_name, key, role, role_used, requests_left, created_at, time_of_last_command, home_row, home_column,
+        first_conquest_at, eliminated_at
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+      RETURNING id;
+    "
+  );
+
+  let mut teams = Vec::with_capacity(snapshot.teams.len());
+
+  for team in &snapshot.teams {
+    let team_key = key_generator.generate_key()?;
+
+    let (team_id,): (i32,) = sqlx::query_as(query)
+      .bind(game_id)
+      .bind(&team.display_name)
+      .bind(&team_key)
+      .bind::<&'static str>(team.role.into())
+      .bind(team.role_used)
+      .bind(team.requests_left)
+      .bind(team.created_at)
+      .bind(team.time_of_last_command)
+      .bind(team.home_row)
+      .bind(team.home_column)
+      .bind(team.first_conquest_at)
+      .bind(team.eliminated_at)
+      .fetch_one(&mut *tx)
+      .await?;
+
+    teams.push(ImportedTeam {
+      previous_team_id: team.id,
+      team_id,
+      team_key,
+    });
+  }
+
+  let team_ids = teams
+    .iter()
+    .map(|team| (team.previous_team_id, team.team_id))
+    .collect::<HashMap<_, _>>();
+
+  let query = sql!(
+    "
+      INSERT INTO flag_hold (game_id, team_id, held_ms)
+      VALUES ($1, $2, $3);
+    "
+  );
+
+  for team in snapshot.teams.iter().filter(|team| team.flag_held_ms > 0) {
+    sqlx::query(query)
+      .bind(game_id)
+      .bind(team_ids[&team.id])
+      .bind(team.flag_held_ms)
+      .execute(&mut *tx)
+      .await?;
+  }
+
+  let query = sql!(
+    "
+      INSERT INTO square_loss (game_id, team_id, lost_at)
+      VALUES ($1, $2, $3);
+    "
+  );
+
+  for team in &snapshot.teams {
+    let Some(lost_at) = team.last_square_lost_at else {
+      continue;
+    };
+    sqlx::query(query)
+      .bind(game_id)
+      .bind(team_ids[&team.id])
+      .bind(lost_at)
+      .execute(&mut *tx)
+      .await?;
+  }
+
+  let squares = snapshot
+    .grid
+    .iter()
+    .map(|square| {
+      serde_json::json!({
+        "row_index": square.row_index,
+        "column_index": square.column_index,
+        "owner_id": square.owner_id.map(|owner_id| team_ids[&owner_id]),
+        "bonus": square.bonus,
+        "health": square.health,
+        "created_at": square.created_at,
+        "flag": square.flag,
+        "flag_held_since": square.flag_held_since,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let query = sql!(
+    "
+      INSERT INTO grid_square (game_id, row_index, column_index, owner_id, bonus, health, created_at, flag, flag_held_since)
+      SELECT
+        $1, parsed.row_index, parsed.column_index, parsed.owner_id, parsed.bonus, parsed.health, parsed.created_at, parsed.flag,
+        parsed.flag_held_since
+      FROM jsonb_to_recordset($2) AS parsed(
+        row_index INTEGER,
+        column_index INTEGER,
+        owner_id INTEGER,
+        bonus INTEGER,
+        health INTEGER,
+        created_at TIMESTAMPTZ,
+        flag BOOLEAN,
+        flag_held_since TIMESTAMPTZ
+      );
+    "
+  );
+
+  sqlx::query(query)
+    .bind(game_id)
+    .bind(serde_json::Value::Array(squares))
+    .execute(&mut *tx)
+    .await?;
+
+  let mines = snapshot
+    .mines
+    .iter()
+    .map(|mine| {
+      serde_json::json!({
+        "row_index": mine.row_index,
+        "column_index": mine.column_index,
+        "owner_id": team_ids[&mine.owner_id],
+        "triggerer_id": mine.triggerer_id.map(|triggerer_id| team_ids[&triggerer_id]),
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let query = sql!(
+    "
+      INSERT INTO mine (square_id, game_id, owner_id, triggerer_id)
+      SELECT grid_square.id, $1, parsed.owner_id, parsed.triggerer_id
+      FROM jsonb_to_recordset($2) AS parsed(row_index INTEGER, column_index INTEGER, owner_id INTEGER, triggerer_id INTEGER)
+      INNER JOIN grid_square
+      ON grid_square.game_id = $1
+        AND grid_square.row_index = parsed.row_index
+        AND grid_square.column_index = parsed.column_index;
+    "
+  );
+
+  sqlx::query(query)
+    .bind(game_id)
+    .bind(serde_json::Value::Array(mines))
+    .execute(&mut *tx)
+    .await?;
+
+  // teams only get their new ids once the game exists
+  if let Some(winner) = imported_winner(&snapshot) {
+    let query = sql!("UPDATE game SET winner_id = $2 WHERE id = $1;");
+    sqlx::query(query)
+      .bind(game_id)
+      .bind(team_ids[&winner])
+      .execute(&mut *tx)
+      .await?;
+  }
+
+  append_imported_history(&mut tx, game_id, &snapshot, &team_ids).await?;
+
+  tx.commit().await?;
+
+  Ok(ImportGameResponse { game_id, teams })
+}
+
+/// Starts the event log of an imported game with the history that leads to its imported state, so it
+/// can be replayed and verified like any other game. Teams join and get their home squares as they
+/// would have, then the squares, mines and requests that came from playing are set as they were.
+async fn append_imported_history(
+  conn: &mut PgConnection,
+  game_id: i32,
+  snapshot: &GameSnapshot,
+  team_ids: &HashMap<i32, i32>,
+) -> Result<()> {
+  let mut events = Vec::new();
+
+  let flags = snapshot
+    .grid
+    .iter()
+    .filter(|square| square.flag)
+    .map(|square| (square.row_index, square.column_index))
+    .collect::<Vec<_>>();
+  for (index, team) in snapshot.teams.iter().enumerate() {
+    let event = match index {
+      0 => NewEvent::new(EventKind::GameCreated, game_id, Some(team_ids[&team.id])).with_flags(flags.clone()),
+      _ => NewEvent::new(EventKind::TeamJoined, game_id, Some(team_ids[&team.id])),
+    };
+    events.push(event);
+  }
+
+  let homes = snapshot
+    .teams
+    .iter()
+    .filter_map(|team| Some((team.id, team.home_row?, team.home_column?)))
+    .collect::<Vec<_>>();
+  for (team_id, row, column) in &homes {
+    let event = NewEvent::new(EventKind::HomeSquareAssigned, game_id, Some(team_ids[team_id]))
+      .at(*row, *column)
+      .with_owner_and_health(Some(team_ids[team_id]), HOME_SQUARE_HEALTH);
+    events.push(event);
+  }
+
+  let started = snapshot.game.status != GameStatus::WaitingForRegistrations;
+  if let Some(host) = snapshot.teams.first().filter(|_| started) {
+    events.push(NewEvent::new(EventKind::GameStarted, game_id, Some(team_ids[&host.id])));
+  }
+
+  // home squares are included even if nothing has changed since, they may have been lost and won back
+  let changed = snapshot.grid.iter().filter(|square| {
+    square.owner_id.is_some()
+      || square.health != GRID_SQUARE_DEFAULT_HEALTH
+      || homes
+        .iter()
+        .any(|(_, row, column)| (*row, *column) == (square.row_index, square.column_index))
+  });
+  for square in changed {
+    let owner_id = square.owner_id.map(|owner_id| team_ids[&owner_id]);
+    let event = NewEvent::new(EventKind::SquareImported, game_id, None)
+      .at(square.row_index, square.column_index)
+      .with_owner_and_health(owner_id, square.health);
+    events.push(event);
+  }
+
+  for mine in &snapshot.mines {
+    let event = NewEvent::new(EventKind::MineImported, game_id, Some(team_ids[&mine.owner_id]))
+      .at(mine.row_index, mine.column_index)
+      .with_triggered_by(mine.triggerer_id.map(|triggerer_id| team_ids[&triggerer_id]));
+    events.push(event);
+  }
+
+  for team in &snapshot.teams {
+    let event = NewEvent::new(EventKind::TeamImported, game_id, Some(team_ids[&team.id])).with_requests_left(team.requests_left);
+    events.push(event);
+  }
+
+  if started {
+    for team in snapshot.teams.iter().filter(|team| team.eliminated_at.is_some()) {
+      events.push(NewEvent::new(EventKind::TeamEliminated, game_id, Some(team_ids[&team.id])));
+    }
+  }
+
+  if snapshot.game.status == GameStatus::Ended {
+    let winner = imported_winner(snapshot).map(|winner| team_ids[&winner]);
+    events.push(NewEvent::new(EventKind::GameEnded, game_id, winner));
+  }
+
+  for event in &events {
+    append_event(conn, event, None).await?;
+  }
+
+  Ok(())
+}
+
+/// The team that won an ended game, by its id in the snapshot. Snapshots taken before games recorded
+/// their winner only say so when a single team was left standing.
+fn imported_winner(snapshot: &GameSnapshot) -> Option<i32> {
+  if snapshot.game.status != GameStatus::Ended {
+    return None;
+  }
+  if let Some(winner_id) = snapshot.game.winner_id {
+    return Some(winner_id);
+  }
+
+  let mut standing = snapshot.teams.iter().filter(|team| team.eliminated_at.is_none());
+  match (standing.next(), standing.next()) {
+    (Some(team), None) => Some(team.id),
+    _ => None,
+  }
+}
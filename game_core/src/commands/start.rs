@@ -1,19 +1,39 @@
-use crate::types::{Error, GameStatus, Json, PgPool, Result, SenderDetails};
+use crate::commands::{GRID_SIZE, HOME_SQUARE_HEALTH};
+use crate::event_bus::{EventBus, GameEvent};
+use crate::event_log::{append_event, commit_with_event_and_notify, NewEvent};
+use crate::home_squares;
+use crate::types::{Error, EventKind, GameStatus, Json, PgPool, Result, SenderDetails};
 use postgres_syntax::sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StartRequest {
   pub game_id: i32,
   pub sender: SenderDetails,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StartResponse {
   pub game_id: i32,
   pub status: GameStatus,
 }
 
-pub async fn try_start(pool: &PgPool, request: StartRequest) -> Result<StartResponse> {
+pub async fn try_start(pool: &PgPool, bus: &EventBus, request: StartRequest) -> Result<StartResponse> {
+  let mut tx = pool.begin().await?;
+  let result = match start(&mut tx, &request).await {
+    Ok(response) => assign_home_squares(&mut tx, request.game_id).await.map(|()| response),
+    Err(error) => Err(error),
+  };
+  let event = NewEvent::new(EventKind::GameStarted, request.game_id, Some(request.sender.team_id));
+
+  commit_with_event_and_notify(pool, tx, result, event, bus, |response| GameEvent::GameStarted {
+    game_id: response.game_id,
+  })
+  .await
+}
+
+async fn start(conn: &mut PgConnection, request: &StartRequest) -> Result<StartResponse> {
   let expected_status: &'static str = GameStatus::WaitingForRegistrations.into();
   let next_status: &'static str = GameStatus::Started.into();
 
@@ -76,7 +96,7 @@ pub async fn try_start(pool: &PgPool, request: StartRequest) -> Result<StartResp
     .bind(expected_status)
     .bind(request.sender.team_id)
     .bind(&request.sender.team_key)
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
   match row {
@@ -103,3 +123,81 @@ pub async fn try_start(pool: &PgPool, request: StartRequest) -> Result<StartResp
     }),
   }
 }
+
+/// Gives every team its home square, in games created with a seed for them. Runs in the same
+/// transaction as the start, so teams never see a started game without them. Owning a home square
+/// counts as a first conquest, so elimination grace periods start with the game.
+async fn assign_home_squares(conn: &mut PgConnection, game_id: i32) -> Result<()> {
+  let query = sql!(
+    "
+      SELECT game.home_squares_seed, COALESCE(array_agg(team.id ORDER BY team.id), '{}')
+      FROM game
+      INNER JOIN team ON team.game_id = game.id
+      WHERE game.id = $1
+      GROUP BY game.id;
+    "
+  );
+
+  let (seed, team_ids): (Option<i64>, Vec<i32>) = sqlx::query_as(query).bind(game_id).fetch_one(&mut *conn).await?;
+  let Some(seed) = seed else {
+    return Ok(());
+  };
+
+  let seed = u32::try_from(seed).map_err(|_| Error::Unexpected {
+    message: "home squares seed is out of range",
+  })?;
+  // home squares would otherwise hand out flags before anyone has fought for them
+  let query = sql!("SELECT row_index, column_index FROM grid_square WHERE game_id = $1 AND flag;");
+  let flags: Vec<(i32, i32)> = sqlx::query_as(query).bind(game_id).fetch_all(&mut *conn).await?;
+
+  let assigned = home_squares::allocate(GRID_SIZE, &team_ids, seed, &flags)?;
+
+  let homes = assigned
+    .iter()
+    .map(|home| {
+      serde_json::json!({
+        "team_id": home.team_id,
+        "row_index": home.row,
+        "column_index": home.column,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  let query = sql!(
+    "
+      WITH
+        parsed AS (
+          SELECT *
+          FROM jsonb_to_recordset($2) AS parsed(team_id INTEGER, row_index INTEGER, column_index INTEGER)
+        ),
+        updated_team AS (
+          UPDATE team
+          SET home_row = parsed.row_index, home_column = parsed.column_index, first_conquest_at = NOW()
+          FROM parsed
+          WHERE team.id = parsed.team_id AND team.game_id = $1
+        )
+      UPDATE grid_square
+      SET owner_id = parsed.team_id, health = $3
+      FROM parsed
+      WHERE grid_square.game_id = $1
+        AND grid_square.row_index = parsed.row_index
+        AND grid_square.column_index = parsed.column_index;
+    "
+  );
+
+  sqlx::query(query)
+    .bind(game_id)
+    .bind(serde_json::Value::Array(homes))
+    .bind(HOME_SQUARE_HEALTH)
+    .execute(&mut *conn)
+    .await?;
+
+  for home in &assigned {
+    let event = NewEvent::new(EventKind::HomeSquareAssigned, game_id, Some(home.team_id))
+      .at(home.row, home.column)
+      .with_owner_and_health(Some(home.team_id), HOME_SQUARE_HEALTH);
+    append_event(conn, &event, None).await?;
+  }
+
+  Ok(())
+}
@@ -1,7 +1,11 @@
 use crate::types::{GameStatus, TeamRole};
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumDiscriminants, EnumString, IntoStaticStr};
 use thiserror::Error;
 
-#[derive(Debug, Error, PartialEq)]
+/// `ErrorCode` is the fieldless twin of `Error`, for sending errors over the wire.
+#[derive(Debug, Error, PartialEq, IntoStaticStr, EnumDiscriminants)]
+#[strum_discriminants(name(ErrorCode), derive(Hash, IntoStaticStr, EnumString, Serialize, Deserialize))]
 pub enum Error {
   #[error("Invalid coordinates row = {row}, column = {column}")]
   InvalidCoordinates { row: i32, column: i32 },
@@ -16,6 +20,9 @@ pub enum Error {
   #[error("Invalid credentials. Please recheck team_key and team_id")]
   InvalidCredentials,
 
+  #[error("Square row = {row}, column = {column} is out of reach, attack squares next to ones your team owns.")]
+  SquareNotReachable { row: i32, column: i32 },
+
   #[error("Invalid team role")]
   InvalidTeamRole,
 
@@ -29,7 +36,7 @@ pub enum Error {
   InvalidGameId { game_id: i32 },
 
   #[error("Failed to attack square, please recheck request.")]
-  FailedToAttackSquare,
+  FailedToAttackSquare { source: DatabaseCause },
 
   #[error("Failed to defend square, please recheck request.")]
   FailedToDefendSquare,
@@ -67,16 +74,104 @@ pub enum Error {
   #[error("Your team has already used its role. Roles can only be used once.")]
   RoleAlreadyUsed,
 
-  #[error("Failed to connect to database {cause}")]
-  FailedToConnectToDatabase { cause: String },
+  #[error("Invalid key length {length}, keys must be between 1 and {max} characters long.")]
+  InvalidKeyLength { length: usize, max: usize },
+
+  #[error("Failed to generate key {cause}")]
+  FailedToGenerateKey { cause: String },
+
+  #[error("Replay diverged from the event log at sequence {sequence}: {reason}")]
+  ReplayMismatch { sequence: i64, reason: String },
+
+  #[error("Snapshot version {version} is not supported, expected version {supported}.")]
+  UnsupportedSnapshotVersion { version: u32, supported: u32 },
+
+  #[error("Invalid snapshot: {reason}")]
+  InvalidSnapshot { reason: String },
+
+  #[error("Invalid rules: {reason}")]
+  InvalidRules { reason: String },
+
+  #[error("Invalid request: {reason}")]
+  InvalidRequest { reason: String },
+
+  #[error("Operation {index} of the batch failed, nothing was applied: {error}")]
+  BatchOperationFailed { index: usize, error: Box<Error> },
+
+  #[error("Your team ({team_id}) has been eliminated and can no longer send commands.")]
+  TeamEliminated { team_id: i32 },
+
+  #[error("Too many requests, try again in {retry_after_ms}ms")]
+  RateLimited { retry_after_ms: u64 },
 
-  #[error("Database error {cause}")]
-  DatabaseError { cause: String },
+  #[error("Your team sent a command too recently, try again in {retry_after_ms}ms")]
+  CommandTooSoon { retry_after_ms: u64 },
+
+  #[error("A command with this idempotency key is still running, or never finished. Try again later.")]
+  IdempotentCommandInProgress,
+
+  #[error("Too many failed attempts to authenticate, try again in {retry_after_ms}ms")]
+  TooManyFailedAttempts { retry_after_ms: u64 },
+
+  #[error("Failed to query authentication failures, your team ({team_id}) is not the host of this game.")]
+  OnlyHostCanQueryAuthFailures { team_id: i32 },
+
+  #[error("Failed to connect to database {source}")]
+  FailedToConnectToDatabase { source: DatabaseCause },
+
+  #[error("Database error {source}")]
+  DatabaseError { source: DatabaseCause },
 
   #[error("An unexpected error occurred: {message}")]
   Unexpected { message: &'static str },
 }
 
+impl Error {
+  pub fn code(&self) -> ErrorCode {
+    self.into()
+  }
+
+  /// Whether the error is the server's fault rather than the request's, i.e. it came from the database.
+  pub fn is_internal(&self) -> bool {
+    std::error::Error::source(self).is_some()
+  }
+}
+
+/// The sqlx error behind an `Error`, kept as its source so logs show what actually went wrong.
+/// Compared by message, since sqlx errors can't be compared themselves.
+#[derive(Debug)]
+pub struct DatabaseCause(sqlx::Error);
+
+impl DatabaseCause {
+  pub fn inner(&self) -> &sqlx::Error {
+    &self.0
+  }
+}
+
+impl From<sqlx::Error> for DatabaseCause {
+  fn from(value: sqlx::Error) -> Self {
+    Self(value)
+  }
+}
+
+impl PartialEq for DatabaseCause {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.to_string() == other.0.to_string()
+  }
+}
+
+impl std::fmt::Display for DatabaseCause {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.0.fmt(f)
+  }
+}
+
+impl std::error::Error for DatabaseCause {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.0)
+  }
+}
+
 impl std::convert::From<sqlx::Error> for Error {
   fn from(value: sqlx::Error) -> Self {
     if let sqlx::Error::Database(error) = &value {
@@ -92,9 +187,7 @@ impl std::convert::From<sqlx::Error> for Error {
         }
       }
     }
-    Self::DatabaseError {
-      cause: value.to_string(),
-    }
+    Self::DatabaseError { source: value.into() }
   }
 }
 
@@ -0,0 +1,259 @@
+use crate::types::{AttackResponse, DefendResponse, PgPool, PlaceMineResponse, Result};
+use postgres_syntax::sql;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use strum_macros::IntoStaticStr;
+use tokio::sync::broadcast;
+
+/// How many events a subscriber can fall behind before it starts missing them.
+pub const DEFAULT_EVENT_BUS_CAPACITY: usize = 256;
+
+/// Postgres channel every instance publishes its events on.
+pub const NOTIFY_CHANNEL: &str = "game_events";
+
+/// Something that happened in a game, published once the command behind it has been committed.
+///
+/// `MinePlaced` gives away where the mine is, so transports that serve other teams or spectators
+/// have to hold it back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, IntoStaticStr)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+  SquareAttacked {
+    game_id: i32,
+    team_id: i32,
+    row: i32,
+    column: i32,
+    health: i32,
+    owner_id: Option<i32>,
+  },
+  SquareConquered {
+    game_id: i32,
+    team_id: i32,
+    row: i32,
+    column: i32,
+    health: i32,
+  },
+  SquareDefended {
+    game_id: i32,
+    team_id: i32,
+    row: i32,
+    column: i32,
+    health: i32,
+    owner_id: Option<i32>,
+  },
+  MinePlaced {
+    game_id: i32,
+    team_id: i32,
+    row: i32,
+    column: i32,
+  },
+  /// An attack hit another team's mine and cost the attacker its requests instead of damaging the square.
+  MineTriggered {
+    game_id: i32,
+    team_id: i32,
+    row: i32,
+    column: i32,
+    placed_by: i32,
+    requests_left: i32,
+  },
+  GameStarted {
+    game_id: i32,
+  },
+  /// The team owned nothing once its grace period was over, and can't send commands anymore.
+  TeamEliminated {
+    game_id: i32,
+    team_id: i32,
+  },
+  /// Eliminations left at most one team standing, or a team won a capture the flag game by holding
+  /// its flags.
+  GameEnded {
+    game_id: i32,
+  },
+}
+
+impl GameEvent {
+  pub fn game_id(&self) -> i32 {
+    match self {
+      GameEvent::SquareAttacked { game_id, .. }
+      | GameEvent::SquareConquered { game_id, .. }
+      | GameEvent::SquareDefended { game_id, .. }
+      | GameEvent::MinePlaced { game_id, .. }
+      | GameEvent::MineTriggered { game_id, .. }
+      | GameEvent::TeamEliminated { game_id, .. }
+      | GameEvent::GameStarted { game_id }
+      | GameEvent::GameEnded { game_id } => *game_id,
+    }
+  }
+
+  pub(crate) fn attacked(game_id: i32, team_id: i32, response: &AttackResponse) -> Self {
+    let square = &response.square;
+
+    // attacks only report a mine when they trigger it
+    match &square.mine {
+      Some(mine) => GameEvent::MineTriggered {
+        game_id,
+        team_id,
+        row: square.row,
+        column: square.column,
+        placed_by: mine.placed_by,
+        requests_left: response.requests_left,
+      },
+      None if response.conquered => GameEvent::SquareConquered {
+        game_id,
+        team_id,
+        row: square.row,
+        column: square.column,
+        health: square.health,
+      },
+      None => GameEvent::SquareAttacked {
+        game_id,
+        team_id,
+        row: square.row,
+        column: square.column,
+        health: square.health,
+        owner_id: square.owner_id,
+      },
+    }
+  }
+
+  pub(crate) fn defended(game_id: i32, team_id: i32, response: &DefendResponse) -> Self {
+    GameEvent::SquareDefended {
+      game_id,
+      team_id,
+      row: response.square.row,
+      column: response.square.column,
+      health: response.square.health,
+      owner_id: response.square.owner_id,
+    }
+  }
+
+  pub(crate) fn mine_placed(game_id: i32, team_id: i32, response: &PlaceMineResponse) -> Self {
+    GameEvent::MinePlaced {
+      game_id,
+      team_id,
+      row: response.square.row,
+      column: response.square.column,
+    }
+  }
+}
+
+/// Fans game events out to everyone subscribed to that game, using one broadcast channel per game.
+///
+/// Channels are created by the first subscriber and dropped again once an event finds nobody listening.
+/// Each bus only sees the events of its own process, an `EventRelay` brings in the ones from other instances.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+  id: u64,
+  channels: Arc<Mutex<HashMap<i32, broadcast::Sender<GameEvent>>>>,
+  capacity: usize,
+}
+
+impl Default for EventBus {
+  fn default() -> Self {
+    Self::new(DEFAULT_EVENT_BUS_CAPACITY)
+  }
+}
+
+impl EventBus {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      id: rand::thread_rng().next_u64(),
+      channels: Arc::default(),
+      capacity: capacity.max(1),
+    }
+  }
+
+  /// Tells this bus apart from the ones in other instances, so a relay can skip its own notifications.
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+
+  pub fn subscribe(&self, game_id: i32) -> broadcast::Receiver<GameEvent> {
+    let mut channels = self.channels.lock().expect("event bus lock poisoned");
+    channels
+      .entry(game_id)
+      .or_insert_with(|| broadcast::channel(self.capacity).0)
+      .subscribe()
+  }
+
+  /// Sends the event to the game's subscribers, returning how many there were.
+  pub fn publish(&self, event: GameEvent) -> usize {
+    let mut channels = self.channels.lock().expect("event bus lock poisoned");
+    let game_id = event.game_id();
+
+    let Some(sender) = channels.get(&game_id) else {
+      return 0;
+    };
+
+    match sender.send(event) {
+      Ok(receivers) => receivers,
+      Err(_) => {
+        channels.remove(&game_id);
+        0
+      }
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Notification {
+  origin: u64,
+  event: GameEvent,
+}
+
+/// Sends the event to the other instances through Postgres, as part of the transaction of the command
+/// behind it. Postgres only delivers notifications once their transaction commits, so the others never
+/// hear about commands that were rolled back.
+pub(crate) async fn notify(conn: &mut PgConnection, bus: &EventBus, event: &GameEvent) -> Result<()> {
+  let payload = serde_json::to_string(&Notification {
+    origin: bus.id,
+    event: event.clone(),
+  })
+  .expect("game events always serialize");
+
+  sqlx::query(sql!("SELECT pg_notify($1, $2);"))
+    .bind(NOTIFY_CHANNEL)
+    .bind(payload)
+    .execute(conn)
+    .await?;
+
+  Ok(())
+}
+
+/// Listens for the events other instances publish and rebroadcasts them on the local bus.
+///
+/// Postgres doesn't keep notifications for listeners that aren't connected, so events sent while the
+/// relay is reconnecting are lost. Anything that can't afford that should go back to the event log.
+#[derive(Debug)]
+pub struct EventRelay {
+  listener: PgListener,
+  bus: EventBus,
+}
+
+impl EventRelay {
+  /// Starts listening straight away, so nothing published after this returns is missed.
+  pub async fn connect(pool: &PgPool, bus: EventBus) -> Result<Self> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(NOTIFY_CHANNEL).await?;
+    Ok(Self { listener, bus })
+  }
+
+  /// Relays events until the listener fails for good, reconnecting in between as needed.
+  pub async fn run(mut self) -> Result<()> {
+    loop {
+      let notification = self.listener.recv().await?;
+
+      match serde_json::from_str::<Notification>(notification.payload()) {
+        Ok(Notification { origin, .. }) if origin == self.bus.id => {}
+        Ok(Notification { event, .. }) => {
+          self.bus.publish(event);
+        }
+        Err(error) => tracing::warn!(%error, "ignoring malformed notification on {NOTIFY_CHANNEL}"),
+      }
+    }
+  }
+}
@@ -0,0 +1,152 @@
+use crate::event_bus::{self, EventBus, GameEvent};
+use crate::idempotency::{self, IdempotentResponse};
+use crate::types::{Error, EventKind, GridSquare, Json, PgPool, Result};
+use postgres_syntax::sql;
+use sqlx::{PgConnection, Postgres, Transaction};
+
+#[derive(Debug)]
+pub(crate) struct NewEvent {
+  game_id: i32,
+  team_id: Option<i32>,
+  kind: EventKind,
+  row_index: Option<i32>,
+  column_index: Option<i32>,
+  health: Option<i32>,
+  owner_id: Option<i32>,
+  requests_left: Option<i32>,
+  triggered_by: Option<i32>,
+  flags: Option<Vec<(i32, i32)>>,
+}
+
+impl NewEvent {
+  pub(crate) fn new(kind: EventKind, game_id: i32, team_id: Option<i32>) -> Self {
+    Self {
+      game_id,
+      team_id,
+      kind,
+      row_index: None,
+      column_index: None,
+      health: None,
+      owner_id: None,
+      requests_left: None,
+      triggered_by: None,
+      flags: None,
+    }
+  }
+
+  pub(crate) fn at(mut self, row_index: i32, column_index: i32) -> Self {
+    self.row_index = Some(row_index);
+    self.column_index = Some(column_index);
+    self
+  }
+
+  pub(crate) fn with_square(mut self, square: &GridSquare) -> Self {
+    self.health = Some(square.health);
+    self.owner_id = square.owner_id;
+    self
+  }
+
+  pub(crate) fn with_owner_and_health(mut self, owner_id: Option<i32>, health: i32) -> Self {
+    self.health = Some(health);
+    self.owner_id = owner_id;
+    self
+  }
+
+  pub(crate) fn with_requests_left(mut self, requests_left: i32) -> Self {
+    self.requests_left = Some(requests_left);
+    self
+  }
+
+  pub(crate) fn with_triggered_by(mut self, triggered_by: Option<i32>) -> Self {
+    self.triggered_by = triggered_by;
+    self
+  }
+
+  /// Games without flags leave them out.
+  pub(crate) fn with_flags(mut self, flags: Vec<(i32, i32)>) -> Self {
+    self.flags = Some(flags).filter(|flags| !flags.is_empty());
+    self
+  }
+}
+
+pub(crate) async fn append_event(conn: &mut PgConnection, event: &NewEvent, error: Option<&Error>) -> Result<()> {
+  // rejected commands may reference a game that doesn't exist, in which case there is nothing to attach the event to
+  let query = sql!(
+    "
+      INSERT INTO event (
+        game_id, team_id, kind, row_index, column_index, health, owner_id, requests_left, triggered_by, flags, error_code
+      )
+      SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11
+      WHERE EXISTS (SELECT 1 FROM game WHERE id = $1);
+    "
+  );
+
+  sqlx::query(query)
+    .bind(event.game_id)
+    .bind(event.team_id)
+    .bind::<&'static str>(event.kind.into())
+    .bind(event.row_index)
+    .bind(event.column_index)
+    .bind(event.health)
+    .bind(event.owner_id)
+    .bind(event.requests_left)
+    .bind(event.triggered_by)
+    .bind(event.flags.as_ref().map(Json))
+    .bind(error.map(<&'static str>::from))
+    .execute(conn)
+    .await?;
+
+  Ok(())
+}
+
+/// Successful commands are committed together with their event, and with their response if they were sent
+/// with an idempotency key. Rejected commands are rolled back, then their event is appended on its own.
+pub(crate) async fn commit_with_event<T: IdempotentResponse>(
+  pool: &PgPool,
+  mut tx: Transaction<'_, Postgres>,
+  result: Result<T>,
+  event: NewEvent,
+) -> Result<T> {
+  let result = match result {
+    Ok(value) => idempotency::store_response(&mut tx, &value).await.map(|()| value),
+    Err(error) => Err(error),
+  };
+  match result {
+    Ok(value) => {
+      append_event(&mut tx, &event, None).await?;
+      tx.commit().await?;
+      Ok(value)
+    }
+    Err(error) => {
+      tx.rollback().await?;
+      append_rejected_event(pool, &event, &error).await?;
+      Err(error)
+    }
+  }
+}
+
+/// Like `commit_with_event`, and also tells the other instances about a successful command from
+/// inside its transaction, see `event_bus::notify`.
+pub(crate) async fn commit_with_event_and_notify<T: IdempotentResponse>(
+  pool: &PgPool,
+  mut tx: Transaction<'_, Postgres>,
+  result: Result<T>,
+  event: NewEvent,
+  bus: &EventBus,
+  game_event: impl FnOnce(&T) -> GameEvent,
+) -> Result<T> {
+  if let Ok(value) = &result {
+    event_bus::notify(&mut tx, bus, &game_event(value)).await?;
+  }
+  commit_with_event(pool, tx, result, event).await
+}
+
+/// Appends the event of a rejected command outside of its rolled back transaction. Failing to
+/// record it is only logged, the command's own error is what the sender needs to see.
+pub(crate) async fn append_rejected_event(pool: &PgPool, event: &NewEvent, error: &Error) -> Result<()> {
+  let mut conn = pool.acquire().await?;
+  if let Err(e) = append_event(&mut conn, event, Some(error)).await {
+    tracing::warn!(error = &e as &dyn std::error::Error, "failed to record rejected command");
+  }
+  Ok(())
+}
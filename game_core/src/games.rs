@@ -1,34 +1,29 @@
+use crate::auth::{self, LockoutPolicy};
 use crate::commands::{
-  try_attack_a_square, try_create_and_join_a_game, try_defend_a_square, try_join_an_existing_game, try_place_a_mine,
-  try_query_game, try_query_grid_square, try_start,
+  eliminate_teams, games_to_settle, settle_capture_the_flag, try_attack_a_square, try_create_and_join_a_game,
+  try_defend_a_square, try_export_game, try_import_game, try_join_an_existing_game, try_place_a_mine, try_query_auth_failures,
+  try_query_events, try_query_flags, try_query_game, try_query_grid, try_query_grid_changes, try_query_grid_square,
+  try_query_load, try_replay_game, try_run_batch, try_start, try_verify_game,
 };
+use crate::event_bus::EventRelay;
+use crate::idempotency::{self, Claim};
+use crate::keys::{KeyGenerator, OsKeyGenerator};
 use crate::types::{
-  AttackRequest, AttackResponse, CreateAndJoinRequest, CreateAndJoinResponse, DefendRequest, DefendResponse, Error,
-  JoinExistingRequest, JoinExistingResponse, PgPool, PlaceMineRequest, PlaceMineResponse, QueryGameRequest, QueryGameResponse,
-  QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse, Result, StartRequest, StartResponse,
+  AttackRequest, AttackResponse, BatchItemResult, BatchRequest, BatchResponse, Command, CommandKind, CommandResponse,
+  CreateAndJoinRequest, CreateAndJoinResponse, DefendRequest, DefendResponse, Error, EventBus, ExportGameRequest,
+  ExportGameResponse, GameEvent, ImportGameRequest, ImportGameResponse, JoinExistingRequest, JoinExistingResponse, PgPool,
+  PlaceMineRequest, PlaceMineResponse, QueryAuthFailuresRequest, QueryAuthFailuresResponse, QueryEventsRequest,
+  QueryEventsResponse, QueryFlagsRequest, QueryFlagsResponse, QueryGameRequest, QueryGameResponse, QueryGridChangesRequest,
+  QueryGridChangesResponse, QueryGridRequest, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse,
+  QueryLoadResponse, ReplayGameRequest, ReplayGameResponse, Result, SenderDetails, StartRequest, StartResponse,
+  VerifyGameRequest, VerifyGameResponse, DEFAULT_IDEMPOTENCY_WINDOW,
 };
 
 use sqlx::postgres::PgPoolOptions;
-
-pub async fn create_random_hex() -> Result<String> {
-  use std::fmt::Write;
-  use tokio::fs::File;
-  use tokio::io::AsyncReadExt;
-
-  let mut file = File::open("/dev/urandom").await.unwrap();
-  let mut buffer = [0_u8; 15];
-  let length = file.read_exact(&mut buffer).await.unwrap();
-
-  buffer
-    .iter()
-    .take(length)
-    .try_fold(String::with_capacity(buffer.len() * 2), |mut acc, byte| {
-      write!(&mut acc, "{byte:02x?}").map(|_| acc)
-    })
-    .map_err(|_| Error::Unexpected {
-      message: "failed to create random hex",
-    })
-}
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{instrument, Span};
 
 pub async fn create_pool(database_name: Option<&str>) -> Result<PgPool> {
   let database_name = database_name.unwrap_or("postgres");
@@ -39,20 +34,31 @@ pub async fn create_pool(database_name: Option<&str>) -> Result<PgPool> {
     .max_connections(5)
     .connect(url.as_str())
     .await
-    .map_err(|e| Error::FailedToConnectToDatabase { cause: e.to_string() })
+    .map_err(|e| Error::FailedToConnectToDatabase { source: e.into() })
 }
 
 pub async fn setup_database(db_pool: &PgPool) -> Result<()> {
-  sqlx::query("DROP TABLE IF EXISTS mine, grid_square, game, team;")
-    .execute(db_pool)
-    .await?;
+  sqlx::query(
+    "DROP TABLE IF EXISTS square_loss, flag_hold, grid_square_version, grid_version, idempotency_key, auth_lockout, auth_failure, event, mine, grid_square, game, team;",
+  )
+  .execute(db_pool)
+  .await?;
 
   sqlx::query(
     "
     CREATE TABLE game (
       id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
       status TEXT NOT NULL CHECK (status IN ('WaitingForRegistrations', 'Started', 'Ended')),
-      created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+      created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+      command_interval_ms BIGINT NULL CHECK (command_interval_ms >= 0),
+      attack_range TEXT NOT NULL DEFAULT 'Anywhere' CHECK (attack_range IN ('Anywhere', 'Adjacent')),
+      home_squares_seed BIGINT NULL CHECK (home_squares_seed >= 0),
+      elimination_grace_ms BIGINT NULL CHECK (elimination_grace_ms >= 0),
+      mode TEXT NOT NULL DEFAULT 'Conquest' CHECK (mode IN ('Conquest', 'CaptureTheFlag')),
+      flag_count BIGINT NULL CHECK (flag_count >= 1),
+      flag_hold_ms BIGINT NULL CHECK (flag_hold_ms >= 0),
+      flags_seed BIGINT NULL CHECK (flags_seed >= 0),
+      winner_id INTEGER NULL
     );",
   )
   .execute(db_pool)
@@ -69,6 +75,10 @@ pub async fn setup_database(db_pool: &PgPool) -> Result<()> {
       role_used BOOLEAN NOT NULL DEFAULT FALSE,
       requests_left INTEGER NOT NULL CONSTRAINT requests_left_is_within_valid_range CHECK (requests_left BETWEEN 0 AND 30),
       time_of_last_command TIMESTAMPTZ CONSTRAINT time_of_last_command_either_null_or_gte_created_at CHECK (time_of_last_command IS NULL OR time_of_last_command >= created_at),
+      home_row INTEGER NULL,
+      home_column INTEGER NULL CONSTRAINT home_square_is_complete CHECK ((home_row IS NULL) = (home_column IS NULL)),
+      first_conquest_at TIMESTAMPTZ NULL,
+      eliminated_at TIMESTAMPTZ NULL,
       UNIQUE (game_id, display_name),
       UNIQUE (game_id, key)
     );",
@@ -85,14 +95,187 @@ pub async fn setup_database(db_pool: &PgPool) -> Result<()> {
       row_index INTEGER NOT NULL,
       column_index INTEGER NOT NULL,
       bonus INTEGER NOT NULL CHECK (bonus BETWEEN 0 AND 5),
-      health INTEGER NOT NULL CHECK (health BETWEEN 0 AND 120),
+      health INTEGER NOT NULL CHECK (health BETWEEN 0 AND 240),
       created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+      flag BOOLEAN NOT NULL DEFAULT FALSE,
+      flag_held_since TIMESTAMPTZ NULL CONSTRAINT only_owned_flags_are_held CHECK (flag_held_since IS NULL OR (flag AND owner_id IS NOT NULL)),
       UNIQUE (game_id, row_index, column_index)
     );",
   )
   .execute(db_pool)
   .await?;
 
+  sqlx::query(
+    "
+    CREATE TABLE grid_version (
+      game_id INTEGER PRIMARY KEY REFERENCES game (id),
+      version BIGINT NOT NULL
+    );",
+  )
+  .execute(db_pool)
+  .await?;
+
+  // kept apart from grid_square, so bumping a version doesn't check the square's foreign keys again
+  sqlx::query(
+    "
+    CREATE TABLE grid_square_version (
+      square_id INTEGER PRIMARY KEY,
+      game_id INTEGER NOT NULL,
+      version BIGINT NOT NULL
+    );",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query("CREATE INDEX grid_square_version_game_id ON grid_square_version (game_id, version);")
+    .execute(db_pool)
+    .await?;
+
+  // every square that's created or changes gets the game's next grid version. The trigger is deferred
+  // until commit, so versions are handed out in the order commands commit, and a client that has seen a
+  // version can't miss a change committed with an earlier one. Taking the game's version row last also
+  // keeps it out of the lock order every command follows.
+  sqlx::query(
+    "
+    CREATE OR REPLACE FUNCTION bump_grid_version() RETURNS TRIGGER AS $$
+    DECLARE
+      next_version BIGINT;
+    BEGIN
+      INSERT INTO grid_version (game_id, version)
+      VALUES (NEW.game_id, 1)
+      ON CONFLICT (game_id) DO UPDATE SET version = grid_version.version + 1
+      RETURNING version INTO next_version;
+
+      INSERT INTO grid_square_version (square_id, game_id, version)
+      VALUES (NEW.id, NEW.game_id, next_version)
+      ON CONFLICT (square_id) DO UPDATE SET version = next_version;
+      RETURN NULL;
+    END;
+    $$ LANGUAGE plpgsql;",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE CONSTRAINT TRIGGER grid_square_version
+    AFTER INSERT OR UPDATE OF owner_id, bonus, health ON grid_square
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION bump_grid_version();",
+  )
+  .execute(db_pool)
+  .await?;
+
+  // flag time already banked by each team, for flags they've since lost. Like the versions, it has no
+  // foreign keys, so crediting a team doesn't lock it
+  sqlx::query(
+    "
+    CREATE TABLE flag_hold (
+      game_id INTEGER NOT NULL,
+      team_id INTEGER NOT NULL,
+      held_ms BIGINT NOT NULL,
+      PRIMARY KEY (game_id, team_id)
+    );",
+  )
+  .execute(db_pool)
+  .await?;
+
+  // a flag starts counting for its new owner as soon as it changes hands
+  sqlx::query(
+    "
+    CREATE OR REPLACE FUNCTION start_flag_hold() RETURNS TRIGGER AS $$
+    BEGIN
+      NEW.flag_held_since := CASE WHEN NEW.owner_id IS NULL THEN NULL ELSE NOW() END;
+      RETURN NEW;
+    END;
+    $$ LANGUAGE plpgsql;",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE TRIGGER start_flag_hold
+    BEFORE UPDATE OF owner_id ON grid_square
+    FOR EACH ROW
+    WHEN (NEW.flag AND OLD.owner_id IS DISTINCT FROM NEW.owner_id)
+    EXECUTE FUNCTION start_flag_hold();",
+  )
+  .execute(db_pool)
+  .await?;
+
+  // and the time it was held for is banked for the previous owner once the hold ends. Deferred until
+  // commit for the same reason as the versions
+  sqlx::query(
+    "
+    CREATE OR REPLACE FUNCTION bank_flag_hold() RETURNS TRIGGER AS $$
+    BEGIN
+      INSERT INTO flag_hold (game_id, team_id, held_ms)
+      VALUES (
+        OLD.game_id,
+        OLD.owner_id,
+        CEIL(EXTRACT(EPOCH FROM COALESCE(NEW.flag_held_since, NOW()) - OLD.flag_held_since) * 1000)::BIGINT
+      )
+      ON CONFLICT (game_id, team_id) DO UPDATE SET held_ms = flag_hold.held_ms + EXCLUDED.held_ms;
+      RETURN NULL;
+    END;
+    $$ LANGUAGE plpgsql;",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE CONSTRAINT TRIGGER bank_flag_hold
+    AFTER UPDATE OF owner_id, flag_held_since ON grid_square
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+    WHEN (OLD.flag_held_since IS NOT NULL AND OLD.flag_held_since IS DISTINCT FROM NEW.flag_held_since)
+    EXECUTE FUNCTION bank_flag_hold();",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE TABLE square_loss (
+      game_id INTEGER NOT NULL,
+      team_id INTEGER NOT NULL,
+      lost_at TIMESTAMPTZ NOT NULL,
+      PRIMARY KEY (game_id, team_id)
+    );",
+  )
+  .execute(db_pool)
+  .await?;
+
+  // when a team last lost a square, which is when it was left with nothing if it owns none. Kept out
+  // of the team table and deferred like the flag holds, so conquests don't lock the losing team
+  sqlx::query(
+    "
+    CREATE OR REPLACE FUNCTION record_square_loss() RETURNS TRIGGER AS $$
+    BEGIN
+      INSERT INTO square_loss (game_id, team_id, lost_at)
+      VALUES (OLD.game_id, OLD.owner_id, NOW())
+      ON CONFLICT (game_id, team_id) DO UPDATE SET lost_at = EXCLUDED.lost_at;
+      RETURN NULL;
+    END;
+    $$ LANGUAGE plpgsql;",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE CONSTRAINT TRIGGER record_square_loss
+    AFTER UPDATE OF owner_id ON grid_square
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+    WHEN (OLD.owner_id IS NOT NULL AND OLD.owner_id IS DISTINCT FROM NEW.owner_id)
+    EXECUTE FUNCTION record_square_loss();",
+  )
+  .execute(db_pool)
+  .await?;
+
   sqlx::query(
     "
     CREATE TABLE mine (
@@ -109,52 +292,489 @@ pub async fn setup_database(db_pool: &PgPool) -> Result<()> {
   .execute(db_pool)
   .await?;
 
+  // team_id is whatever the sender claimed, so rejected commands can be recorded even if the team doesn't exist
+  sqlx::query(
+    "
+    CREATE TABLE event (
+      sequence BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+      game_id INTEGER NOT NULL REFERENCES game (id),
+      team_id INTEGER NULL,
+      kind TEXT NOT NULL CHECK (kind IN ('GameCreated', 'TeamJoined', 'GameStarted', 'HomeSquareAssigned', 'SquareAttacked', 'SquareDefended', 'MinePlaced', 'TeamEliminated', 'GameEnded', 'SquareImported', 'MineImported', 'TeamImported', 'BatchRun')),
+      row_index INTEGER NULL,
+      column_index INTEGER NULL,
+      health INTEGER NULL,
+      owner_id INTEGER NULL,
+      requests_left INTEGER NULL,
+      triggered_by INTEGER NULL,
+      flags JSONB NULL,
+      error_code TEXT NULL,
+      created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+  ",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query("CREATE INDEX event_game_id_sequence ON event (game_id, sequence);")
+    .execute(db_pool)
+    .await?;
+
+  // like event.team_id, these are whatever the sender claimed
+  sqlx::query(
+    "
+    CREATE TABLE auth_failure (
+      id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+      game_id INTEGER NOT NULL,
+      team_id INTEGER NOT NULL,
+      source TEXT NULL,
+      command TEXT NOT NULL,
+      created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+  ",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query("CREATE INDEX auth_failure_game_id ON auth_failure (game_id);")
+    .execute(db_pool)
+    .await?;
+
+  sqlx::query(
+    "
+    CREATE TABLE auth_lockout (
+      scope TEXT NOT NULL CHECK (scope IN ('team', 'source')),
+      key TEXT NOT NULL,
+      failures INTEGER NOT NULL,
+      locked_until TIMESTAMPTZ NULL,
+      PRIMARY KEY (scope, key)
+    );
+  ",
+  )
+  .execute(db_pool)
+  .await?;
+
+  sqlx::query(
+    "
+    CREATE TABLE idempotency_key (
+      team_id INTEGER NOT NULL REFERENCES team (id),
+      key VARCHAR(64) NOT NULL,
+      command TEXT NOT NULL,
+      response JSONB NULL,
+      created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+      PRIMARY KEY (team_id, key)
+    );
+  ",
+  )
+  .execute(db_pool)
+  .await?;
+
   Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Games {
   db_pool: PgPool,
+  key_generator: Arc<dyn KeyGenerator>,
+  event_bus: EventBus,
+  lockout_policy: LockoutPolicy,
+  source: Option<String>,
+  idempotency_window: Duration,
 }
 
 impl Games {
   pub async fn try_new(pool: PgPool) -> Result<Self> {
-    Ok(Self { db_pool: pool })
+    Ok(Self {
+      db_pool: pool,
+      key_generator: Arc::new(OsKeyGenerator::default()),
+      event_bus: EventBus::default(),
+      lockout_policy: LockoutPolicy::default(),
+      source: None,
+      idempotency_window: DEFAULT_IDEMPOTENCY_WINDOW,
+    })
+  }
+
+  pub fn with_key_generator(mut self, key_generator: impl KeyGenerator + 'static) -> Self {
+    self.key_generator = Arc::new(key_generator);
+    self
+  }
+
+  /// Shares an event bus with other `Games`, e.g. ones using a different key generator.
+  pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+    self.event_bus = event_bus;
+    self
+  }
+
+  pub fn with_lockout_policy(mut self, lockout_policy: LockoutPolicy) -> Self {
+    self.lockout_policy = lockout_policy;
+    self
   }
 
+  /// Where commands are coming from, e.g. the client's IP address. Failed attempts to authenticate are
+  /// counted against the source as well as the team, so one client can't guess keys across many teams.
+  pub fn with_source(mut self, source: impl Into<String>) -> Self {
+    self.source = Some(source.into());
+    self
+  }
+
+  /// How long responses are kept for retries sent with the same idempotency key.
+  pub fn with_idempotency_window(mut self, idempotency_window: Duration) -> Self {
+    self.idempotency_window = idempotency_window;
+    self
+  }
+
+  /// Where committed commands are published, clones of this `Games` share the same bus.
+  pub fn event_bus(&self) -> &EventBus {
+    &self.event_bus
+  }
+
+  /// Relays events published by other instances sharing the database onto this bus.
+  pub async fn try_connect_relay(&self) -> Result<EventRelay> {
+    EventRelay::connect(&self.db_pool, self.event_bus.clone()).await
+  }
+
+  /// Publishes a committed event to this instance's subscribers. The other instances were notified by
+  /// the command's transaction.
+  fn publish(&self, event: GameEvent) {
+    self.event_bus.publish(event);
+  }
+
+  /// Eliminates the teams the game's elimination rule has caught up with and ends capture the flag
+  /// games someone has won, and lets everyone know. Runs before the commands either could turn away,
+  /// once their sender has proven who they are, after the conquests that could win a game outright,
+  /// and every so often for games nobody sends commands to, see `try_settle_started_games`.
+  async fn settle(&self, game_id: i32) -> Result<()> {
+    let eliminations = eliminate_teams(&self.db_pool, &self.event_bus, game_id).await?;
+    for team_id in eliminations.team_ids {
+      self.publish(GameEvent::TeamEliminated { game_id, team_id });
+    }
+    if eliminations.game_ended || settle_capture_the_flag(&self.db_pool, &self.event_bus, game_id).await? {
+      self.publish(GameEvent::GameEnded { game_id });
+    }
+    Ok(())
+  }
+
+  /// Settles every started game that time alone can decide, so teams are eliminated and games end on
+  /// time even when nobody sends a command. Servers run it every so often. A game that fails to settle
+  /// doesn't hold up the others, the next run tries it again.
+  pub async fn try_settle_started_games(&self) -> Result<()> {
+    for game_id in games_to_settle(&self.db_pool).await? {
+      if let Err(error) = self.settle(game_id).await {
+        tracing::warn!(error = &error as &dyn std::error::Error, game_id, "failed to settle the game");
+      }
+    }
+    Ok(())
+  }
+
+  /// Settles the game after a conquest that went through, which could have won it. The conquest stands
+  /// either way, so failing to settle doesn't fail it, the next command settles the game instead.
+  async fn settle_after_conquest(&self, game_id: i32) {
+    if let Err(error) = self.settle(game_id).await {
+      tracing::warn!(
+        error = &error as &dyn std::error::Error,
+        "failed to settle the game after a conquest"
+      );
+    }
+  }
+
+  /// Runs a command sent on behalf of a team, unless the team or the source is locked out. Bad keys are
+  /// recorded and count towards a lockout, a command that goes through clears them. Other errors don't
+  /// always mean the key was checked, so they leave the counts alone.
+  async fn authenticated<T>(
+    &self,
+    game_id: i32,
+    team_id: i32,
+    kind: CommandKind,
+    command: impl Future<Output = Result<T>>,
+  ) -> Result<T> {
+    let source = self.source.as_deref();
+    auth::check_lockout(&self.db_pool, team_id, source).await?;
+
+    let result = command.await;
+    let recorded = match &result {
+      Err(Error::InvalidCredentials) => {
+        auth::record_failure(&self.db_pool, &self.lockout_policy, game_id, team_id, source, kind).await
+      }
+      Ok(_) => auth::record_success(&self.db_pool, team_id, source).await,
+      Err(_) => Ok(()),
+    };
+    if let Err(error) = recorded {
+      tracing::warn!(
+        error = &error as &dyn std::error::Error,
+        "failed to record authentication attempt"
+      );
+    }
+
+    result
+  }
+
+  /// Like `authenticated`, for commands the game has to be settled for first. The game is only settled
+  /// for senders whose key checks out, anyone else is left to the command to turn away.
+  async fn settled_and_authenticated<T>(
+    &self,
+    game_id: i32,
+    sender: &SenderDetails,
+    kind: CommandKind,
+    command: impl Future<Output = Result<T>>,
+  ) -> Result<T> {
+    let settled = async {
+      if auth::credentials_match(&self.db_pool, game_id, sender).await? {
+        self.settle(game_id).await?;
+      }
+      command.await
+    };
+    // boxed, or the futures of `try_execute` grow too big for the stack in debug builds
+    self.authenticated(game_id, sender.team_id, kind, Box::pin(settled)).await
+  }
+
+  #[instrument(skip_all, fields(command = "CreateAndJoin", game_id, team_id), err(level = "debug"))]
   pub async fn try_create_and_join_a_game(&mut self, request: CreateAndJoinRequest) -> Result<CreateAndJoinResponse> {
-    try_create_and_join_a_game(&self.db_pool, request).await
+    let response = try_create_and_join_a_game(&self.db_pool, self.key_generator.as_ref(), request).await?;
+    Span::current()
+      .record("game_id", response.game_id)
+      .record("team_id", response.team_id);
+    Ok(response)
   }
 
+  #[instrument(skip_all, fields(command = "JoinExisting", game_id = request.game_id, team_id), err(level = "debug"))]
   pub async fn try_join_an_existing_game(&mut self, request: JoinExistingRequest) -> Result<JoinExistingResponse> {
-    try_join_an_existing_game(&self.db_pool, request).await
+    let response = try_join_an_existing_game(&self.db_pool, self.key_generator.as_ref(), request).await?;
+    Span::current().record("team_id", response.team_id);
+    Ok(response)
   }
 
+  #[instrument(skip_all, fields(command = "Attack", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
   pub async fn try_attack_a_square(&mut self, request: AttackRequest) -> Result<AttackResponse> {
-    try_attack_a_square(&self.db_pool, request).await
+    let (game_id, team_id, sender) = (request.game_id, request.sender.team_id, request.sender.clone());
+    let response = self
+      .settled_and_authenticated(
+        game_id,
+        &sender,
+        CommandKind::Attack,
+        try_attack_a_square(&self.db_pool, &self.event_bus, request),
+      )
+      .await?;
+    self.publish(GameEvent::attacked(game_id, team_id, &response));
+    if response.conquered {
+      self.settle_after_conquest(game_id).await;
+    }
+    Ok(response)
   }
 
+  #[instrument(skip_all, fields(command = "Defend", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
   pub async fn try_defend_a_square(&mut self, request: DefendRequest) -> Result<DefendResponse> {
-    try_defend_a_square(&self.db_pool, request).await
+    let (game_id, team_id, sender) = (request.game_id, request.sender.team_id, request.sender.clone());
+    let response = self
+      .settled_and_authenticated(
+        game_id,
+        &sender,
+        CommandKind::Defend,
+        try_defend_a_square(&self.db_pool, &self.event_bus, request),
+      )
+      .await?;
+    self.publish(GameEvent::defended(game_id, team_id, &response));
+    Ok(response)
   }
 
+  #[instrument(skip_all, fields(command = "QueryGridSquare", game_id = request.game_id), err(level = "debug"))]
   pub async fn try_query_grid_square(&self, request: QueryGridSquareRequest) -> Result<QueryGridSquareResponse> {
     try_query_grid_square(&self.db_pool, request).await
   }
 
-  pub async fn try_query_grid(&self) -> Result<QueryGridResponse> {
-    todo!()
+  #[instrument(skip_all, fields(command = "QueryGrid", game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_query_grid(&self, request: QueryGridRequest) -> Result<QueryGridResponse> {
+    try_query_grid(&self.db_pool, request).await
+  }
+
+  #[instrument(skip_all, fields(command = "QueryFlags", game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_query_flags(&self, request: QueryFlagsRequest) -> Result<QueryFlagsResponse> {
+    try_query_flags(&self.db_pool, request).await
   }
 
+  #[instrument(skip_all, fields(command = "QueryGridChanges", game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_query_grid_changes(&self, request: QueryGridChangesRequest) -> Result<QueryGridChangesResponse> {
+    try_query_grid_changes(&self.db_pool, request).await
+  }
+
+  #[instrument(skip_all, fields(command = "QueryGame", game_id = request.game_id), err(level = "debug"))]
   pub async fn try_query_game(&self, request: QueryGameRequest) -> Result<QueryGameResponse> {
     try_query_game(&self.db_pool, request).await
   }
 
+  #[instrument(skip_all, fields(game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_query_events(&self, request: QueryEventsRequest) -> Result<QueryEventsResponse> {
+    try_query_events(&self.db_pool, request).await
+  }
+
+  pub async fn try_query_load(&self) -> Result<QueryLoadResponse> {
+    try_query_load(&self.db_pool).await
+  }
+
+  #[instrument(skip_all, fields(game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_replay_game(&self, request: ReplayGameRequest) -> Result<ReplayGameResponse> {
+    try_replay_game(&self.db_pool, request).await
+  }
+
+  #[instrument(skip_all, fields(game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_verify_game(&self, request: VerifyGameRequest) -> Result<VerifyGameResponse> {
+    try_verify_game(&self.db_pool, request).await
+  }
+
+  #[instrument(skip_all, fields(game_id = request.game_id), err(level = "debug"))]
+  pub async fn try_export_game(&self, request: ExportGameRequest) -> Result<ExportGameResponse> {
+    try_export_game(&self.db_pool, request).await
+  }
+
+  pub async fn try_import_game(&mut self, request: ImportGameRequest) -> Result<ImportGameResponse> {
+    try_import_game(&self.db_pool, self.key_generator.as_ref(), request).await
+  }
+
+  #[instrument(skip_all, fields(command = "PlaceMine", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
   pub async fn try_place_a_mine(&mut self, request: PlaceMineRequest) -> Result<PlaceMineResponse> {
-    try_place_a_mine(&self.db_pool, request).await
+    let (game_id, team_id, sender) = (request.game_id, request.sender.team_id, request.sender.clone());
+    let response = self
+      .settled_and_authenticated(
+        game_id,
+        &sender,
+        CommandKind::PlaceMine,
+        try_place_a_mine(&self.db_pool, &self.event_bus, request),
+      )
+      .await?;
+    self.publish(GameEvent::mine_placed(game_id, team_id, &response));
+    Ok(response)
   }
 
+  #[instrument(skip_all, fields(command = "Start", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
   pub async fn try_start(&mut self, request: StartRequest) -> Result<StartResponse> {
-    try_start(&self.db_pool, request).await
+    let (game_id, team_id) = (request.game_id, request.sender.team_id);
+    let response = self
+      .authenticated(
+        game_id,
+        team_id,
+        CommandKind::Start,
+        try_start(&self.db_pool, &self.event_bus, request),
+      )
+      .await?;
+    self.publish(GameEvent::GameStarted {
+      game_id: response.game_id,
+    });
+    Ok(response)
+  }
+
+  #[instrument(skip_all, fields(command = "QueryAuthFailures", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
+  pub async fn try_query_auth_failures(&self, request: QueryAuthFailuresRequest) -> Result<QueryAuthFailuresResponse> {
+    let (game_id, team_id) = (request.game_id, request.sender.team_id);
+    self
+      .authenticated(
+        game_id,
+        team_id,
+        CommandKind::QueryAuthFailures,
+        try_query_auth_failures(&self.db_pool, request),
+      )
+      .await
+  }
+
+  #[instrument(skip_all, fields(command = "Batch", game_id = request.game_id, team_id = request.sender.team_id), err(level = "debug"))]
+  pub async fn try_run_batch(&mut self, request: BatchRequest) -> Result<BatchResponse> {
+    let (game_id, team_id, sender) = (request.game_id, request.sender.team_id, request.sender.clone());
+    let response = self
+      .settled_and_authenticated(
+        game_id,
+        &sender,
+        CommandKind::Batch,
+        try_run_batch(&self.db_pool, &self.event_bus, request),
+      )
+      .await?;
+    for result in &response.results {
+      match result {
+        BatchItemResult::Attacked(attacked) => self.publish(GameEvent::attacked(game_id, team_id, attacked)),
+        BatchItemResult::Defended(defended) => self.publish(GameEvent::defended(game_id, team_id, defended)),
+        BatchItemResult::Failed { .. } => {}
+      }
+    }
+    let conquered = response
+      .results
+      .iter()
+      .any(|result| matches!(result, BatchItemResult::Attacked(attacked) if attacked.conquered));
+    if conquered {
+      self.settle_after_conquest(game_id).await;
+    }
+    Ok(response)
+  }
+
+  /// The team a command was sent on behalf of, if the command's key is that team's. `None` for commands
+  /// anyone can send. Transports use it to count commands against a team without letting whoever merely
+  /// knows the team's id use the team's share up.
+  pub async fn try_verify_sender(&self, command: &Command) -> Result<Option<i32>> {
+    let (Some(game_id), Some(sender)) = (command.game_id(), command.sender()) else {
+      return Ok(None);
+    };
+    let matches = auth::credentials_match(&self.db_pool, game_id, sender).await?;
+    Ok(Some(sender.team_id).filter(|_| matches))
+  }
+
+  pub async fn try_execute(&mut self, command: Command) -> Result<CommandResponse> {
+    match command {
+      Command::CreateAndJoin(request) => self
+        .try_create_and_join_a_game(request)
+        .await
+        .map(CommandResponse::CreateAndJoin),
+      Command::JoinExisting(request) => self
+        .try_join_an_existing_game(request)
+        .await
+        .map(CommandResponse::JoinExisting),
+      Command::Start(request) => self.try_start(request).await.map(CommandResponse::Start),
+      Command::Attack(request) => self.try_attack_a_square(request).await.map(CommandResponse::Attack),
+      Command::Defend(request) => self.try_defend_a_square(request).await.map(CommandResponse::Defend),
+      Command::PlaceMine(request) => self.try_place_a_mine(request).await.map(CommandResponse::PlaceMine),
+      Command::QueryGame(request) => self.try_query_game(request).await.map(CommandResponse::QueryGame),
+      Command::QueryGrid(request) => self.try_query_grid(request).await.map(CommandResponse::QueryGrid),
+      Command::QueryGridChanges(request) => self
+        .try_query_grid_changes(request)
+        .await
+        .map(CommandResponse::QueryGridChanges),
+      Command::QueryGridSquare(request) => self
+        .try_query_grid_square(request)
+        .await
+        .map(CommandResponse::QueryGridSquare),
+      Command::QueryFlags(request) => self.try_query_flags(request).await.map(CommandResponse::QueryFlags),
+      Command::QueryAuthFailures(request) => self
+        .try_query_auth_failures(request)
+        .await
+        .map(CommandResponse::QueryAuthFailures),
+      Command::Batch(request) => self.try_run_batch(request).await.map(CommandResponse::Batch),
+    }
+  }
+
+  /// Runs a command at most once per idempotency key, so a client that lost the response can send it
+  /// again without the command being applied twice: retries get back the response stored the first time.
+  /// Keys are scoped to the sender's team, so creating and joining games, which have no team to scope
+  /// them to yet, turn keys away rather than being applied again on every retry. Queries ignore them.
+  /// Failed commands aren't stored, a retry runs them again.
+  pub async fn try_execute_idempotent(&mut self, command: Command, idempotency_key: Option<&str>) -> Result<CommandResponse> {
+    let Some(key) = idempotency_key.filter(|_| command.changes_state()) else {
+      return self.try_execute(command).await;
+    };
+    let Some(sender) = command.sender().cloned() else {
+      let kind: &'static str = command.kind().into();
+      return Err(Error::InvalidRequest {
+        reason: format!("{kind} commands aren't sent on behalf of a team, so they can't carry an idempotency key"),
+      });
+    };
+
+    let claimed = match idempotency::claim(&self.db_pool, &sender, key, command.kind(), self.idempotency_window).await? {
+      Claim::Replayed(response) => return Ok(response),
+      Claim::Unauthenticated => return self.try_execute(command).await,
+      Claim::Claimed(claimed) => claimed,
+    };
+
+    // the command stores its response in its own transaction, see `idempotency::store_response`
+    let result = idempotency::with_claim(claimed.clone(), Box::pin(self.try_execute(command))).await;
+    if result.is_err() {
+      if let Err(error) = idempotency::release(&self.db_pool, &claimed).await {
+        tracing::warn!(error = &error as &dyn std::error::Error, "failed to release idempotency key");
+      }
+    }
+
+    result
   }
 }
@@ -0,0 +1,78 @@
+use crate::types::{Error, Result};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::cmp::Reverse;
+
+/// The square a team starts the game with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HomeSquare {
+  pub team_id: i32,
+  pub row: i32,
+  pub column: i32,
+}
+
+/// Picks a home square for every team, as far as possible from the ones picked before it, and hands
+/// them out in a shuffled order. Squares in `unavailable`, like flags, are left alone. The same seed,
+/// grid and teams always give the same squares.
+pub fn allocate(grid_size: i32, team_ids: &[i32], seed: u32, unavailable: &[(i32, i32)]) -> Result<Vec<HomeSquare>> {
+  let mut rng = ChaCha20Rng::seed_from_u64(u64::from(seed));
+
+  let candidates = (0..grid_size)
+    .flat_map(|row| (0..grid_size).map(move |column| (row, column)))
+    .filter(|square| !unavailable.contains(square))
+    .collect::<Vec<_>>();
+
+  if team_ids.len() > candidates.len() {
+    return Err(Error::InvalidRequest {
+      reason: format!(
+        "a {grid_size}x{grid_size} grid has room for {} home squares, {} teams joined",
+        candidates.len(),
+        team_ids.len()
+      ),
+    });
+  }
+
+  let picked = spread(candidates, team_ids.len(), &mut rng);
+
+  let mut teams = team_ids.to_vec();
+  teams.sort_unstable();
+  teams.shuffle(&mut rng);
+
+  Ok(
+    teams
+      .into_iter()
+      .zip(picked)
+      .map(|(team_id, (row, column))| HomeSquare { team_id, row, column })
+      .collect(),
+  )
+}
+
+/// Picks `count` of the candidates, each as far as possible from the ones picked before it. There
+/// have to be at least `count` candidates.
+pub(crate) fn spread(mut candidates: Vec<(i32, i32)>, count: usize, rng: &mut impl Rng) -> Vec<(i32, i32)> {
+  // ties between equally distant squares go to whichever was shuffled first
+  candidates.shuffle(rng);
+
+  let mut picked: Vec<(i32, i32)> = Vec::with_capacity(count);
+  for _ in 0..count {
+    let distance_to_picked = |(row, column): (i32, i32)| {
+      picked
+        .iter()
+        .map(|(picked_row, picked_column)| (row - picked_row).abs() + (column - picked_column).abs())
+        .min()
+        .unwrap_or(i32::MAX)
+    };
+
+    let (_, next) = candidates
+      .iter()
+      .copied()
+      .enumerate()
+      .filter(|(_, square)| !picked.contains(square))
+      .max_by_key(|(index, square)| (distance_to_picked(*square), Reverse(*index)))
+      .expect("there are at least as many candidates as squares to pick");
+    picked.push(next);
+  }
+
+  picked
+}
@@ -0,0 +1,253 @@
+use crate::types::{
+  AttackResponse, BatchResponse, CommandKind, CommandResponse, CreateAndJoinResponse, DateTimeUtc, DefendResponse, Error,
+  JoinExistingResponse, Json, PgPool, PlaceMineResponse, Result, SenderDetails, StartResponse,
+};
+use postgres_syntax::sql;
+use serde::Serialize;
+use sqlx::PgConnection;
+use std::future::Future;
+use std::time::Duration;
+
+/// How long the response to a command is kept for retries using the same idempotency key.
+pub const DEFAULT_IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Longest idempotency key accepted, long enough for a UUID in any of its usual formats.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 64;
+
+/// How long a claim without a response holds its key. Commands take well under a second, a claim this
+/// old belongs to a command whose process went away before committing, so retries may claim the key again.
+pub const IDEMPOTENCY_CLAIM_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+  /// The claim of the command running in this task, if it was sent with an idempotency key.
+  static CLAIMED: Claimed;
+}
+
+/// A key claimed for a command that hasn't stored its response yet. `claimed_at` tells this claim apart
+/// from one made for the same key after it timed out.
+#[derive(Debug, Clone)]
+pub(crate) struct Claimed {
+  team_id: i32,
+  key: String,
+  claimed_at: DateTimeUtc,
+}
+
+/// What to do with a command sent along with an idempotency key.
+#[derive(Debug)]
+pub(crate) enum Claim {
+  /// First time the key is seen, the command runs within `with_claim` and stores its response itself.
+  Claimed(Claimed),
+  /// The key was used before, this is the response the command got back then.
+  Replayed(CommandResponse),
+  /// The sender's key is wrong, so the command runs as if it had no idempotency key and fails like any other.
+  Unauthenticated,
+}
+
+/// Claims `key` for the sender's team, or finds the response stored for it. The key is claimed in a
+/// transaction of its own before the command runs, so a retry racing the original waits for the claim
+/// and then finds it, instead of running the command a second time.
+pub(crate) async fn claim(
+  pool: &PgPool,
+  sender: &SenderDetails,
+  key: &str,
+  kind: CommandKind,
+  window: Duration,
+) -> Result<Claim> {
+  if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+    return Err(Error::InvalidRequest {
+      reason: format!("idempotency keys must be between 1 and {MAX_IDEMPOTENCY_KEY_LENGTH} characters long"),
+    });
+  }
+
+  let window_ms = i64::try_from(window.as_millis()).unwrap_or(i64::MAX);
+  let timeout_ms = i64::try_from(IDEMPOTENCY_CLAIM_TIMEOUT.as_millis()).unwrap_or(i64::MAX);
+  let mut tx = pool.begin().await?;
+
+  let query = sql!(
+    "
+      DELETE FROM idempotency_key
+      WHERE team_id = $1
+        AND (
+          created_at < NOW() - $2 * INTERVAL '1 millisecond'
+          OR (key = $3 AND response IS NULL AND created_at < NOW() - $4 * INTERVAL '1 millisecond')
+        );
+    "
+  );
+
+  sqlx::query(query)
+    .bind(sender.team_id)
+    .bind(window_ms)
+    .bind(key)
+    .bind(timeout_ms)
+    .execute(&mut *tx)
+    .await?;
+
+  let query = sql!(
+    "
+      INSERT INTO idempotency_key (team_id, key, command)
+      SELECT $1, $2, $3
+      WHERE EXISTS (SELECT 1 FROM team WHERE id = $1 AND key = $4)
+      ON CONFLICT (team_id, key) DO NOTHING
+      RETURNING created_at;
+    "
+  );
+
+  let claimed: Option<(DateTimeUtc,)> = sqlx::query_as(query)
+    .bind(sender.team_id)
+    .bind(key)
+    .bind::<&'static str>(kind.into())
+    .bind(&sender.team_key)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+  if let Some((claimed_at,)) = claimed {
+    tx.commit().await?;
+    return Ok(Claim::Claimed(Claimed {
+      team_id: sender.team_id,
+      key: key.to_string(),
+      claimed_at,
+    }));
+  }
+
+  let query = sql!(
+    "
+      SELECT idempotency_key.command, idempotency_key.response
+      FROM idempotency_key
+      INNER JOIN team ON team.id = idempotency_key.team_id
+      WHERE idempotency_key.team_id = $1 AND idempotency_key.key = $2 AND team.key = $3;
+    "
+  );
+
+  let stored: Option<(String, Option<Json<CommandResponse>>)> = sqlx::query_as(query)
+    .bind(sender.team_id)
+    .bind(key)
+    .bind(&sender.team_key)
+    .fetch_optional(&mut *tx)
+    .await?;
+  tx.commit().await?;
+
+  match stored {
+    None => Ok(Claim::Unauthenticated),
+    Some((command, _)) if command != <&'static str>::from(kind) => Err(Error::InvalidRequest {
+      reason: format!("idempotency key `{key}` was already used for a {command} command"),
+    }),
+    Some((_, Some(Json(response)))) => Ok(Claim::Replayed(response)),
+    Some((_, None)) => Err(Error::IdempotentCommandInProgress),
+  }
+}
+
+/// Runs `command` holding `claimed`, for `store_response` to find.
+pub(crate) async fn with_claim<F: Future>(claimed: Claimed, command: F) -> F::Output {
+  CLAIMED.scope(claimed, command).await
+}
+
+/// Stores the response of a command run `with_claim` in the command's own transaction, so the response is
+/// there if and only if the command was applied. Fails if the claim timed out and a retry claimed the key
+/// again, that retry is the one applying the command now. Does nothing for commands sent without a key.
+pub(crate) async fn store_response<T: IdempotentResponse>(conn: &mut PgConnection, response: &T) -> Result<()> {
+  let Ok(claimed) = CLAIMED.try_with(Claimed::clone) else {
+    return Ok(());
+  };
+
+  let query = sql!(
+    "
+      UPDATE idempotency_key
+      SET response = $4
+      WHERE team_id = $1 AND key = $2 AND created_at = $3 AND response IS NULL;
+    "
+  );
+
+  let stored = sqlx::query(query)
+    .bind(claimed.team_id)
+    .bind(&claimed.key)
+    .bind(claimed.claimed_at)
+    .bind(Json(response.stored()))
+    .execute(conn)
+    .await?;
+
+  if stored.rows_affected() == 0 {
+    return Err(Error::IdempotentCommandInProgress);
+  }
+  Ok(())
+}
+
+/// Gives up a claim after the command failed. Failed commands didn't change anything, so a retry is
+/// free to run them again. Claims whose command stored a response are kept, the command was applied.
+pub(crate) async fn release(pool: &PgPool, claimed: &Claimed) -> Result<()> {
+  let query = sql!(
+    "
+      DELETE FROM idempotency_key
+      WHERE team_id = $1 AND key = $2 AND created_at = $3 AND response IS NULL;
+    "
+  );
+
+  sqlx::query(query)
+    .bind(claimed.team_id)
+    .bind(&claimed.key)
+    .bind(claimed.claimed_at)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+/// Borrowing twin of `CommandResponse`, serialized the same way, so a response can be stored before it
+/// is handed back.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum StoredResponse<'a> {
+  CreateAndJoin(&'a CreateAndJoinResponse),
+  JoinExisting(&'a JoinExistingResponse),
+  Start(&'a StartResponse),
+  Attack(&'a AttackResponse),
+  Defend(&'a DefendResponse),
+  PlaceMine(&'a PlaceMineResponse),
+  Batch(&'a BatchResponse),
+}
+
+/// Responses of the commands that change state, the only ones stored for retries.
+pub(crate) trait IdempotentResponse {
+  fn stored(&self) -> StoredResponse<'_>;
+}
+
+impl IdempotentResponse for CreateAndJoinResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::CreateAndJoin(self)
+  }
+}
+
+impl IdempotentResponse for JoinExistingResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::JoinExisting(self)
+  }
+}
+
+impl IdempotentResponse for StartResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::Start(self)
+  }
+}
+
+impl IdempotentResponse for AttackResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::Attack(self)
+  }
+}
+
+impl IdempotentResponse for DefendResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::Defend(self)
+  }
+}
+
+impl IdempotentResponse for PlaceMineResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::PlaceMine(self)
+  }
+}
+
+impl IdempotentResponse for BatchResponse {
+  fn stored(&self) -> StoredResponse<'_> {
+    StoredResponse::Batch(self)
+  }
+}
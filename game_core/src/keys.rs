@@ -0,0 +1,121 @@
+use crate::types::{Error, Result};
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::sync::Mutex;
+
+/// Upper bound imposed by the `team.key` column.
+pub const MAX_KEY_LENGTH: usize = 30;
+
+const HEX_ALPHABET: &[u8] = b"0123456789abcdef";
+const ALPHANUMERIC_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+pub trait KeyGenerator: std::fmt::Debug + Send + Sync {
+  fn generate_key(&self) -> Result<String>;
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum KeyFormat {
+  Hex,
+  Alphanumeric,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct KeyOptions {
+  pub length: usize,
+  pub format: KeyFormat,
+}
+
+impl Default for KeyOptions {
+  fn default() -> Self {
+    Self {
+      length: MAX_KEY_LENGTH,
+      format: KeyFormat::Hex,
+    }
+  }
+}
+
+impl KeyOptions {
+  fn validate(self) -> Result<Self> {
+    Some(self)
+      .filter(|options| (1..=MAX_KEY_LENGTH).contains(&options.length))
+      .ok_or(Error::InvalidKeyLength {
+        length: self.length,
+        max: MAX_KEY_LENGTH,
+      })
+  }
+
+  fn alphabet(&self) -> &'static [u8] {
+    match self.format {
+      KeyFormat::Hex => HEX_ALPHABET,
+      KeyFormat::Alphanumeric => ALPHANUMERIC_ALPHABET,
+    }
+  }
+}
+
+/// Generates keys from the operating system's CSPRNG.
+#[derive(Debug, Default)]
+pub struct OsKeyGenerator {
+  options: KeyOptions,
+}
+
+impl OsKeyGenerator {
+  pub fn try_new(options: KeyOptions) -> Result<Self> {
+    Ok(Self {
+      options: options.validate()?,
+    })
+  }
+}
+
+impl KeyGenerator for OsKeyGenerator {
+  fn generate_key(&self) -> Result<String> {
+    encode_key(&mut OsRng, self.options)
+  }
+}
+
+/// Generates a reproducible sequence of keys from a seed. Only meant for tests.
+#[derive(Debug)]
+pub struct SeededKeyGenerator {
+  options: KeyOptions,
+  rng: Mutex<ChaCha20Rng>,
+}
+
+impl SeededKeyGenerator {
+  pub fn try_new(seed: u64, options: KeyOptions) -> Result<Self> {
+    Ok(Self {
+      options: options.validate()?,
+      rng: Mutex::new(ChaCha20Rng::seed_from_u64(seed)),
+    })
+  }
+}
+
+impl KeyGenerator for SeededKeyGenerator {
+  fn generate_key(&self) -> Result<String> {
+    let mut rng = self.rng.lock().map_err(|_| Error::FailedToGenerateKey {
+      cause: "seeded generator lock was poisoned".to_string(),
+    })?;
+    encode_key(&mut *rng, self.options)
+  }
+}
+
+fn encode_key(rng: &mut impl RngCore, options: KeyOptions) -> Result<String> {
+  let alphabet = options.alphabet();
+  // largest multiple of the alphabet's size that fits in a byte, so that sampling stays uniform
+  let zone = (256 / alphabet.len() * alphabet.len()) as u16;
+  let mut key = String::with_capacity(options.length);
+  let mut buffer = [0_u8; MAX_KEY_LENGTH];
+
+  while key.len() < options.length {
+    rng
+      .try_fill_bytes(&mut buffer)
+      .map_err(|e| Error::FailedToGenerateKey { cause: e.to_string() })?;
+
+    buffer
+      .iter()
+      .filter(|byte| u16::from(**byte) < zone)
+      .take(options.length - key.len())
+      .for_each(|byte| key.push(alphabet[usize::from(*byte) % alphabet.len()] as char));
+  }
+
+  Ok(key)
+}
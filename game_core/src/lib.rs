@@ -1,4 +1,14 @@
+mod auth;
 pub mod commands;
 pub mod error;
+pub mod event_bus;
+mod event_log;
 pub mod games;
+pub mod home_squares;
+mod idempotency;
+pub mod keys;
+pub mod protocol;
+pub mod replay;
+pub mod rules;
+pub mod snapshot;
 pub mod types;
@@ -0,0 +1,75 @@
+//! Line-delimited JSON: every request is a single line holding a `RequestEnvelope`,
+//! and gets back exactly one line holding a `ResponseEnvelope` with the same id. Shared by the
+//! server and its clients, so neither has to depend on the other.
+
+use crate::types::{Command, CommandResponse, Error, ErrorCode};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+/// Longest request line a server reads, not counting the newline. The largest batch is a few kilobytes.
+pub const MAX_LINE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+  /// Picked by the client and echoed back, so responses can be matched to requests.
+  pub id: u64,
+  pub command: Command,
+  /// Picked by the client to make retries safe: a state-changing command sent on behalf of a team is
+  /// applied at most once per key, and resending it returns the response it got the first time.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub idempotency_key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorPayload {
+  pub code: ErrorCode,
+  pub message: String,
+  /// Only set for `RateLimited`, `TooManyFailedAttempts` and `CommandTooSoon`, how long to wait before sending anything else.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub retry_after_ms: Option<u64>,
+}
+
+impl From<&Error> for ErrorPayload {
+  fn from(error: &Error) -> Self {
+    let retry_after_ms = match error {
+      Error::RateLimited { retry_after_ms }
+      | Error::TooManyFailedAttempts { retry_after_ms }
+      | Error::CommandTooSoon { retry_after_ms } => Some(*retry_after_ms),
+      _ => None,
+    };
+
+    Self {
+      code: error.code(),
+      message: error.to_string(),
+      retry_after_ms,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+  /// `None` if the request was too malformed to find its id.
+  pub id: Option<u64>,
+  pub result: Result<CommandResponse, ErrorPayload>,
+}
+
+impl ResponseEnvelope {
+  pub fn error(id: Option<u64>, error: &Error) -> Self {
+    Self {
+      id,
+      result: Err(ErrorPayload::from(error)),
+    }
+  }
+}
+
+/// Decodes a single request line, or returns the response explaining why it couldn't be. The response
+/// is boxed, responses are much larger than requests.
+pub fn decode_line(line: &str) -> Result<RequestEnvelope, Box<ResponseEnvelope>> {
+  let invalid = |id: Option<u64>, reason: String| Box::new(ResponseEnvelope::error(id, &Error::InvalidRequest { reason }));
+
+  let value = serde_json::from_str::<serde_json::Value>(line).map_err(|error| invalid(None, error.to_string()))?;
+  let id = value.get("id").and_then(serde_json::Value::as_u64);
+
+  serde_json::from_value(value).map_err(|error| invalid(id, error.to_string()))
+}
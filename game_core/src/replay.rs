@@ -0,0 +1,595 @@
+use crate::rules::Rules;
+use crate::types::{Error, Event, EventKind, Game, GameStatus, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamState {
+  pub id: i32,
+  pub requests_left: i32,
+  pub role_used: bool,
+  pub home: Option<(i32, i32)>,
+  pub eliminated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SquareState {
+  pub row: i32,
+  pub column: i32,
+  pub owner_id: Option<i32>,
+  pub health: i32,
+  pub mine_placed_by: Option<i32>,
+  pub mine_triggered_by: Option<i32>,
+  pub flag: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttackOutcome {
+  Damaged,
+  Conquered,
+  /// The attack hit another team's mine instead of the square.
+  MineTriggered {
+    placed_by: i32,
+  },
+}
+
+/// State of a game rebuilt from its event log, as of `sequence`.
+///
+/// The command methods apply the same rules as the database, so the state can also be played
+/// forward in memory. They don't know about team roles, so callers have to check those themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayState {
+  pub game_id: i32,
+  pub sequence: i64,
+  pub status: GameStatus,
+  pub rules: Rules,
+  pub winner_id: Option<i32>,
+  pub teams: BTreeMap<i32, TeamState>,
+  pub grid: Vec<SquareState>,
+}
+
+impl ReplayState {
+  pub fn new(game_id: i32, rules: Rules) -> Self {
+    let grid = (0..rules.grid_size)
+      .flat_map(|row| {
+        (0..rules.grid_size).map(move |column| SquareState {
+          row,
+          column,
+          owner_id: None,
+          health: rules.square_health,
+          mine_placed_by: None,
+          mine_triggered_by: None,
+          flag: false,
+        })
+      })
+      .collect();
+
+    Self {
+      game_id,
+      sequence: 0,
+      status: GameStatus::WaitingForRegistrations,
+      rules,
+      winner_id: None,
+      teams: BTreeMap::new(),
+      grid,
+    }
+  }
+
+  pub fn square(&self, row: i32, column: i32) -> Option<&SquareState> {
+    self.grid.iter().find(|square| square.row == row && square.column == column)
+  }
+
+  pub fn join(&mut self, team_id: i32) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::WaitingForRegistrations)
+      .ok_or(Error::CannotJoinAfterHostHasStarted)?;
+
+    if self.teams.contains_key(&team_id) {
+      return Err(Error::Unexpected {
+        message: "team has already joined",
+      });
+    }
+
+    let team = TeamState {
+      id: team_id,
+      requests_left: self.rules.requests_count,
+      role_used: false,
+      home: None,
+      eliminated: false,
+    };
+    self.teams.insert(team_id, team);
+
+    Ok(())
+  }
+
+  /// Puts the flags of a capture the flag game on the grid, as they were when it was created.
+  pub fn place_flags(&mut self, flags: &[(i32, i32)]) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::WaitingForRegistrations)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::WaitingForRegistrations,
+        action: "place flags",
+      })?;
+
+    for &(row, column) in flags {
+      let square = self
+        .grid
+        .iter_mut()
+        .find(|square| square.row == row && square.column == column)
+        .ok_or(Error::InvalidCoordinates { row, column })?;
+      square.flag = true;
+    }
+    Ok(())
+  }
+
+  pub fn start(&mut self) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::WaitingForRegistrations)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::WaitingForRegistrations,
+        action: "start game",
+      })?;
+
+    self.status = GameStatus::Started;
+    Ok(())
+  }
+
+  /// Gives the team its home square, conquered and at full health, before the game starts.
+  pub fn assign_home(&mut self, team_id: i32, row: i32, column: i32) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::WaitingForRegistrations)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::WaitingForRegistrations,
+        action: "assign home square",
+      })?;
+
+    let home_health = self.rules.home_health;
+    let team = self.teams.get_mut(&team_id).ok_or(Error::InvalidTeamId { team_id })?;
+    let square = self
+      .grid
+      .iter_mut()
+      .find(|square| square.row == row && square.column == column)
+      .ok_or(Error::InvalidCoordinates { row, column })?;
+
+    team.home = Some((row, column));
+    square.owner_id = Some(team_id);
+    square.health = home_health;
+    Ok(())
+  }
+
+  /// Knocks the team out of a started game. Which teams go out, and when, is decided by the database
+  /// from the time that has passed, so this only records it.
+  pub fn eliminate(&mut self, team_id: i32) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::Started)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::Started,
+        action: "eliminate team",
+      })?;
+
+    let team = self.teams.get_mut(&team_id).ok_or(Error::InvalidTeamId { team_id })?;
+    if team.eliminated {
+      return Err(Error::TeamEliminated { team_id });
+    }
+
+    team.eliminated = true;
+    Ok(())
+  }
+
+  pub fn end(&mut self, winner_id: Option<i32>) -> Result<()> {
+    Some(self.status)
+      .filter(|status| *status == GameStatus::Started)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::Started,
+        action: "end game",
+      })?;
+
+    self.status = GameStatus::Ended;
+    self.winner_id = winner_id;
+    Ok(())
+  }
+
+  /// Sets a square back to how it was when its game was imported.
+  pub fn restore_square(&mut self, row: i32, column: i32, owner_id: Option<i32>, health: i32) -> Result<()> {
+    if let Some(owner_id) = owner_id.filter(|owner_id| !self.teams.contains_key(owner_id)) {
+      return Err(Error::InvalidTeamId { team_id: owner_id });
+    }
+
+    let square = self
+      .grid
+      .iter_mut()
+      .find(|square| square.row == row && square.column == column)
+      .ok_or(Error::InvalidCoordinates { row, column })?;
+
+    square.owner_id = owner_id;
+    square.health = health;
+    Ok(())
+  }
+
+  /// Puts back a mine an imported game had. Placing it used up the team's role.
+  pub fn restore_mine(&mut self, placed_by: i32, row: i32, column: i32, triggered_by: Option<i32>) -> Result<()> {
+    if let Some(triggered_by) = triggered_by.filter(|triggered_by| !self.teams.contains_key(triggered_by)) {
+      return Err(Error::InvalidTeamId { team_id: triggered_by });
+    }
+
+    let team = self
+      .teams
+      .get_mut(&placed_by)
+      .ok_or(Error::InvalidTeamId { team_id: placed_by })?;
+    let square = self
+      .grid
+      .iter_mut()
+      .find(|square| square.row == row && square.column == column)
+      .ok_or(Error::InvalidCoordinates { row, column })?;
+
+    team.role_used = true;
+    square.mine_placed_by = Some(placed_by);
+    square.mine_triggered_by = triggered_by;
+    Ok(())
+  }
+
+  /// Sets a team's requests back to what its game was imported with.
+  pub fn restore_team(&mut self, team_id: i32, requests_left: i32) -> Result<()> {
+    let team = self.teams.get_mut(&team_id).ok_or(Error::InvalidTeamId { team_id })?;
+    team.requests_left = requests_left;
+    Ok(())
+  }
+
+  /// Checks that the team can act on the square and spends one of its requests.
+  fn spend_request(
+    &mut self,
+    team_id: i32,
+    row: i32,
+    column: i32,
+    action: &'static str,
+  ) -> Result<(&mut TeamState, &mut SquareState)> {
+    let team = self.teams.get_mut(&team_id).ok_or(Error::InvalidTeamId { team_id })?;
+
+    if team.eliminated {
+      return Err(Error::TeamEliminated { team_id });
+    }
+
+    Some(team.requests_left)
+      .filter(|count| count > &0)
+      .ok_or(Error::NoMoreRequestsLeft)?;
+
+    Some(self.status)
+      .filter(|status| *status == GameStatus::Started)
+      .ok_or(Error::InvalidGameStatus {
+        current: self.status,
+        required: GameStatus::Started,
+        action,
+      })?;
+
+    let square = self
+      .grid
+      .iter_mut()
+      .find(|square| square.row == row && square.column == column)
+      .ok_or(Error::InvalidCoordinates { row, column })?;
+
+    team.requests_left -= 1;
+    Ok((team, square))
+  }
+
+  pub fn attack(&mut self, team_id: i32, row: i32, column: i32) -> Result<AttackOutcome> {
+    let rules = self.rules;
+    let (team, square) = self.spend_request(team_id, row, column, "attack square")?;
+
+    let armed_mine = square
+      .mine_placed_by
+      .filter(|placed_by| *placed_by != team_id && square.mine_triggered_by.is_none());
+
+    if let Some(placed_by) = armed_mine {
+      square.mine_triggered_by = Some(team_id);
+      team.requests_left = rules.mine_penalty.requests_left_after(team.requests_left);
+      return Ok(AttackOutcome::MineTriggered { placed_by });
+    }
+
+    if square.health > 1 {
+      square.health -= 1;
+      return Ok(AttackOutcome::Damaged);
+    }
+
+    square.owner_id = Some(team_id);
+    square.health = rules.conquered_health;
+    Ok(AttackOutcome::Conquered)
+  }
+
+  pub fn defend(&mut self, team_id: i32, row: i32, column: i32) -> Result<()> {
+    let rules = self.rules;
+    let (_, square) = self.spend_request(team_id, row, column, "defend square")?;
+
+    // home squares can start out above the health defends go up to
+    square.health = square.health.max(rules.max_health(square.owner_id).min(square.health + 1));
+    Ok(())
+  }
+
+  pub fn place_mine(&mut self, team_id: i32, row: i32, column: i32) -> Result<()> {
+    if self.teams.get(&team_id).is_some_and(|team| team.role_used) {
+      return Err(Error::RoleAlreadyUsed);
+    }
+
+    let (team, square) = self.spend_request(team_id, row, column, "place mine")?;
+
+    team.role_used = true;
+    square.mine_placed_by = Some(team_id);
+    square.mine_triggered_by = None;
+    Ok(())
+  }
+
+  /// Applies the rules for a single event and checks that the outcome matches what was recorded.
+  /// Rejected commands are skipped, since they never changed any state.
+  pub fn apply(&mut self, event: &Event) -> Result<()> {
+    let mismatch = |reason: String| Error::ReplayMismatch {
+      sequence: event.sequence,
+      reason,
+    };
+
+    Some(event.game_id)
+      .filter(|game_id| *game_id == self.game_id)
+      .ok_or_else(|| mismatch(format!("event belongs to game {}", event.game_id)))?;
+
+    self.sequence = event.sequence;
+
+    if event.error_code.is_some() {
+      return Ok(());
+    }
+
+    // only game ended and imported square events may leave it out
+    let team_id = event.team_id.ok_or_else(|| mismatch("missing team".to_string()));
+    let coordinates = || {
+      event
+        .row
+        .zip(event.column)
+        .ok_or_else(|| mismatch("missing coordinates".to_string()))
+    };
+
+    let (row, column) = match event.kind {
+      EventKind::GameCreated | EventKind::TeamJoined => {
+        let team_id = team_id?;
+        self
+          .join(team_id)
+          .map_err(|error| mismatch(format!("team {team_id} could not join: {error}")))?;
+        return self
+          .place_flags(event.flags.as_deref().unwrap_or_default())
+          .map_err(|error| mismatch(format!("flags could not be placed: {error}")));
+      }
+      EventKind::GameStarted => {
+        team_id?;
+        return self.start().map_err(|error| mismatch(error.to_string()));
+      }
+      EventKind::TeamEliminated => {
+        let team_id = team_id?;
+        return self
+          .eliminate(team_id)
+          .map_err(|error| mismatch(format!("team {team_id} could not be eliminated: {error}")));
+      }
+      EventKind::GameEnded => {
+        return self.end(event.team_id).map_err(|error| mismatch(error.to_string()));
+      }
+      EventKind::SquareImported => {
+        let (row, column) = coordinates()?;
+        let health = event.health.ok_or_else(|| mismatch("missing health".to_string()))?;
+        return self
+          .restore_square(row, column, event.owner_id, health)
+          .map_err(|error| mismatch(format!("square ({row}, {column}) could not be imported: {error}")));
+      }
+      EventKind::MineImported => {
+        let (team_id, (row, column)) = (team_id?, coordinates()?);
+        return self
+          .restore_mine(team_id, row, column, event.triggered_by)
+          .map_err(|error| mismatch(format!("mine at ({row}, {column}) could not be imported: {error}")));
+      }
+      EventKind::TeamImported => {
+        let team_id = team_id?;
+        let requests_left = event
+          .requests_left
+          .ok_or_else(|| mismatch("missing requests left".to_string()))?;
+        return self
+          .restore_team(team_id, requests_left)
+          .map_err(|error| mismatch(format!("team {team_id} could not be imported: {error}")));
+      }
+      EventKind::BatchRun => return Err(mismatch("batches are only recorded when turned away".to_string())),
+      EventKind::HomeSquareAssigned | EventKind::SquareAttacked | EventKind::SquareDefended | EventKind::MinePlaced => {
+        coordinates()?
+      }
+    };
+
+    let team_id = team_id?;
+    match event.kind {
+      EventKind::HomeSquareAssigned => self.assign_home(team_id, row, column),
+      EventKind::SquareAttacked => self.attack(team_id, row, column).map(|_| ()),
+      EventKind::SquareDefended => self.defend(team_id, row, column),
+      _ => self.place_mine(team_id, row, column),
+    }
+    .map_err(|error| mismatch(format!("replaying {:?} failed: {error}", event.kind)))?;
+
+    // assigning a home square doesn't cost a request, so none are recorded
+    let requests_left = self.teams[&team_id].requests_left;
+    if event.kind != EventKind::HomeSquareAssigned && event.requests_left != Some(requests_left) {
+      return Err(mismatch(format!(
+        "expected team {team_id} to have {requests_left} requests left, recorded {:?}",
+        event.requests_left
+      )));
+    }
+
+    let square = self.square(row, column).expect("square exists after a successful command");
+    if (event.health, event.owner_id) != (Some(square.health), square.owner_id) {
+      return Err(mismatch(format!(
+        "expected square ({row}, {column}) to have health {} and owner {:?}, recorded health {:?} and owner {:?}",
+        square.health, square.owner_id, event.health, event.owner_id
+      )));
+    }
+
+    Ok(())
+  }
+
+  /// Checks the replayed state against the game as currently stored, along with the coordinates of its
+  /// flags, which `Game` doesn't carry.
+  pub fn verify(&self, game: &Game, flags: &[(i32, i32)]) -> Result<()> {
+    let mismatch = |reason: String| Error::ReplayMismatch {
+      sequence: self.sequence,
+      reason,
+    };
+
+    if game.status != self.status {
+      return Err(mismatch(format!(
+        "replayed status {:?}, stored status {:?}",
+        self.status, game.status
+      )));
+    }
+
+    if game.winner_id != self.winner_id {
+      return Err(mismatch(format!(
+        "replayed winner {:?}, stored winner {:?}",
+        self.winner_id, game.winner_id
+      )));
+    }
+
+    if game.teams.len() != self.teams.len() {
+      return Err(mismatch(format!(
+        "replayed {} teams, stored {} teams",
+        self.teams.len(),
+        game.teams.len()
+      )));
+    }
+
+    for team in &game.teams {
+      let replayed = self
+        .teams
+        .get(&team.id)
+        .ok_or_else(|| mismatch(format!("team {} was never replayed", team.id)))?;
+
+      let stored = (
+        team.requests_left,
+        team.role_used,
+        team.home_row.zip(team.home_column),
+        team.eliminated_at.is_some(),
+      );
+      if (replayed.requests_left, replayed.role_used, replayed.home, replayed.eliminated) != stored {
+        return Err(mismatch(format!("team {} differs: replayed {replayed:?}", team.id)));
+      }
+    }
+
+    let mut replayed_flags = self
+      .grid
+      .iter()
+      .filter(|square| square.flag)
+      .map(|square| (square.row, square.column))
+      .collect::<Vec<_>>();
+    let mut stored_flags = flags.to_vec();
+    replayed_flags.sort_unstable();
+    stored_flags.sort_unstable();
+    if replayed_flags != stored_flags {
+      return Err(mismatch(format!(
+        "replayed flags at {replayed_flags:?}, stored flags at {stored_flags:?}"
+      )));
+    }
+
+    for square in &game.grid {
+      let replayed = self
+        .square(square.row, square.column)
+        .ok_or_else(|| mismatch(format!("square ({}, {}) was never replayed", square.row, square.column)))?;
+
+      if (replayed.health, replayed.owner_id) != (square.health, square.owner_id) {
+        return Err(mismatch(format!(
+          "square ({}, {}) differs: replayed health {} and owner {:?}, stored health {} and owner {:?}",
+          square.row, square.column, replayed.health, replayed.owner_id, square.health, square.owner_id
+        )));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl fmt::Display for ReplayState {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "game {} at sequence {} ({:?})", self.game_id, self.sequence, self.status)?;
+
+    for row in self.grid.chunks(self.rules.grid_size as usize) {
+      let cells = row
+        .iter()
+        .map(|square| {
+          let owner = square
+            .owner_id
+            .map_or_else(|| "-".to_string(), |owner_id| owner_id.to_string());
+          let mine = match (square.mine_placed_by, square.mine_triggered_by) {
+            (Some(_), Some(_)) => "x",
+            (Some(_), None) => "*",
+            _ => " ",
+          };
+          format!("{owner:>4}:{:<3}{mine}", square.health)
+        })
+        .collect::<Vec<_>>();
+      writeln!(f, "{}", cells.join(" "))?;
+    }
+
+    for team in self.teams.values() {
+      writeln!(
+        f,
+        "team {}: {} requests left{}{}",
+        team.id,
+        team.requests_left,
+        if team.role_used { ", role used" } else { "" },
+        if team.eliminated { ", eliminated" } else { "" }
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Rebuilds a game's state by applying its recorded events in sequence order.
+#[derive(Debug)]
+pub struct Replay {
+  game_id: i32,
+  events: Vec<Event>,
+}
+
+impl Replay {
+  pub fn new(game_id: i32, mut events: Vec<Event>) -> Self {
+    events.sort_by_key(|event| event.sequence);
+    Self { game_id, events }
+  }
+
+  pub fn events(&self) -> &[Event] {
+    &self.events
+  }
+
+  /// Yields the state after each event has been applied.
+  pub fn steps(&self) -> impl Iterator<Item = Result<ReplayState>> + '_ {
+    let mut state = Some(ReplayState::new(self.game_id, Rules::default()));
+
+    self.events.iter().map_while(move |event| {
+      let mut next = state.take()?;
+      match next.apply(event) {
+        Ok(()) => {
+          state = Some(next.clone());
+          Some(Ok(next))
+        }
+        Err(error) => Some(Err(error)),
+      }
+    })
+  }
+
+  /// State after every event up to and including `sequence` has been applied.
+  pub fn state_at(&self, sequence: i64) -> Result<ReplayState> {
+    let mut state = ReplayState::new(self.game_id, Rules::default());
+
+    for event in self.events.iter().take_while(|event| event.sequence <= sequence) {
+      state.apply(event)?;
+    }
+
+    Ok(state)
+  }
+
+  pub fn final_state(&self) -> Result<ReplayState> {
+    self.state_at(i64::MAX)
+  }
+}
@@ -0,0 +1,79 @@
+use crate::commands::{
+  CONQUERED_SQUARE_HEALTH, GRID_SIZE, GRID_SQUARE_DEFAULT_HEALTH, HOME_SQUARE_HEALTH, MINE_PENALTY, REQUESTS_COUNT,
+};
+use crate::types::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// What happens to a team that attacks a square with another team's untriggered mine on it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinePenalty {
+  /// The team loses every request it has left.
+  AllRequests,
+  /// The team loses this many requests on top of the one spent on the attack.
+  Requests(i32),
+}
+
+impl MinePenalty {
+  pub fn requests_left_after(self, requests_left: i32) -> i32 {
+    match self {
+      MinePenalty::AllRequests => 0,
+      MinePenalty::Requests(count) => (requests_left - count).max(0),
+    }
+  }
+}
+
+/// The tunable numbers behind a game. The database always plays with `Rules::default()`,
+/// other values are only used by the in-memory engine, e.g. when simulating games.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+  pub grid_size: i32,
+  pub square_health: i32,
+  pub conquered_health: i32,
+  pub home_health: i32,
+  pub requests_count: i32,
+  pub mine_penalty: MinePenalty,
+}
+
+impl Default for Rules {
+  fn default() -> Self {
+    Self {
+      grid_size: GRID_SIZE,
+      square_health: GRID_SQUARE_DEFAULT_HEALTH,
+      conquered_health: CONQUERED_SQUARE_HEALTH,
+      home_health: HOME_SQUARE_HEALTH,
+      requests_count: REQUESTS_COUNT,
+      mine_penalty: MINE_PENALTY,
+    }
+  }
+}
+
+impl Rules {
+  pub fn validate(&self) -> Result<()> {
+    let invalid = |reason: &str| {
+      Err(Error::InvalidRules {
+        reason: reason.to_string(),
+      })
+    };
+
+    if self.grid_size < 1 {
+      return invalid("grid_size must be at least 1");
+    }
+    if self.square_health < 1 || self.conquered_health < 1 || self.home_health < 1 {
+      return invalid("square_health, conquered_health and home_health must be at least 1");
+    }
+    if self.requests_count < 1 {
+      return invalid("requests_count must be at least 1");
+    }
+    if matches!(self.mine_penalty, MinePenalty::Requests(count) if count < 0) {
+      return invalid("mine_penalty cannot be negative");
+    }
+
+    Ok(())
+  }
+
+  /// Health a square can be defended up to.
+  pub fn max_health(&self, owner_id: Option<i32>) -> i32 {
+    owner_id.map_or(self.square_health, |_| self.conquered_health)
+  }
+}
@@ -0,0 +1,172 @@
+use crate::commands::GRID_SIZE;
+use crate::types::{DateTimeUtc, Error, GameOptions, GameStatus, Result, TeamRole};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Bumped whenever the layout of `GameSnapshot` changes in a way older readers can't handle.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Portable copy of a game. Team keys are deliberately left out, imported teams get new ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+  pub version: u32,
+  pub game: GameDetailsSnapshot,
+  pub teams: Vec<TeamSnapshot>,
+  pub grid: Vec<GridSquareSnapshot>,
+  pub mines: Vec<MineSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameDetailsSnapshot {
+  pub id: i32,
+  pub status: GameStatus,
+  pub created_at: DateTimeUtc,
+  #[serde(default)]
+  pub options: GameOptions,
+  #[serde(default)]
+  pub winner_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeamSnapshot {
+  pub id: i32,
+  pub display_name: String,
+  pub role: TeamRole,
+  pub role_used: bool,
+  pub requests_left: i32,
+  pub created_at: DateTimeUtc,
+  pub time_of_last_command: Option<DateTimeUtc>,
+  #[serde(default)]
+  pub home_row: Option<i32>,
+  #[serde(default)]
+  pub home_column: Option<i32>,
+  #[serde(default)]
+  pub first_conquest_at: Option<DateTimeUtc>,
+  #[serde(default)]
+  pub eliminated_at: Option<DateTimeUtc>,
+  /// When the team last lost a square to another team.
+  #[serde(default)]
+  pub last_square_lost_at: Option<DateTimeUtc>,
+  /// Time banked for flags the team held before, not counting the ones it still holds.
+  #[serde(default)]
+  pub flag_held_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridSquareSnapshot {
+  pub row_index: i32,
+  pub column_index: i32,
+  pub owner_id: Option<i32>,
+  pub bonus: i32,
+  pub health: i32,
+  pub created_at: DateTimeUtc,
+  #[serde(default)]
+  pub flag: bool,
+  #[serde(default)]
+  pub flag_held_since: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MineSnapshot {
+  pub row_index: i32,
+  pub column_index: i32,
+  pub owner_id: i32,
+  pub triggerer_id: Option<i32>,
+}
+
+impl GameSnapshot {
+  pub fn to_json(&self) -> Result<String> {
+    serde_json::to_string_pretty(self).map_err(|e| Error::InvalidSnapshot { reason: e.to_string() })
+  }
+
+  pub fn from_json(json: &str) -> Result<Self> {
+    #[derive(Deserialize)]
+    struct Versioned {
+      version: u32,
+    }
+
+    let Versioned { version } = serde_json::from_str(json).map_err(|e| Error::InvalidSnapshot { reason: e.to_string() })?;
+
+    Some(version)
+      .filter(|version| *version == SNAPSHOT_VERSION)
+      .ok_or(Error::UnsupportedSnapshotVersion {
+        version,
+        supported: SNAPSHOT_VERSION,
+      })?;
+
+    serde_json::from_str(json).map_err(|e| Error::InvalidSnapshot { reason: e.to_string() })
+  }
+
+  /// Checks that every square is on the grid and there at most once, that every team referenced by the grid,
+  /// mines and home squares is part of the snapshot, and that mines and home squares are on the grid.
+  pub fn validate(&self) -> Result<()> {
+    Some(self.version)
+      .filter(|version| *version == SNAPSHOT_VERSION)
+      .ok_or(Error::UnsupportedSnapshotVersion {
+        version: self.version,
+        supported: SNAPSHOT_VERSION,
+      })?;
+
+    let mut seen = HashSet::new();
+    for square in &self.grid {
+      let (row, column) = (square.row_index, square.column_index);
+      if !(0..GRID_SIZE).contains(&row) || !(0..GRID_SIZE).contains(&column) {
+        return Err(Error::InvalidSnapshot {
+          reason: format!("square ({row}, {column}) is outside the grid"),
+        });
+      }
+      if !seen.insert((row, column)) {
+        return Err(Error::InvalidSnapshot {
+          reason: format!("square ({row}, {column}) is included more than once"),
+        });
+      }
+    }
+
+    let is_known = |team_id: &i32| self.teams.iter().any(|team| team.id == *team_id);
+
+    let unknown = self
+      .grid
+      .iter()
+      .filter_map(|square| square.owner_id)
+      .chain(self.mines.iter().map(|mine| mine.owner_id))
+      .chain(self.mines.iter().filter_map(|mine| mine.triggerer_id))
+      .chain(self.game.winner_id)
+      .find(|team_id| !is_known(team_id));
+
+    if let Some(team_id) = unknown {
+      return Err(Error::InvalidSnapshot {
+        reason: format!("team {team_id} is referenced but not included"),
+      });
+    }
+
+    let mut homes = HashMap::new();
+    for team in &self.teams {
+      let home = match (team.home_row, team.home_column) {
+        (None, None) => continue,
+        (Some(row), Some(column)) if seen.contains(&(row, column)) => (row, column),
+        (row, column) => {
+          return Err(Error::InvalidSnapshot {
+            reason: format!("home square {row:?}, {column:?} of team {} is not on the grid", team.id),
+          })
+        }
+      };
+      if let Some(other) = homes.insert(home, team.id) {
+        return Err(Error::InvalidSnapshot {
+          reason: format!("teams {other} and {} share the home square {home:?}", team.id),
+        });
+      }
+    }
+
+    let misplaced = self
+      .mines
+      .iter()
+      .find(|mine| !seen.contains(&(mine.row_index, mine.column_index)));
+
+    match misplaced {
+      Some(mine) => Err(Error::InvalidSnapshot {
+        reason: format!("mine at ({}, {}) is not on the grid", mine.row_index, mine.column_index),
+      }),
+      None => Ok(()),
+    }
+  }
+}
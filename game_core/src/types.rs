@@ -2,18 +2,31 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use strum_macros::IntoStaticStr;
 
+pub use crate::auth::LockoutPolicy;
 pub use crate::commands::{AttackRequest, AttackResponse};
-// pub use crate::commands::{Command, CommandResponse};
+pub use crate::commands::{BatchItemResult, BatchMode, BatchOperation, BatchRequest, BatchResponse};
+pub use crate::commands::{Command, CommandKind, CommandResponse};
 pub use crate::commands::{CreateAndJoinRequest, CreateAndJoinResponse};
 pub use crate::commands::{DefendRequest, DefendResponse};
+pub use crate::commands::{ExportGameRequest, ExportGameResponse, ImportGameRequest, ImportGameResponse, ImportedTeam};
+pub use crate::commands::{Flag, FlagStanding, QueryFlagsRequest, QueryFlagsResponse};
 pub use crate::commands::{JoinExistingRequest, JoinExistingResponse};
 pub use crate::commands::{PlaceMineRequest, PlaceMineResponse};
+pub use crate::commands::{QueryAuthFailuresRequest, QueryAuthFailuresResponse};
 pub use crate::commands::{
-  QueryGameRequest, QueryGameResponse, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse,
+  QueryEventsRequest, QueryEventsResponse, QueryGameRequest, QueryGameResponse, QueryGridChangesRequest,
+  QueryGridChangesResponse, QueryGridRequest, QueryGridResponse, QueryGridSquareRequest, QueryGridSquareResponse,
+  QueryLoadResponse,
 };
+pub use crate::commands::{ReplayGameRequest, ReplayGameResponse, VerifyGameRequest, VerifyGameResponse};
 pub use crate::commands::{StartRequest, StartResponse};
-pub use crate::error::{Error, Result};
+pub use crate::error::{DatabaseCause, Error, ErrorCode, Result};
+pub use crate::event_bus::{EventBus, EventRelay, GameEvent};
 pub use crate::games::Games;
+pub use crate::idempotency::{DEFAULT_IDEMPOTENCY_WINDOW, IDEMPOTENCY_CLAIM_TIMEOUT, MAX_IDEMPOTENCY_KEY_LENGTH};
+pub use crate::keys::{KeyFormat, KeyGenerator, KeyOptions, OsKeyGenerator, SeededKeyGenerator};
+pub use crate::rules::{MinePenalty, Rules};
+pub use crate::snapshot::GameSnapshot;
 
 pub use sqlx::types::Json;
 pub use sqlx::PgPool;
@@ -33,6 +46,28 @@ pub enum GameStatus {
   Ended,
 }
 
+/// Which squares a team is allowed to attack.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, IntoStaticStr, Serialize, Deserialize)]
+pub enum AttackRange {
+  /// Any square on the grid.
+  #[default]
+  Anywhere,
+  /// Only squares orthogonally adjacent to one the team owns, so territory grows outwards. A team that
+  /// owns nothing can attack anywhere, to get a foothold.
+  Adjacent,
+}
+
+/// What a game is won by.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, IntoStaticStr, Serialize, Deserialize)]
+pub enum GameMode {
+  /// Squares are conquered for their own sake, and nothing but eliminations ends the game.
+  #[default]
+  Conquest,
+  /// Flag squares are placed when the game is created. A team wins by holding every flag at once, or
+  /// by holding flags for long enough in total, each flag counting separately.
+  CaptureTheFlag,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Mine {
   pub placed_by: i32,
@@ -58,24 +93,132 @@ pub struct GridSquare {
 pub struct Team {
   pub id: i32,
   pub display_name: String,
+  /// Never serialized, so a team's key can't leak to other teams through a query.
+  #[serde(skip_serializing, default)]
   pub key: String,
   pub role: TeamRole,
   pub role_used: bool,
   pub requests_left: i32,
   pub created_at: DateTime<Utc>,
   pub time_of_last_command: Option<DateTime<Utc>>,
+  /// Where the team started, only set in games allocating home squares.
+  #[serde(default)]
+  pub home_row: Option<i32>,
+  #[serde(default)]
+  pub home_column: Option<i32>,
+  /// When the team first owned a square, which starts its elimination grace period.
+  #[serde(default)]
+  pub first_conquest_at: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub eliminated_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug)]
+/// Settings the host picks when creating a game.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameOptions {
+  /// Minimum time between two state-changing commands from the same team, any pace goes when `None`.
+  #[serde(default)]
+  pub command_interval_ms: Option<u32>,
+  #[serde(default)]
+  pub attack_range: AttackRange,
+  /// Gives every team a home square when the game starts, spread across the grid by this seed. Teams
+  /// start with nothing when `None`.
+  #[serde(default)]
+  pub home_squares_seed: Option<u32>,
+  /// Eliminates teams that own no squares once this long has passed since their first conquest, and
+  /// ends the game when one team is left. Teams are never eliminated when `None`.
+  #[serde(default)]
+  pub elimination_grace_ms: Option<u32>,
+  #[serde(default)]
+  pub mode: GameMode,
+  /// How many flags capture the flag games have, `DEFAULT_FLAG_COUNT` when `None`.
+  #[serde(default)]
+  pub flag_count: Option<u32>,
+  /// Total time a team has to hold flags for to win a capture the flag game, `DEFAULT_FLAG_HOLD_MS`
+  /// when `None`.
+  #[serde(default)]
+  pub flag_hold_ms: Option<u32>,
+  /// Spreads the flags of capture the flag games across the grid, picked at random when `None`.
+  #[serde(default)]
+  pub flags_seed: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Game {
   pub id: i32,
   pub status: GameStatus,
   pub created_at: DateTime<Utc>,
+  #[serde(default)]
+  pub options: GameOptions,
+  /// The team left standing or holding the flags once the game has ended, if there was one.
+  #[serde(default)]
+  pub winner_id: Option<i32>,
   pub grid: Vec<GridSquare>,
   pub teams: Vec<Team>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Copy, Clone, IntoStaticStr, Serialize, Deserialize)]
+pub enum EventKind {
+  GameCreated,
+  TeamJoined,
+  GameStarted,
+  /// A team was given its home square as the game started.
+  HomeSquareAssigned,
+  SquareAttacked,
+  SquareDefended,
+  MinePlaced,
+  TeamEliminated,
+  /// The game ended, `team_id` is the winner: the team eliminations left standing, if any, or the one
+  /// that won capture the flag.
+  GameEnded,
+  /// A square of an imported game, with the `owner_id` and `health` it was imported with.
+  SquareImported,
+  /// A mine of an imported game, placed by `team_id` and triggered by `triggered_by` if anyone had.
+  MineImported,
+  /// A team of an imported game, with the `requests_left` it was imported with.
+  TeamImported,
+  /// A batch turned away before any of its operations ran, so always with `error_code` set. Batches that
+  /// ran are recorded as an event per operation instead.
+  BatchRun,
+}
+
+/// An entry in a game's append-only event log. Rejected commands are recorded too, with `error_code` set
+/// to the name of the `Error` variant they failed with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+  pub sequence: i64,
+  pub game_id: i32,
+  pub team_id: Option<i32>,
+  pub kind: EventKind,
+  #[serde(rename = "row_index")]
+  pub row: Option<i32>,
+  #[serde(rename = "column_index")]
+  pub column: Option<i32>,
+  pub health: Option<i32>,
+  pub owner_id: Option<i32>,
+  pub requests_left: Option<i32>,
+  /// Only set for `MineImported`.
+  #[serde(default)]
+  pub triggered_by: Option<i32>,
+  /// Rows and columns of the game's flags, only set for the `GameCreated` of capture the flag games.
+  #[serde(default)]
+  pub flags: Option<Vec<(i32, i32)>>,
+  pub error_code: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// A failed attempt to authenticate as `team_id`, from `source` if the transport knows where it came from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthFailure {
+  pub id: i64,
+  pub game_id: i32,
+  pub team_id: i32,
+  pub source: Option<String>,
+  pub command: CommandKind,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SenderDetails {
   pub team_id: i32,
   pub team_key: String,
@@ -90,4 +233,5 @@ pub enum DatabaseErrorKind {
   InvalidCoordinates,
   InvalidGameStatus,
   InvalidTeamRole,
+  SquareNotReachable,
 }
@@ -0,0 +1,196 @@
+use crate::live::{LiveFeed, LiveUpdate};
+use crate::metrics::Metrics;
+use crate::protocol::ErrorPayload;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use game_core::types::{Error, ErrorCode, GameEvent, Games, QueryGameRequest};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::broadcast;
+use tracing::{instrument, Instrument, Span};
+
+pub const DEFAULT_HTTP_ADDRESS: &str = "127.0.0.1:7879";
+
+#[derive(Debug, Clone)]
+struct AppState {
+  games: Games,
+  poll_interval: Duration,
+  metrics: Metrics,
+}
+
+/// Serves live game updates to browsers as server-sent events, on `GET /games/{game_id}/events`.
+///
+/// A new stream starts with a `snapshot` event holding the whole game, then sends a `LiveUpdate` for
+/// every change, with the sequence of the event behind it as the SSE id. Browsers send that id back
+/// as `Last-Event-ID` when they reconnect, and the stream carries on from there without a snapshot.
+#[derive(Debug)]
+pub struct HttpServer {
+  listener: TcpListener,
+  state: AppState,
+}
+
+impl HttpServer {
+  pub async fn bind(address: impl ToSocketAddrs, games: Games) -> io::Result<Self> {
+    let listener = TcpListener::bind(address).await?;
+    Ok(Self {
+      listener,
+      state: AppState {
+        games,
+        poll_interval: Duration::from_secs(1),
+        metrics: Metrics::default(),
+      },
+    })
+  }
+
+  /// How often each stream checks the event log for events the event bus didn't announce.
+  pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+    self.state.poll_interval = poll_interval;
+    self
+  }
+
+  /// Serves these metrics on `GET /metrics`, e.g. the ones the `TcpServer` records.
+  pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+    self.state.metrics = metrics;
+    self
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.listener.local_addr()
+  }
+
+  pub async fn run(self) -> io::Result<()> {
+    let router = Router::new()
+      .route("/games/:game_id/events", get(game_events))
+      .route("/metrics", get(metrics))
+      .with_state(self.state);
+
+    axum::serve(self.listener, router).await
+  }
+}
+
+fn error_response(error: &Error) -> Response {
+  let status = match error.code() {
+    ErrorCode::InvalidGameId => StatusCode::NOT_FOUND,
+    _ if error.is_internal() => {
+      tracing::error!(error = error as &dyn std::error::Error, "request failed");
+      StatusCode::INTERNAL_SERVER_ERROR
+    }
+    _ => StatusCode::BAD_REQUEST,
+  };
+
+  (status, Json(ErrorPayload::from(error))).into_response()
+}
+
+async fn metrics(State(state): State<AppState>) -> Response {
+  match state.metrics.render(&state.games).await {
+    Ok(encoded) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], encoded).into_response(),
+    Err(error) => error_response(&error),
+  }
+}
+
+fn update_event(sequence: i64, update: &LiveUpdate) -> Event {
+  Event::default()
+    .id(sequence.to_string())
+    .event(update.name())
+    .json_data(update)
+    .expect("live updates always serialize")
+}
+
+#[instrument(skip_all, fields(game_id))]
+async fn game_events(State(state): State<AppState>, Path(game_id): Path<i32>, headers: HeaderMap) -> Response {
+  Span::current().record("game_id", game_id);
+  let resume_from = headers
+    .get("last-event-id")
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<i64>().ok());
+
+  // subscribing before catching up means no event can slip through in between
+  let events = state.games.event_bus().subscribe(game_id);
+
+  // the feed catches up first, so anything that happens before the snapshot is taken is sent twice rather than lost
+  let feed = match LiveFeed::follow(state.games.clone(), game_id, resume_from).await {
+    Ok(feed) => feed,
+    Err(error) => return error_response(&error),
+  };
+
+  let mut pending = VecDeque::new();
+  if resume_from.is_none() {
+    let game = match state.games.try_query_game(QueryGameRequest { game_id }).await {
+      Ok(response) => response.game,
+      Err(error) => return error_response(&error),
+    };
+
+    let snapshot = Event::default()
+      .id(feed.last_sequence().to_string())
+      .event("snapshot")
+      .json_data(&game)
+      .expect("games always serialize");
+    pending.push_back(snapshot);
+  }
+
+  Sse::new(live_stream(feed, events, pending, state.poll_interval))
+    .keep_alive(KeepAlive::default())
+    .into_response()
+}
+
+/// Checks the event log whenever the event bus has news for the game, or every `poll_interval` to pick up
+/// changes the bus doesn't know about. Ends after sending an `error` event if the log can't be read or replayed.
+fn live_stream(
+  feed: LiveFeed,
+  events: broadcast::Receiver<GameEvent>,
+  pending: VecDeque<Event>,
+  poll_interval: Duration,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+  let mut interval = tokio::time::interval(poll_interval);
+  interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+  // the stream outlives the handler, so it carries the handler's span along
+  let span = Span::current();
+
+  stream::unfold(
+    (Some(feed), events, pending, interval),
+    move |(mut feed, mut events, mut pending, mut interval)| {
+      async move {
+        loop {
+          if let Some(event) = pending.pop_front() {
+            return Some((Ok(event), (feed, events, pending, interval)));
+          }
+
+          let live = feed.as_mut()?;
+          // lagging behind only means several events get picked up in one go
+          tokio::select! {
+            received = events.recv() => {
+              if let Err(broadcast::error::RecvError::Closed) = received {
+                interval.tick().await;
+              }
+            }
+            _ = interval.tick() => {}
+          }
+
+          match live.poll().await {
+            Ok(updates) => pending.extend(updates.iter().map(|(sequence, update)| update_event(*sequence, update))),
+            Err(error) => {
+              tracing::warn!(error = &error as &dyn std::error::Error, "ending live stream");
+              let event = Event::default()
+                .event("error")
+                .json_data(ErrorPayload::from(&error))
+                .expect("errors always serialize");
+              pending.push_back(event);
+              feed = None;
+            }
+          }
+        }
+      }
+      .instrument(span.clone())
+    },
+  )
+}
@@ -1,3 +1,10 @@
-pub async fn create_server() {
-  todo!()
-}
+mod http;
+pub mod live;
+pub mod logging;
+pub mod metrics;
+pub mod protocol;
+pub mod rate_limit;
+mod tcp;
+
+pub use http::{HttpServer, DEFAULT_HTTP_ADDRESS};
+pub use tcp::TcpServer;
@@ -0,0 +1,159 @@
+use game_core::commands::MAX_EVENTS_PER_PAGE;
+use game_core::replay::ReplayState;
+use game_core::types::{Event, EventKind, GameStatus, Games, QueryEventsRequest, ReplayGameRequest, Result};
+use serde::Serialize;
+
+/// A change to a game that spectators can see. Mine placements are left out, since the squares
+/// they are on are secret until someone walks into them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveUpdate {
+  SquareChanged {
+    row: i32,
+    column: i32,
+    health: i32,
+    owner_id: Option<i32>,
+  },
+  SquareConquered {
+    row: i32,
+    column: i32,
+    health: i32,
+    owner_id: i32,
+    previous_owner_id: Option<i32>,
+  },
+  MineDetonated {
+    row: i32,
+    column: i32,
+    placed_by: i32,
+    triggered_by: i32,
+  },
+  StatusChanged {
+    status: GameStatus,
+  },
+  TeamEliminated {
+    team_id: i32,
+  },
+}
+
+impl LiveUpdate {
+  /// Name of the variant, used as the SSE event name.
+  pub fn name(&self) -> &'static str {
+    match self {
+      LiveUpdate::SquareChanged { .. } => "square_changed",
+      LiveUpdate::SquareConquered { .. } => "square_conquered",
+      LiveUpdate::MineDetonated { .. } => "mine_detonated",
+      LiveUpdate::StatusChanged { .. } => "status_changed",
+      LiveUpdate::TeamEliminated { .. } => "team_eliminated",
+    }
+  }
+}
+
+/// Follows a game's event log, replaying each new event to work out what it changed.
+#[derive(Debug)]
+pub struct LiveFeed {
+  games: Games,
+  state: ReplayState,
+}
+
+impl LiveFeed {
+  /// Starts following the game after `after_sequence`, or from its latest event when `None`.
+  pub async fn follow(games: Games, game_id: i32, after_sequence: Option<i64>) -> Result<Self> {
+    let state = games
+      .try_replay_game(ReplayGameRequest {
+        game_id,
+        until_sequence: after_sequence,
+      })
+      .await?
+      .state;
+
+    Ok(Self { games, state })
+  }
+
+  /// Sequence of the last event seen, so a dropped stream can carry on from there.
+  pub fn last_sequence(&self) -> i64 {
+    self.state.sequence
+  }
+
+  /// Updates from every event recorded since the last call, each with the sequence of the event behind it.
+  pub async fn poll(&mut self) -> Result<Vec<(i64, LiveUpdate)>> {
+    let mut updates = Vec::new();
+
+    loop {
+      let page = self
+        .games
+        .try_query_events(QueryEventsRequest {
+          game_id: self.state.game_id,
+          after_sequence: self.state.sequence,
+          limit: Some(MAX_EVENTS_PER_PAGE),
+        })
+        .await?;
+
+      let full_page = page.events.len() as i64 == MAX_EVENTS_PER_PAGE;
+
+      for event in &page.events {
+        let before = self.state.clone();
+        self.state.apply(event)?;
+
+        if event.error_code.is_none() {
+          updates.extend(self.describe(&before, event).map(|update| (event.sequence, update)));
+        }
+      }
+
+      if !full_page {
+        return Ok(updates);
+      }
+    }
+  }
+
+  fn describe(&self, before: &ReplayState, event: &Event) -> Option<LiveUpdate> {
+    let kind = event.kind;
+    match kind {
+      EventKind::GameStarted | EventKind::GameEnded => {
+        return Some(LiveUpdate::StatusChanged {
+          status: self.state.status,
+        })
+      }
+      EventKind::TeamEliminated => return Some(LiveUpdate::TeamEliminated { team_id: event.team_id? }),
+      _ => {}
+    }
+
+    let (row, column) = event.row.zip(event.column)?;
+    let previous = before.square(row, column)?;
+    let square = self.state.square(row, column)?;
+
+    match kind {
+      EventKind::SquareAttacked if previous.mine_triggered_by.is_none() && square.mine_triggered_by.is_some() => {
+        Some(LiveUpdate::MineDetonated {
+          row,
+          column,
+          placed_by: square.mine_placed_by?,
+          triggered_by: square.mine_triggered_by?,
+        })
+      }
+      EventKind::SquareAttacked if previous.owner_id != square.owner_id => Some(LiveUpdate::SquareConquered {
+        row,
+        column,
+        health: square.health,
+        owner_id: square.owner_id?,
+        previous_owner_id: previous.owner_id,
+      }),
+      EventKind::HomeSquareAssigned | EventKind::SquareAttacked | EventKind::SquareDefended | EventKind::SquareImported => {
+        Some(LiveUpdate::SquareChanged {
+          row,
+          column,
+          health: square.health,
+          owner_id: square.owner_id,
+        })
+      }
+      EventKind::GameCreated
+      | EventKind::TeamJoined
+      | EventKind::GameStarted
+      | EventKind::MinePlaced
+      | EventKind::TeamEliminated
+      | EventKind::GameEnded
+      | EventKind::MineImported
+      | EventKind::TeamImported
+      | EventKind::BatchRun => None,
+    }
+  }
+}
@@ -0,0 +1,53 @@
+use std::str::FromStr;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::EnvFilter;
+
+/// What's logged when `RUST_LOG` isn't set. SQL statements are logged by sqlx at `debug`, so
+/// `RUST_LOG=info,sqlx=debug` adds them.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// How log lines are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LogFormat {
+  /// Human readable lines, for running locally.
+  #[default]
+  Text,
+  /// One JSON object per line with the fields of every enclosing span, for log ingestion.
+  Json,
+}
+
+impl FromStr for LogFormat {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value.to_lowercase().as_str() {
+      "text" => Ok(LogFormat::Text),
+      "json" => Ok(LogFormat::Json),
+      _ => Err(format!("unknown log format `{value}`, expected text or json")),
+    }
+  }
+}
+
+/// A subscriber writing events that pass `filter` (in `RUST_LOG` syntax) to `writer`.
+pub fn subscriber<W>(format: LogFormat, filter: &str, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+  W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+  let builder = tracing_subscriber::fmt()
+    .with_env_filter(EnvFilter::new(filter))
+    .with_writer(writer);
+
+  match format {
+    LogFormat::Text => Box::new(builder.finish()),
+    LogFormat::Json => Box::new(builder.json().with_current_span(true).with_span_list(true).finish()),
+  }
+}
+
+/// Logs to stdout for the rest of the process, filtered by `RUST_LOG`. Records logged through the
+/// `log` crate, like sqlx's SQL statements, are logged too.
+pub fn init(format: LogFormat) -> Result<(), TryInitError> {
+  let filter = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_FILTER.to_string());
+  subscriber(format, &filter, std::io::stdout).try_init()
+}
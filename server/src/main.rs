@@ -0,0 +1,68 @@
+use game_core::games;
+use game_core::types::Games;
+use server::logging::{self, LogFormat};
+use server::metrics::Metrics;
+use server::protocol::DEFAULT_ADDRESS;
+use server::{HttpServer, TcpServer, DEFAULT_HTTP_ADDRESS};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// How often started games are settled.
+const SETTLE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+  let log_format = match std::env::var("CNC_LOG_FORMAT") {
+    Ok(value) => value.parse::<LogFormat>()?,
+    Err(_) => LogFormat::default(),
+  };
+  logging::init(log_format)?;
+
+  let address = std::env::var("CNC_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+  let http_address = std::env::var("CNC_HTTP_ADDRESS").unwrap_or_else(|_| DEFAULT_HTTP_ADDRESS.to_string());
+  let database_name = std::env::var("CNC_DATABASE").ok();
+
+  let pool = games::create_pool(database_name.as_deref()).await?;
+
+  // recreates every table, so only do it when asked to
+  if std::env::var("CNC_SETUP_DATABASE").is_ok_and(|value| value == "1") {
+    games::setup_database(&pool).await?;
+  }
+
+  let games = Games::try_new(pool).await?;
+
+  // brings in events from other instances running against the same database
+  let relay = games.try_connect_relay().await?;
+  tokio::spawn(async move {
+    if let Err(error) = relay.run().await {
+      tracing::error!(
+        error = &error as &dyn std::error::Error,
+        "stopped relaying events from other instances"
+      );
+    }
+  });
+
+  // teams are eliminated and games end as time passes, whether or not anyone sends commands
+  let settler = games.clone();
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(SETTLE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+      interval.tick().await;
+      if let Err(error) = settler.try_settle_started_games().await {
+        tracing::warn!(error = &error as &dyn std::error::Error, "failed to settle started games");
+      }
+    }
+  });
+
+  let metrics = Metrics::new();
+  let server = TcpServer::bind(address, games.clone()).await?.with_metrics(metrics.clone());
+  let http_server = HttpServer::bind(http_address, games).await?.with_metrics(metrics);
+  tracing::info!(address = %server.local_addr()?, "listening");
+  tracing::info!("streaming live games on http://{}", http_server.local_addr()?);
+  tracing::info!("serving metrics on http://{}/metrics", http_server.local_addr()?);
+
+  tokio::try_join!(server.run(), http_server.run())?;
+
+  Ok(())
+}
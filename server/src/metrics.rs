@@ -0,0 +1,130 @@
+use crate::protocol::ErrorPayload;
+use game_core::types::{CommandKind, CommandResponse, ErrorCode, Games, Result};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::time::Duration;
+
+/// Prometheus metrics for a server. Clones share the same metrics, so the TCP server can record them
+/// while the HTTP server exposes them.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+  registry: Registry,
+  commands: IntCounterVec,
+  errors: IntCounterVec,
+  command_duration: HistogramVec,
+  db_connections: IntGaugeVec,
+  db_max_connections: IntGauge,
+  active_games: IntGauge,
+  active_teams: IntGauge,
+}
+
+impl Default for Metrics {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Metrics {
+  pub fn new() -> Self {
+    let commands = IntCounterVec::new(
+      Opts::new("cnc_commands_total", "Commands executed, by type of command"),
+      &["command"],
+    )
+    .expect("valid metric");
+    let errors = IntCounterVec::new(
+      Opts::new("cnc_errors_total", "Commands that failed, by error code"),
+      &["code"],
+    )
+    .expect("valid metric");
+    let command_duration = HistogramVec::new(
+      HistogramOpts::new(
+        "cnc_command_duration_seconds",
+        "How long commands took to execute, by type of command",
+      ),
+      &["command"],
+    )
+    .expect("valid metric");
+    let db_connections = IntGaugeVec::new(
+      Opts::new(
+        "cnc_db_connections",
+        "Connections in the database pool, by whether they are in use",
+      ),
+      &["state"],
+    )
+    .expect("valid metric");
+    let db_max_connections =
+      IntGauge::new("cnc_db_max_connections", "Connections the database pool is allowed to open").expect("valid metric");
+    let active_games = IntGauge::new("cnc_active_games", "Games that haven't ended yet").expect("valid metric");
+    let active_teams = IntGauge::new("cnc_active_teams", "Teams in games that haven't ended yet").expect("valid metric");
+
+    let registry = Registry::new();
+    for collector in [
+      Box::new(commands.clone()) as Box<dyn prometheus::core::Collector>,
+      Box::new(errors.clone()),
+      Box::new(command_duration.clone()),
+      Box::new(db_connections.clone()),
+      Box::new(db_max_connections.clone()),
+      Box::new(active_games.clone()),
+      Box::new(active_teams.clone()),
+    ] {
+      registry.register(collector).expect("metric registered twice");
+    }
+
+    Self {
+      registry,
+      commands,
+      errors,
+      command_duration,
+      db_connections,
+      db_max_connections,
+      active_games,
+      active_teams,
+    }
+  }
+
+  /// Records a command that was decoded, including ones turned away before they were executed.
+  pub fn record_command(
+    &self,
+    kind: CommandKind,
+    elapsed: Duration,
+    result: &std::result::Result<CommandResponse, ErrorPayload>,
+  ) {
+    let command: &'static str = kind.into();
+    self.commands.with_label_values(&[command]).inc();
+    self
+      .command_duration
+      .with_label_values(&[command])
+      .observe(elapsed.as_secs_f64());
+    if let Err(error) = result {
+      self.record_error(error.code);
+    }
+  }
+
+  /// Records an error on its own, for requests that never made it to being a command.
+  pub fn record_error(&self, code: ErrorCode) {
+    let code: &'static str = code.into();
+    self.errors.with_label_values(&[code]).inc();
+  }
+
+  /// Samples the database pool and the number of active games, then encodes every metric in the
+  /// Prometheus text format.
+  pub async fn render(&self, games: &Games) -> Result<String> {
+    let load = games.try_query_load().await?;
+    let idle = i64::try_from(load.db_idle_connections).unwrap_or(i64::MAX);
+    self.db_connections.with_label_values(&["idle"]).set(idle);
+    // the pool is sampled twice, so connections can go idle in between
+    self
+      .db_connections
+      .with_label_values(&["in_use"])
+      .set((i64::from(load.db_connections) - idle).max(0));
+    self.db_max_connections.set(i64::from(load.db_max_connections));
+    self.active_games.set(load.active_games);
+    self.active_teams.set(load.active_teams);
+
+    let mut encoded = Vec::new();
+    TextEncoder::new()
+      .encode(&self.registry.gather(), &mut encoded)
+      .expect("metrics encode as text");
+
+    Ok(String::from_utf8(encoded).expect("metrics are utf-8"))
+  }
+}
@@ -0,0 +1,34 @@
+//! Runs decoded requests against the game, see `game_core::protocol` for the wire format.
+
+pub use game_core::protocol::{decode_line, ErrorPayload, RequestEnvelope, ResponseEnvelope, DEFAULT_ADDRESS, MAX_LINE_BYTES};
+use game_core::types::Games;
+
+pub async fn execute(
+  games: &mut Games,
+  RequestEnvelope {
+    id,
+    command,
+    idempotency_key,
+  }: RequestEnvelope,
+) -> ResponseEnvelope {
+  let result = games
+    .try_execute_idempotent(command, idempotency_key.as_deref())
+    .await
+    .map_err(|error| {
+      // the client only gets the message, so the database error behind it has to be logged here
+      if error.is_internal() {
+        tracing::error!(error = &error as &dyn std::error::Error, "command failed");
+      }
+      ErrorPayload::from(&error)
+    });
+
+  ResponseEnvelope { id: Some(id), result }
+}
+
+/// Decodes and executes a single request line. Never fails, errors are sent back to the client instead.
+pub async fn handle_line(games: &mut Games, line: &str) -> ResponseEnvelope {
+  match decode_line(line) {
+    Ok(request) => execute(games, request).await,
+    Err(response) => *response,
+  }
+}
@@ -0,0 +1,202 @@
+use game_core::types::{Command, CommandKind, Error};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Buckets that have refilled completely are dropped once there are more than this many,
+/// so clients that went away don't pile up.
+const PRUNE_ABOVE_BUCKETS: usize = 10_000;
+
+/// Allows bursts of up to `burst` commands, refilling at `per_second` commands a second.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RateLimit {
+  pub burst: u32,
+  pub per_second: f64,
+}
+
+impl RateLimit {
+  pub fn new(burst: u32, per_second: f64) -> Self {
+    Self { burst, per_second }
+  }
+}
+
+/// What a limit is counted against.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+  Connection,
+  Ip,
+  /// Only applies to commands sent on behalf of a team, once their key has checked out, so that
+  /// guessing at a team's key doesn't use up the team's commands. See `RateLimiter::check_team`.
+  Team,
+}
+
+/// Limits for each scope. A limit for a specific kind of command gets its own bucket, every other
+/// command in that scope shares the scope's general limit, if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+  limits: HashMap<(Scope, Option<CommandKind>), RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+  fn default() -> Self {
+    Self::unlimited()
+      .with_limit(Scope::Connection, RateLimit::new(40, 20.0))
+      .with_limit(Scope::Ip, RateLimit::new(100, 50.0))
+      .with_limit(Scope::Team, RateLimit::new(20, 10.0))
+      // creating games and teams costs rows in the database, and nothing else limits it
+      .with_command_limit(Scope::Ip, CommandKind::CreateAndJoin, RateLimit::new(5, 0.1))
+      .with_command_limit(Scope::Ip, CommandKind::JoinExisting, RateLimit::new(10, 0.5))
+  }
+}
+
+impl RateLimitConfig {
+  pub fn unlimited() -> Self {
+    Self { limits: HashMap::new() }
+  }
+
+  pub fn with_limit(mut self, scope: Scope, limit: RateLimit) -> Self {
+    self.limits.insert((scope, None), limit);
+    self
+  }
+
+  pub fn with_command_limit(mut self, scope: Scope, kind: CommandKind, limit: RateLimit) -> Self {
+    self.limits.insert((scope, Some(kind)), limit);
+    self
+  }
+
+  /// The limit that applies, along with the command kind its bucket is kept under.
+  fn limit(&self, scope: Scope, kind: CommandKind) -> Option<(Option<CommandKind>, RateLimit)> {
+    self
+      .limits
+      .get(&(scope, Some(kind)))
+      .map(|limit| (Some(kind), *limit))
+      .or_else(|| self.limits.get(&(scope, None)).map(|limit| (None, *limit)))
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Key {
+  Connection(u64),
+  Ip(IpAddr),
+  Team(i32),
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+  limit: RateLimit,
+  tokens: f64,
+  updated_at: Instant,
+}
+
+impl TokenBucket {
+  fn new(limit: RateLimit, now: Instant) -> Self {
+    Self {
+      limit,
+      tokens: f64::from(limit.burst),
+      updated_at: now,
+    }
+  }
+
+  fn refill(&mut self, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.limit.per_second).min(f64::from(self.limit.burst));
+    self.updated_at = now;
+  }
+
+  /// How long until a token is available, zero if one is available now.
+  fn wait_time(&self) -> Duration {
+    if self.tokens >= 1.0 {
+      return Duration::ZERO;
+    }
+    if self.limit.per_second <= 0.0 {
+      return Duration::MAX;
+    }
+
+    Duration::from_secs_f64((1.0 - self.tokens) / self.limit.per_second)
+  }
+
+  fn is_full(&self) -> bool {
+    self.tokens >= f64::from(self.limit.burst)
+  }
+}
+
+/// Token buckets shared by every connection of a server.
+#[derive(Debug)]
+pub struct RateLimiter {
+  config: RateLimitConfig,
+  buckets: Mutex<HashMap<(Key, Option<CommandKind>), TokenBucket>>,
+}
+
+impl RateLimiter {
+  pub fn new(config: RateLimitConfig) -> Self {
+    Self {
+      config,
+      buckets: Mutex::default(),
+    }
+  }
+
+  /// Takes a token from the connection's and the IP's buckets, or none at all if either of them is empty,
+  /// in which case the error says how long to wait for the emptiest one.
+  pub fn check(&self, connection_id: u64, ip: IpAddr, command: &Command) -> Result<(), Error> {
+    self.take(
+      [(Scope::Connection, Key::Connection(connection_id)), (Scope::Ip, Key::Ip(ip))],
+      command.kind(),
+    )
+  }
+
+  /// Takes a token from the bucket of the team a command was sent on behalf of. Only meant for commands
+  /// whose key has checked out.
+  pub fn check_team(&self, team_id: i32, kind: CommandKind) -> Result<(), Error> {
+    self.take([(Scope::Team, Key::Team(team_id))], kind)
+  }
+
+  fn take(&self, keys: impl IntoIterator<Item = (Scope, Key)>, kind: CommandKind) -> Result<(), Error> {
+    let now = Instant::now();
+
+    let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+    if buckets.len() > PRUNE_ABOVE_BUCKETS {
+      buckets.retain(|_, bucket| {
+        bucket.refill(now);
+        !bucket.is_full()
+      });
+    }
+
+    let applicable = keys
+      .into_iter()
+      .filter_map(|(scope, key)| {
+        let (bucket_kind, limit) = self.config.limit(scope, kind)?;
+        Some(((key, bucket_kind), limit))
+      })
+      .collect::<Vec<_>>();
+
+    let mut wait = Duration::ZERO;
+    for (bucket_key, limit) in &applicable {
+      let bucket = buckets.entry(*bucket_key).or_insert_with(|| TokenBucket::new(*limit, now));
+      bucket.refill(now);
+      wait = wait.max(bucket.wait_time());
+    }
+
+    if !wait.is_zero() {
+      return Err(Error::RateLimited {
+        // rounded up, so waiting exactly this long is never too short
+        retry_after_ms: u64::try_from(wait.as_micros().div_ceil(1000)).unwrap_or(u64::MAX),
+      });
+    }
+
+    for (bucket_key, _) in &applicable {
+      if let Some(bucket) = buckets.get_mut(bucket_key) {
+        bucket.tokens -= 1.0;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Drops the buckets of a connection that has been closed.
+  pub fn forget_connection(&self, connection_id: u64) {
+    let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+    buckets.retain(|(key, _), _| *key != Key::Connection(connection_id));
+  }
+}
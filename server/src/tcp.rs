@@ -0,0 +1,172 @@
+use crate::metrics::Metrics;
+use crate::protocol::{self, ResponseEnvelope};
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use game_core::types::{Command, Error, Games};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tracing::Instrument;
+
+/// How long to wait after failing to accept a connection before accepting again.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Serves the line-delimited JSON protocol over TCP, one task per connection.
+#[derive(Debug)]
+pub struct TcpServer {
+  listener: TcpListener,
+  games: Games,
+  rate_limiter: Arc<RateLimiter>,
+  metrics: Metrics,
+}
+
+impl TcpServer {
+  pub async fn bind(address: impl ToSocketAddrs, games: Games) -> io::Result<Self> {
+    let listener = TcpListener::bind(address).await?;
+    Ok(Self {
+      listener,
+      games,
+      rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::default())),
+      metrics: Metrics::default(),
+    })
+  }
+
+  pub fn with_rate_limits(mut self, config: RateLimitConfig) -> Self {
+    self.rate_limiter = Arc::new(RateLimiter::new(config));
+    self
+  }
+
+  /// Records commands into metrics shared with e.g. the `HttpServer` serving them.
+  pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+    self.metrics = metrics;
+    self
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.listener.local_addr()
+  }
+
+  pub async fn run(self) -> io::Result<()> {
+    let mut next_connection_id = 0;
+
+    loop {
+      let (stream, peer) = match self.listener.accept().await {
+        Ok(accepted) => accepted,
+        Err(error) => {
+          // e.g. out of file descriptors or a connection reset before it was accepted, neither of which
+          // should take the server down; wait a bit for connections to close before trying again
+          tracing::warn!(%error, "failed to accept connection");
+          tokio::time::sleep(ACCEPT_BACKOFF).await;
+          continue;
+        }
+      };
+      let connection_id = next_connection_id;
+      next_connection_id += 1;
+      // failed attempts to authenticate are counted per IP, whichever connection they come from
+      let games = self.games.clone().with_source(peer.ip().to_string());
+      let rate_limiter = self.rate_limiter.clone();
+      let metrics = self.metrics.clone();
+
+      let span = tracing::info_span!("connection", connection_id, %peer);
+
+      tokio::spawn(
+        async move {
+          tracing::debug!("connected");
+          if let Err(error) = handle_connection(stream, peer, connection_id, games, &rate_limiter, &metrics).await {
+            tracing::warn!(%error, "connection failed");
+          }
+          rate_limiter.forget_connection(connection_id);
+          tracing::debug!("disconnected");
+        }
+        .instrument(span),
+      );
+    }
+  }
+}
+
+async fn handle_connection(
+  stream: TcpStream,
+  peer: SocketAddr,
+  connection_id: u64,
+  mut games: Games,
+  rate_limiter: &RateLimiter,
+  metrics: &Metrics,
+) -> io::Result<()> {
+  let (reader, mut writer) = stream.into_split();
+  let mut reader = BufReader::new(reader);
+  let mut buffer = Vec::new();
+
+  loop {
+    buffer.clear();
+    // one byte over the limit is enough to tell a line is too long, without buffering the rest of it
+    let read = (&mut reader)
+      .take(protocol::MAX_LINE_BYTES as u64 + 1)
+      .read_until(b'\n', &mut buffer)
+      .await?;
+    if read == 0 {
+      break;
+    }
+    if buffer.len() > protocol::MAX_LINE_BYTES && !buffer.ends_with(b"\n") {
+      let error = Error::InvalidRequest {
+        reason: format!("requests can't be longer than {} bytes", protocol::MAX_LINE_BYTES),
+      };
+      metrics.record_error(error.code());
+      write_response(&mut writer, &ResponseEnvelope::error(None, &error)).await?;
+      tracing::debug!("request too long, closing the connection");
+      break;
+    }
+
+    let line = std::str::from_utf8(&buffer).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let response = match protocol::decode_line(line) {
+      Ok(request) => {
+        let (kind, started) = (request.command.kind(), Instant::now());
+        let span = tracing::info_span!("request", id = request.id);
+        let checked = match rate_limiter.check(connection_id, peer.ip(), &request.command) {
+          Ok(()) => check_team_limit(&games, rate_limiter, &request.command).await,
+          Err(error) => Err(error),
+        };
+        let response = match checked {
+          Ok(()) => protocol::execute(&mut games, request).instrument(span).await,
+          Err(error) => {
+            span.in_scope(|| tracing::debug!(%error, command = <&'static str>::from(kind), "rate limited"));
+            ResponseEnvelope::error(Some(request.id), &error)
+          }
+        };
+        metrics.record_command(kind, started.elapsed(), &response.result);
+        response
+      }
+      Err(response) => {
+        if let Err(error) = &response.result {
+          metrics.record_error(error.code);
+        }
+        *response
+      }
+    };
+    write_response(&mut writer, &response).await?;
+  }
+
+  Ok(())
+}
+
+async fn write_response(writer: &mut OwnedWriteHalf, response: &ResponseEnvelope) -> io::Result<()> {
+  let mut encoded = serde_json::to_string(response)?;
+  encoded.push('\n');
+  writer.write_all(encoded.as_bytes()).await
+}
+
+/// Counts a command against the limit of the team it was sent on behalf of, once its key checks out. A
+/// wrong key is left for the command to turn away, without using up the team's commands.
+async fn check_team_limit(games: &Games, rate_limiter: &RateLimiter, command: &Command) -> Result<(), Error> {
+  match games.try_verify_sender(command).await? {
+    Some(team_id) => rate_limiter.check_team(team_id, command.kind()),
+    None => Ok(()),
+  }
+}
@@ -0,0 +1,5 @@
+mod report;
+mod runner;
+
+pub use report::{GroupStats, Report};
+pub use runner::{simulate, simulate_game, GameResult, SimulatedTeam, SimulationConfig, StrategyKind};
@@ -0,0 +1,90 @@
+use clap::{Parser, ValueEnum};
+use game_core::types::{MinePenalty, Rules};
+use simulator::{simulate, Report, SimulationConfig, StrategyKind};
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+enum Format {
+  Json,
+  Csv,
+}
+
+/// Plays lots of bot games in memory and reports how the rules hold up.
+#[derive(Debug, Parser)]
+struct Args {
+  #[arg(long, default_value_t = 1000)]
+  games: usize,
+  #[arg(long, default_value_t = 0)]
+  seed: u64,
+  #[arg(long, default_value_t = 4)]
+  teams: usize,
+  #[arg(long, default_value_t = Rules::default().grid_size)]
+  grid_size: i32,
+  #[arg(long, default_value_t = Rules::default().square_health)]
+  square_health: i32,
+  #[arg(long, default_value_t = Rules::default().conquered_health)]
+  conquered_health: i32,
+  #[arg(long, default_value_t = Rules::default().requests_count)]
+  requests: i32,
+  /// Either `all` or the number of extra requests lost when triggering a mine.
+  #[arg(long, default_value = "all", value_parser = parse_mine_penalty)]
+  mine_penalty: MinePenalty,
+  /// Strategies the bots are picked from, defaults to all of them.
+  #[arg(long, value_delimiter = ',')]
+  strategies: Vec<StrategyKind>,
+  #[arg(long, default_value_t = 1000)]
+  max_rounds: usize,
+  #[arg(long, value_enum, default_value_t = Format::Json)]
+  format: Format,
+  /// Writes the report here instead of stdout.
+  #[arg(long)]
+  output: Option<PathBuf>,
+}
+
+fn parse_mine_penalty(value: &str) -> Result<MinePenalty, String> {
+  match value {
+    "all" => Ok(MinePenalty::AllRequests),
+    count => count
+      .parse()
+      .map(MinePenalty::Requests)
+      .map_err(|_| format!("expected `all` or a number of requests, got `{count}`")),
+  }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+  let args = Args::parse();
+
+  let config = SimulationConfig {
+    games: args.games,
+    seed: args.seed,
+    teams: args.teams,
+    rules: Rules {
+      grid_size: args.grid_size,
+      square_health: args.square_health,
+      conquered_health: args.conquered_health,
+      requests_count: args.requests,
+      mine_penalty: args.mine_penalty,
+      ..Rules::default()
+    },
+    strategies: match args.strategies.is_empty() {
+      true => StrategyKind::ALL.to_vec(),
+      false => args.strategies,
+    },
+    max_rounds: args.max_rounds,
+  };
+
+  let results = simulate(&config)?;
+  let report = Report::new(&config, &results);
+
+  let output = match args.format {
+    Format::Json => report.to_json()?,
+    Format::Csv => report.to_csv(),
+  };
+
+  match args.output {
+    Some(path) => std::fs::write(path, output)?,
+    None => println!("{output}"),
+  }
+
+  Ok(())
+}
@@ -0,0 +1,150 @@
+use crate::runner::{GameResult, SimulationConfig};
+use game_core::types::Rules;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupStats {
+  pub name: String,
+  /// Number of teams in this group across all games.
+  pub appearances: usize,
+  pub wins: usize,
+  pub win_rate: f64,
+  pub average_squares: f64,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+  appearances: usize,
+  wins: usize,
+  squares: usize,
+}
+
+impl Tally {
+  fn into_stats(self, name: &str) -> GroupStats {
+    let ratio = |count: usize| {
+      if self.appearances == 0 {
+        0.0
+      } else {
+        count as f64 / self.appearances as f64
+      }
+    };
+
+    GroupStats {
+      name: name.to_string(),
+      appearances: self.appearances,
+      wins: self.wins,
+      win_rate: ratio(self.wins),
+      average_squares: ratio(self.squares),
+    }
+  }
+}
+
+/// Aggregate statistics over a batch of simulated games.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+  pub games: usize,
+  pub seed: u64,
+  pub teams: usize,
+  pub rules: Rules,
+  pub average_rounds: f64,
+  pub average_moves: f64,
+  pub average_rejected_moves: f64,
+  pub mines_triggered: usize,
+  pub draws: usize,
+  pub average_neutral_squares: f64,
+  pub roles: Vec<GroupStats>,
+  pub strategies: Vec<GroupStats>,
+  /// How many games were won with each number of squares.
+  pub winning_territory: BTreeMap<usize, usize>,
+}
+
+impl Report {
+  pub fn new(config: &SimulationConfig, results: &[GameResult]) -> Self {
+    let average = |total: usize| {
+      if results.is_empty() {
+        0.0
+      } else {
+        total as f64 / results.len() as f64
+      }
+    };
+
+    let mut roles = BTreeMap::<&'static str, Tally>::new();
+    let mut strategies = BTreeMap::<&'static str, Tally>::new();
+    let mut winning_territory = BTreeMap::new();
+
+    for result in results {
+      for team in &result.teams {
+        let won = result.winner == Some(team.team_id);
+        if won {
+          *winning_territory.entry(team.squares_owned).or_insert(0) += 1;
+        }
+
+        for tally in [
+          roles.entry(team.role.into()).or_default(),
+          strategies.entry(team.strategy).or_default(),
+        ] {
+          tally.appearances += 1;
+          tally.wins += usize::from(won);
+          tally.squares += team.squares_owned;
+        }
+      }
+    }
+
+    let sum = |field: fn(&GameResult) -> usize| results.iter().map(field).sum::<usize>();
+
+    Self {
+      games: results.len(),
+      seed: config.seed,
+      teams: config.teams,
+      rules: config.rules,
+      average_rounds: average(sum(|result| result.rounds)),
+      average_moves: average(sum(|result| result.moves)),
+      average_rejected_moves: average(sum(|result| result.rejected_moves)),
+      mines_triggered: sum(|result| result.mines_triggered),
+      draws: results.iter().filter(|result| result.winner.is_none()).count(),
+      average_neutral_squares: average(sum(|result| result.neutral_squares)),
+      roles: roles.into_iter().map(|(name, tally)| tally.into_stats(name)).collect(),
+      strategies: strategies.into_iter().map(|(name, tally)| tally.into_stats(name)).collect(),
+      winning_territory,
+    }
+  }
+
+  pub fn to_json(&self) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(self)
+  }
+
+  /// One row per role and strategy, plus an `overall` row that counts games decided rather than teams.
+  pub fn to_csv(&self) -> String {
+    let mut csv = String::from("group,name,appearances,wins,win_rate,average_squares\n");
+
+    let owned_squares = (self.rules.grid_size * self.rules.grid_size) as f64 - self.average_neutral_squares;
+    let overall = GroupStats {
+      name: "all".to_string(),
+      appearances: self.games,
+      wins: self.games - self.draws,
+      win_rate: if self.games == 0 {
+        0.0
+      } else {
+        (self.games - self.draws) as f64 / self.games as f64
+      },
+      average_squares: owned_squares / self.teams.max(1) as f64,
+    };
+
+    let rows = std::iter::once(("overall", &overall))
+      .chain(self.roles.iter().map(|stats| ("role", stats)))
+      .chain(self.strategies.iter().map(|stats| ("strategy", stats)));
+
+    for (group, stats) in rows {
+      // writing to a String can't fail
+      let _ = writeln!(
+        csv,
+        "{group},{},{},{},{:.4},{:.4}",
+        stats.name, stats.appearances, stats.wins, stats.win_rate, stats.average_squares
+      );
+    }
+
+    csv
+  }
+}
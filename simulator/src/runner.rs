@@ -0,0 +1,221 @@
+use bots::{BoardView, GreedyConqueror, Minelayer, Move, RandomAttacker, Strategy, TerritoryDefender};
+use clap::ValueEnum;
+use game_core::replay::{AttackOutcome, ReplayState};
+use game_core::types::{Error, Result, Rules, TeamRole};
+use rand::seq::SliceRandom;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+const ROLES: [TeamRole; 3] = [TeamRole::Minelayer, TeamRole::Cloaker, TeamRole::Spy];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+  RandomAttacker,
+  GreedyConqueror,
+  TerritoryDefender,
+  Minelayer,
+}
+
+impl StrategyKind {
+  pub const ALL: [StrategyKind; 4] = [
+    StrategyKind::RandomAttacker,
+    StrategyKind::GreedyConqueror,
+    StrategyKind::TerritoryDefender,
+    StrategyKind::Minelayer,
+  ];
+
+  pub fn build(self, rules: &Rules) -> Box<dyn Strategy> {
+    match self {
+      StrategyKind::RandomAttacker => Box::new(RandomAttacker),
+      StrategyKind::GreedyConqueror => Box::new(GreedyConqueror),
+      StrategyKind::TerritoryDefender => Box::new(TerritoryDefender {
+        full_health: rules.conquered_health,
+      }),
+      StrategyKind::Minelayer => Box::new(Minelayer),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationConfig {
+  pub games: usize,
+  pub seed: u64,
+  pub teams: usize,
+  pub rules: Rules,
+  /// Each team gets one of these at random, along with a random role.
+  pub strategies: Vec<StrategyKind>,
+  /// Safety net for strategies that keep making moves that get rejected.
+  pub max_rounds: usize,
+}
+
+impl Default for SimulationConfig {
+  fn default() -> Self {
+    Self {
+      games: 1000,
+      seed: 0,
+      teams: 4,
+      rules: Rules::default(),
+      strategies: StrategyKind::ALL.to_vec(),
+      max_rounds: 1000,
+    }
+  }
+}
+
+impl SimulationConfig {
+  pub fn validate(&self) -> Result<()> {
+    self.rules.validate()?;
+
+    let invalid = |reason: &str| {
+      Err(Error::InvalidRules {
+        reason: reason.to_string(),
+      })
+    };
+
+    if self.teams < 1 {
+      return invalid("a game needs at least 1 team");
+    }
+    if self.strategies.is_empty() {
+      return invalid("at least one strategy is needed");
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulatedTeam {
+  pub team_id: i32,
+  pub role: TeamRole,
+  pub strategy: &'static str,
+  pub squares_owned: usize,
+  pub requests_left: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameResult {
+  pub seed: u64,
+  pub rounds: usize,
+  pub moves: usize,
+  pub rejected_moves: usize,
+  pub mines_triggered: usize,
+  /// The team owning the most squares, `None` if that's a tie or nobody owns anything.
+  pub winner: Option<i32>,
+  pub neutral_squares: usize,
+  pub teams: Vec<SimulatedTeam>,
+}
+
+#[derive(Debug)]
+struct Player {
+  team_id: i32,
+  role: TeamRole,
+  strategy: Box<dyn Strategy>,
+}
+
+/// Plays a single game in memory until no team can act any more. Every round, each team
+/// takes one turn in a random order.
+pub fn simulate_game(config: &SimulationConfig, seed: u64) -> Result<GameResult> {
+  let mut rng = ChaCha8Rng::seed_from_u64(seed);
+  let mut state = ReplayState::new(0, config.rules);
+
+  let mut players = Vec::with_capacity(config.teams);
+  for team_id in 1..=config.teams as i32 {
+    let role = *ROLES.choose(&mut rng).expect("roles are not empty");
+    let strategy = config.strategies.choose(&mut rng).ok_or(Error::InvalidRules {
+      reason: "at least one strategy is needed".to_string(),
+    })?;
+
+    state.join(team_id)?;
+    players.push(Player {
+      team_id,
+      role,
+      strategy: strategy.build(&config.rules),
+    });
+  }
+
+  state.start()?;
+
+  let mut result = GameResult {
+    seed,
+    rounds: 0,
+    moves: 0,
+    rejected_moves: 0,
+    mines_triggered: 0,
+    winner: None,
+    neutral_squares: 0,
+    teams: Vec::new(),
+  };
+
+  let mut order = (0..players.len()).collect::<Vec<_>>();
+
+  while result.rounds < config.max_rounds {
+    order.shuffle(&mut rng);
+    let mut anyone_moved = false;
+
+    for &index in &order {
+      let player = &mut players[index];
+      let Some(view) = BoardView::from_state(&state, player.team_id, player.role).filter(BoardView::can_act) else {
+        continue;
+      };
+      let Some(action) = player.strategy.next_move(&view, &mut rng as &mut dyn RngCore) else {
+        continue;
+      };
+      anyone_moved = true;
+
+      let outcome = match action {
+        Move::Attack { row, column } => state.attack(player.team_id, row, column).map(Some),
+        Move::Defend { row, column } => state.defend(player.team_id, row, column).map(|_| None),
+        Move::PlaceMine { .. } if player.role != TeamRole::Minelayer => {
+          Err(Error::OnlyMinelayersCanPlaceMines { team_role: player.role })
+        }
+        Move::PlaceMine { row, column } => state.place_mine(player.team_id, row, column).map(|_| None),
+      };
+
+      match outcome {
+        Ok(Some(AttackOutcome::MineTriggered { .. })) => {
+          result.moves += 1;
+          result.mines_triggered += 1;
+        }
+        Ok(_) => result.moves += 1,
+        Err(_) => result.rejected_moves += 1,
+      }
+    }
+
+    if !anyone_moved {
+      break;
+    }
+    result.rounds += 1;
+  }
+
+  let squares_owned_by = |team_id: i32| state.grid.iter().filter(|square| square.owner_id == Some(team_id)).count();
+
+  result.neutral_squares = state.grid.iter().filter(|square| square.owner_id.is_none()).count();
+  result.teams = players
+    .iter()
+    .map(|player| SimulatedTeam {
+      team_id: player.team_id,
+      role: player.role,
+      strategy: player.strategy.name(),
+      squares_owned: squares_owned_by(player.team_id),
+      requests_left: state.teams[&player.team_id].requests_left,
+    })
+    .collect();
+
+  let most_owned = result.teams.iter().map(|team| team.squares_owned).max().unwrap_or(0);
+  let mut leaders = result.teams.iter().filter(|team| team.squares_owned == most_owned);
+  result.winner = match (leaders.next(), leaders.next()) {
+    (Some(leader), None) if most_owned > 0 => Some(leader.team_id),
+    _ => None,
+  };
+
+  Ok(result)
+}
+
+/// Plays `config.games` games, each with its own seed drawn from `config.seed`.
+pub fn simulate(config: &SimulationConfig) -> Result<Vec<GameResult>> {
+  config.validate()?;
+
+  let mut seeds = ChaCha8Rng::seed_from_u64(config.seed);
+  (0..config.games).map(|_| simulate_game(config, seeds.next_u64())).collect()
+}
@@ -0,0 +1,7 @@
+mod source;
+mod state;
+mod ui;
+
+pub use source::{Source, Update};
+pub use state::{LeaderboardEntry, SpectatorState, MAX_FEED_ENTRIES};
+pub use ui::draw;
@@ -0,0 +1,78 @@
+use clap::Parser;
+use client::{Client, DEFAULT_ADDRESS};
+use game_core::games;
+use game_core::types::Games;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use spectator::{draw, Source, SpectatorState};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Shows a game live in the terminal, for putting on the big screen.
+#[derive(Debug, Parser)]
+struct Args {
+  #[arg(long)]
+  game: i32,
+  /// Address of the game server to watch through.
+  #[arg(long, env = "CNC_ADDRESS", default_value = DEFAULT_ADDRESS)]
+  address: String,
+  /// Reads straight from this database instead of going through the server, which adds the event log to the feed.
+  #[arg(long)]
+  database: Option<String>,
+  #[arg(long, default_value_t = 500)]
+  interval_ms: u64,
+}
+
+/// Forwards key presses from a blocking thread, so they don't hold up the async loop.
+fn spawn_key_reader() -> mpsc::UnboundedReceiver<KeyCode> {
+  let (sender, receiver) = mpsc::unbounded_channel();
+
+  std::thread::spawn(move || {
+    while let Ok(event) = event::read() {
+      if let Event::Key(key) = event {
+        if key.kind == KeyEventKind::Press && sender.send(key.code).is_err() {
+          break;
+        }
+      }
+    }
+  });
+
+  receiver
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+  let args = Args::parse();
+
+  let mut source = match &args.database {
+    Some(database_name) => Source::Database(Games::try_new(games::create_pool(Some(database_name)).await?).await?),
+    None => Source::Server(Client::connect(&args.address).await?),
+  };
+
+  let mut state = SpectatorState::default();
+  let mut terminal = ratatui::init();
+  let mut keys = spawn_key_reader();
+  let mut interval = tokio::time::interval(Duration::from_millis(args.interval_ms));
+
+  let result = loop {
+    tokio::select! {
+      _ = interval.tick() => {
+        match source.poll(args.game, state.last_sequence).await {
+          Ok(update) => state.apply(update),
+          Err(error) => state.last_error = Some(error.to_string()),
+        }
+      }
+      Some(key) = keys.recv() => {
+        if matches!(key, KeyCode::Char('q') | KeyCode::Esc) {
+          break Ok(());
+        }
+      }
+    }
+
+    if let Err(error) = terminal.draw(|frame| draw(frame, &state)) {
+      break Err(error);
+    }
+  };
+
+  ratatui::restore();
+  Ok(result?)
+}
@@ -0,0 +1,64 @@
+use client::Client;
+use game_core::commands::MAX_EVENTS_PER_PAGE;
+use game_core::types::{Command, CommandResponse, Event, Game, Games, QueryEventsRequest, QueryGameRequest};
+
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Where the spectator gets its updates from.
+#[derive(Debug)]
+pub enum Source {
+  /// Through the game server like any team, so only what a query shows. The feed is worked out
+  /// from what changed between polls.
+  Server(Client),
+  /// Straight from the database, with the event log for the feed.
+  Database(Games),
+}
+
+#[derive(Debug)]
+pub struct Update {
+  pub game: Game,
+  /// Events after the requested sequence, `None` if the source has no access to the event log.
+  pub events: Option<Vec<Event>>,
+}
+
+impl Source {
+  pub async fn poll(&mut self, game_id: i32, after_sequence: i64) -> Result<Update, SourceError> {
+    match self {
+      Source::Server(client) => match client.execute(Command::QueryGame(QueryGameRequest { game_id })).await? {
+        CommandResponse::QueryGame(response) => Ok(Update {
+          game: response.game,
+          events: None,
+        }),
+        other => Err(format!("expected a QueryGame response, received {}", <&'static str>::from(&other)).into()),
+      },
+      Source::Database(games) => {
+        let game = games.try_query_game(QueryGameRequest { game_id }).await?.game;
+
+        let mut events = Vec::new();
+        let mut after_sequence = after_sequence;
+        loop {
+          let page = games
+            .try_query_events(QueryEventsRequest {
+              game_id,
+              after_sequence,
+              limit: Some(MAX_EVENTS_PER_PAGE),
+            })
+            .await?;
+
+          let full_page = page.events.len() as i64 == MAX_EVENTS_PER_PAGE;
+          after_sequence = page.last_sequence;
+          events.extend(page.events);
+
+          if !full_page {
+            break;
+          }
+        }
+
+        Ok(Update {
+          game,
+          events: Some(events),
+        })
+      }
+    }
+  }
+}
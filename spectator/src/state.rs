@@ -0,0 +1,179 @@
+use crate::source::Update;
+use game_core::commands::CONQUERED_SQUARE_HEALTH;
+use game_core::types::{DateTimeUtc, Event, EventKind, Game, GameStatus};
+use std::collections::VecDeque;
+
+/// Older entries are dropped from the feed once it grows past this.
+pub const MAX_FEED_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+  pub team_id: i32,
+  pub display_name: String,
+  pub squares: usize,
+  pub total_health: i32,
+  pub requests_left: i32,
+  pub eliminated_at: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Default)]
+pub struct SpectatorState {
+  pub game: Option<Game>,
+  pub last_sequence: i64,
+  /// Newest entries last.
+  pub feed: VecDeque<String>,
+  pub last_error: Option<String>,
+}
+
+fn team_name(game: &Game, team_id: Option<i32>) -> String {
+  let Some(team_id) = team_id else {
+    return "an unknown team".to_string();
+  };
+
+  game
+    .teams
+    .iter()
+    .find(|team| team.id == team_id)
+    .map_or_else(|| format!("team {team_id}"), |team| team.display_name.clone())
+}
+
+/// Mine coordinates are left out, the screen is usually in view of the players.
+fn describe_event(game: &Game, event: &Event) -> String {
+  let name = team_name(game, event.team_id);
+  let square = format!("({}, {})", event.row.unwrap_or_default(), event.column.unwrap_or_default());
+  let health = event.health.unwrap_or_default();
+
+  if let Some(error_code) = &event.error_code {
+    return format!("{name}: {:?} rejected ({error_code})", event.kind);
+  }
+
+  match event.kind {
+    EventKind::GameCreated => format!("{name} created the game"),
+    EventKind::TeamJoined => format!("{name} joined"),
+    EventKind::GameStarted => "the game has started".to_string(),
+    EventKind::HomeSquareAssigned => format!("{name} starts from {square}"),
+    EventKind::SquareAttacked if event.owner_id == event.team_id && health == CONQUERED_SQUARE_HEALTH => {
+      format!("{name} conquered {square}")
+    }
+    EventKind::SquareAttacked => format!("{name} attacked {square}, health {health}"),
+    EventKind::SquareDefended => format!("{name} defended {square}, health {health}"),
+    EventKind::MinePlaced => format!("{name} placed a mine"),
+    EventKind::TeamEliminated => format!("{name} was eliminated"),
+    EventKind::GameEnded if event.team_id.is_some() => format!("the game has ended, {name} won"),
+    EventKind::GameEnded => "the game has ended".to_string(),
+    EventKind::SquareImported => format!("{square} was imported with health {health}"),
+    EventKind::MineImported => format!("{name}'s mine was imported"),
+    EventKind::TeamImported => format!("{name} was imported"),
+    EventKind::BatchRun => format!("{name} ran a batch"),
+  }
+}
+
+/// Best guess at what happened between two polls, for sources without an event log.
+fn describe_changes(previous: &Game, game: &Game) -> Vec<String> {
+  let mut changes = Vec::new();
+
+  for team in &game.teams {
+    match previous.teams.iter().find(|previous_team| previous_team.id == team.id) {
+      None => changes.push(format!("{} joined", team.display_name)),
+      Some(before) if before.eliminated_at.is_none() && team.eliminated_at.is_some() => {
+        changes.push(format!("{} was eliminated", team.display_name))
+      }
+      Some(_) => {}
+    }
+  }
+
+  if previous.status != game.status {
+    changes.push(match game.status {
+      GameStatus::WaitingForRegistrations => "the game is waiting for teams".to_string(),
+      GameStatus::Started => "the game has started".to_string(),
+      GameStatus::Ended => "the game has ended".to_string(),
+    });
+  }
+
+  for square in &game.grid {
+    let Some(before) = previous
+      .grid
+      .iter()
+      .find(|before| (before.row, before.column) == (square.row, square.column))
+    else {
+      continue;
+    };
+
+    let position = format!("({}, {})", square.row, square.column);
+    if before.owner_id != square.owner_id {
+      changes.push(format!("{} conquered {position}", team_name(game, square.owner_id)));
+    } else if square.health < before.health {
+      changes.push(format!("{position} was attacked, health {}", square.health));
+    } else if square.health > before.health {
+      changes.push(format!("{position} was defended, health {}", square.health));
+    }
+  }
+
+  changes
+}
+
+impl SpectatorState {
+  fn push_feed(&mut self, entry: String) {
+    self.feed.push_back(entry);
+    while self.feed.len() > MAX_FEED_ENTRIES {
+      self.feed.pop_front();
+    }
+  }
+
+  pub fn apply(&mut self, update: Update) {
+    let Update { game, events } = update;
+
+    let entries = match (&self.game, events) {
+      (_, Some(events)) => {
+        if let Some(last) = events.last() {
+          self.last_sequence = last.sequence;
+        }
+        events.iter().map(|event| describe_event(&game, event)).collect()
+      }
+      (Some(previous), None) => describe_changes(previous, &game),
+      (None, None) => vec![format!("watching game {}", game.id)],
+    };
+
+    for entry in entries {
+      self.push_feed(entry);
+    }
+
+    self.game = Some(game);
+    self.last_error = None;
+  }
+
+  /// Teams ranked by squares owned, then by the total health of those squares. Eliminated teams come
+  /// last, the ones that held out the longest first.
+  pub fn leaderboard(&self) -> Vec<LeaderboardEntry> {
+    let Some(game) = &self.game else {
+      return Vec::new();
+    };
+
+    let mut entries = game
+      .teams
+      .iter()
+      .map(|team| {
+        let owned = game.grid.iter().filter(|square| square.owner_id == Some(team.id));
+        LeaderboardEntry {
+          team_id: team.id,
+          display_name: team.display_name.clone(),
+          squares: owned.clone().count(),
+          total_health: owned.map(|square| square.health).sum(),
+          requests_left: team.requests_left,
+          eliminated_at: team.eliminated_at,
+        }
+      })
+      .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| {
+      a.eliminated_at
+        .is_some()
+        .cmp(&b.eliminated_at.is_some())
+        .then(b.eliminated_at.cmp(&a.eliminated_at))
+        .then(b.squares.cmp(&a.squares))
+        .then(b.total_health.cmp(&a.total_health))
+        .then(a.team_id.cmp(&b.team_id))
+    });
+    entries
+  }
+}
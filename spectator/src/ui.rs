@@ -0,0 +1,167 @@
+use crate::state::SpectatorState;
+use game_core::commands::CONQUERED_SQUARE_HEALTH;
+use game_core::types::{Game, GridSquare};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table};
+use ratatui::Frame;
+
+const TEAM_COLOURS: [Color; 8] = [
+  Color::Red,
+  Color::Green,
+  Color::Blue,
+  Color::Magenta,
+  Color::Cyan,
+  Color::Yellow,
+  Color::LightRed,
+  Color::LightGreen,
+];
+
+/// Colours are handed out in team id order, so they stay put as teams join.
+fn team_colour(game: &Game, team_id: Option<i32>) -> Color {
+  let mut team_ids = game.teams.iter().map(|team| team.id).collect::<Vec<_>>();
+  team_ids.sort_unstable();
+
+  team_id
+    .and_then(|team_id| team_ids.iter().position(|id| *id == team_id))
+    .map_or(Color::DarkGray, |index| TEAM_COLOURS[index % TEAM_COLOURS.len()])
+}
+
+fn health_bar(health: i32, width: u16) -> String {
+  let width = i32::from(width);
+  let filled = (health.clamp(0, CONQUERED_SQUARE_HEALTH) * width / CONQUERED_SQUARE_HEALTH).clamp(0, width);
+  format!("{}{}", "█".repeat(filled as usize), "░".repeat((width - filled) as usize))
+}
+
+fn draw_square(frame: &mut Frame, area: Rect, game: &Game, square: &GridSquare) {
+  let colour = team_colour(game, square.owner_id);
+  let owner = square.owner_id.map_or_else(
+    || "-".to_string(),
+    |owner_id| {
+      game
+        .teams
+        .iter()
+        .find(|team| team.id == owner_id)
+        .map_or_else(|| owner_id.to_string(), |team| team.display_name.clone())
+    },
+  );
+
+  let block = Block::default()
+    .borders(Borders::ALL)
+    .border_style(Style::default().fg(colour))
+    .title(Span::styled(owner, Style::default().fg(colour).add_modifier(Modifier::BOLD)));
+  let inner_width = area.width.saturating_sub(2);
+
+  let mut stats = vec![Span::raw(format!("{}", square.health))];
+  if square.bonus > 0 {
+    stats.push(Span::styled(
+      format!(" +{}", square.bonus),
+      Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+  }
+
+  let lines = vec![
+    Line::from(Span::styled(
+      health_bar(square.health, inner_width),
+      Style::default().fg(colour),
+    )),
+    Line::from(stats),
+  ];
+
+  frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_grid(frame: &mut Frame, area: Rect, game: &Game) {
+  let rows = game.grid.iter().map(|square| square.row).max().map_or(0, |row| row + 1);
+  let columns = game
+    .grid
+    .iter()
+    .map(|square| square.column)
+    .max()
+    .map_or(0, |column| column + 1);
+
+  let title = format!("game {} ({:?})", game.id, game.status);
+  let block = Block::default().borders(Borders::ALL).title(title);
+  let inner = block.inner(area);
+  frame.render_widget(block, area);
+
+  let row_areas = Layout::vertical((0..rows).map(|_| Constraint::Ratio(1, rows as u32))).split(inner);
+  for (row, row_area) in (0..rows).zip(row_areas.iter()) {
+    let cell_areas = Layout::horizontal((0..columns).map(|_| Constraint::Ratio(1, columns as u32))).split(*row_area);
+    for (column, cell_area) in (0..columns).zip(cell_areas.iter()) {
+      if let Some(square) = game.grid.iter().find(|square| square.row == row && square.column == column) {
+        draw_square(frame, *cell_area, game, square);
+      }
+    }
+  }
+}
+
+fn draw_leaderboard(frame: &mut Frame, area: Rect, state: &SpectatorState, game: &Game) {
+  let rows = state.leaderboard().into_iter().enumerate().map(|(rank, entry)| {
+    Row::new(vec![
+      format!("{}", rank + 1),
+      entry.display_name,
+      entry.squares.to_string(),
+      entry.total_health.to_string(),
+      entry.requests_left.to_string(),
+      entry
+        .eliminated_at
+        .map_or_else(String::new, |eliminated_at| eliminated_at.format("%H:%M:%S").to_string()),
+    ])
+    .style(Style::default().fg(team_colour(game, Some(entry.team_id))))
+  });
+
+  let table = Table::new(
+    rows,
+    [
+      Constraint::Length(3),
+      Constraint::Min(10),
+      Constraint::Length(7),
+      Constraint::Length(6),
+      Constraint::Length(8),
+      Constraint::Length(10),
+    ],
+  )
+  .header(
+    Row::new(vec!["#", "team", "squares", "health", "requests", "out at"]).style(Style::default().add_modifier(Modifier::BOLD)),
+  )
+  .block(Block::default().borders(Borders::ALL).title("leaderboard"));
+
+  frame.render_widget(table, area);
+}
+
+fn draw_feed(frame: &mut Frame, area: Rect, state: &SpectatorState) {
+  let visible = usize::from(area.height.saturating_sub(2));
+  let skip = state.feed.len().saturating_sub(visible);
+  let items = state.feed.iter().skip(skip).map(|entry| ListItem::new(entry.as_str()));
+
+  let title = match &state.last_error {
+    Some(error) => format!("feed (last update failed: {error})"),
+    None => "feed".to_string(),
+  };
+
+  frame.render_widget(
+    List::new(items).block(Block::default().borders(Borders::ALL).title(title)),
+    area,
+  );
+}
+
+/// Grid on the left, leaderboard and the most recent feed entries on the right.
+pub fn draw(frame: &mut Frame, state: &SpectatorState) {
+  let Some(game) = &state.game else {
+    let message = state.last_error.as_deref().unwrap_or("waiting for the first update...");
+    frame.render_widget(
+      Paragraph::new(message).block(Block::default().borders(Borders::ALL)),
+      frame.area(),
+    );
+    return;
+  };
+
+  let [grid_area, side_area] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(frame.area());
+  let [leaderboard_area, feed_area] = Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(side_area);
+
+  draw_grid(frame, grid_area, game);
+  draw_leaderboard(frame, leaderboard_area, state, game);
+  draw_feed(frame, feed_area, state);
+}
@@ -0,0 +1,55 @@
+use client::Credentials;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+pub const DEFAULT_CREDENTIALS_PATH: &str = ".cnc-credentials.json";
+
+/// Team credentials for every game joined from this machine, so later commands don't need the key again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CredentialsStore {
+  /// The game joined most recently, used when a command doesn't name one.
+  pub current_game: Option<i32>,
+  pub games: BTreeMap<i32, Credentials>,
+}
+
+impl CredentialsStore {
+  /// A missing file is treated as an empty store.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    match std::fs::read_to_string(path) {
+      Ok(contents) => serde_json::from_str(&contents).map_err(io::Error::from),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+      Err(error) => Err(error),
+    }
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+    // the file holds team keys, so keep it private to the current user
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+  }
+
+  /// Stores the credentials and makes their game the current one.
+  pub fn insert(&mut self, credentials: Credentials) {
+    self.current_game = Some(credentials.game_id);
+    self.games.insert(credentials.game_id, credentials);
+  }
+
+  /// Credentials for `game_id`, or for the current game if it's `None`.
+  pub fn get(&self, game_id: Option<i32>) -> Option<&Credentials> {
+    game_id.or(self.current_game).and_then(|game_id| self.games.get(&game_id))
+  }
+}
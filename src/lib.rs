@@ -0,0 +1,2 @@
+pub mod credentials;
+pub mod render;
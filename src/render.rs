@@ -0,0 +1,62 @@
+use game_core::types::{Game, GridSquare};
+use std::fmt::Write;
+
+fn cell(square: &GridSquare) -> String {
+  let owner = square
+    .owner_id
+    .map_or_else(|| "-".to_string(), |owner_id| owner_id.to_string());
+  let mine = match &square.mine {
+    Some(mine) if mine.triggered_by.is_some() => "x",
+    Some(_) => "*",
+    None => " ",
+  };
+  format!("{owner:>4}:{:<3}{mine}", square.health)
+}
+
+/// Renders the grid as rows of `owner:health` cells, with `*` marking a mine and `x` a triggered one.
+pub fn render_grid(grid: &[GridSquare]) -> String {
+  let rows = grid.iter().map(|square| square.row).max().map_or(0, |row| row + 1);
+  let columns = grid.iter().map(|square| square.column).max().map_or(0, |column| column + 1);
+
+  let mut output = String::from("   ");
+  for column in 0..columns {
+    let _ = write!(output, " {column:^9}");
+  }
+  output.push('\n');
+
+  for row in 0..rows {
+    let _ = write!(output, "{row:>3}");
+    for column in 0..columns {
+      let square = grid.iter().find(|square| square.row == row && square.column == column);
+      let _ = write!(output, " {}", square.map_or_else(|| format!("{:^9}", "?"), cell));
+    }
+    output.push('\n');
+  }
+
+  output
+}
+
+pub fn render_game(game: &Game) -> String {
+  let mut output = format!("game {} ({:?}), created {}\n\n", game.id, game.status, game.created_at);
+
+  let _ = writeln!(
+    output,
+    "{:>4}  {:<30} {:<10} {:>8}  role used",
+    "id", "team", "role", "requests"
+  );
+  for team in &game.teams {
+    let _ = writeln!(
+      output,
+      "{:>4}  {:<30} {:<10} {:>8}  {}",
+      team.id,
+      team.display_name,
+      format!("{:?}", team.role),
+      team.requests_left,
+      if team.role_used { "yes" } else { "no" }
+    );
+  }
+
+  output.push('\n');
+  output.push_str(&render_grid(&game.grid));
+  output
+}
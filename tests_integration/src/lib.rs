@@ -1,6 +1,7 @@
 use game_core::games;
 use game_core::types::{
-  CreateAndJoinRequest, GameStatus, Games, JoinExistingRequest, Result, SenderDetails, StartRequest, TeamRole,
+  CreateAndJoinRequest, GameStatus, Games, JoinExistingRequest, KeyGenerator, OsKeyGenerator, PgPool, Result, SenderDetails,
+  StartRequest, TeamRole,
 };
 
 #[derive(Debug)]
@@ -10,36 +11,38 @@ pub struct TestSetup {
   pub added: Vec<(i32, String)>,
 }
 
+pub async fn create_test_pool() -> PgPool {
+  let hex = OsKeyGenerator::default().generate_key().unwrap();
+  let database_name = format!("test_{hex}");
+  let pool = games::create_pool(None).await.unwrap();
+  let _ = sqlx::query(&format!("CREATE DATABASE {database_name};"))
+    .execute(&pool)
+    .await
+    .unwrap();
+  let _ = sqlx::query(&format!("ALTER DATABASE {database_name} SET log_statement = 'all';"))
+    .execute(&pool)
+    .await
+    .unwrap();
+  let pool = games::create_pool(Some(&database_name)).await.unwrap();
+  games::setup_database(&pool).await.unwrap();
+  pool
+}
+
 pub async fn setup_with_players<'b, T>(teams: impl IntoIterator<Item = T>) -> Result<TestSetup>
 where
   T: core::borrow::Borrow<(&'b str, TeamRole)>,
 {
-  let hex = games::create_random_hex().await.unwrap();
-  let database_name = format!("test_{hex}");
-  let pool = {
-    let pool = games::create_pool(None).await.unwrap();
-    let _ = sqlx::query(&format!("CREATE DATABASE {database_name};"))
-      .execute(&pool)
-      .await
-      .unwrap();
-    let _ = sqlx::query(&format!("ALTER DATABASE {database_name} SET log_statement = 'all';"))
-      .execute(&pool)
-      .await
-      .unwrap();
-    let pool = games::create_pool(Some(&database_name)).await.unwrap();
-    games::setup_database(&pool).await.unwrap();
-    pool
-  };
-
+  let pool = create_test_pool().await;
   let mut games = games::Games::try_new(pool).await?;
   let mut added = Vec::new();
-  let mut teams_iter = teams.into_iter().map(|team| team.borrow().clone());
+  let mut teams_iter = teams.into_iter().map(|team| *team.borrow());
 
   let game_id = {
     let (display_name, role) = teams_iter.next().unwrap();
     let request = CreateAndJoinRequest {
       display_name: display_name.to_string(),
       team_role: role,
+      options: Default::default(),
     };
     let response = games.try_create_and_join_a_game(request).await.unwrap();
     added.push((response.team_id, response.team_key));
@@ -57,11 +60,7 @@ where
       .map(|response| added.push((response.team_id, response.team_key)))?;
   }
 
-  Ok(TestSetup {
-    games,
-    game_id: game_id,
-    added,
-  })
+  Ok(TestSetup { games, game_id, added })
 }
 
 pub async fn start_game(games: &mut Games, game_id: i32, host_id: i32, host_key: String) {
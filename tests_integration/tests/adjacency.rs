@@ -0,0 +1,90 @@
+use game_core::types::{
+  AttackRange, AttackRequest, AttackResponse, CreateAndJoinRequest, Error, GameOptions, Games, JoinExistingRequest,
+  QueryGameRequest, Result, SenderDetails, TeamRole,
+};
+use rstest::*;
+use tests_integration::{create_test_pool, start_game};
+
+async fn attack(games: &mut Games, game_id: i32, sender: &SenderDetails, (row, column): (i32, i32)) -> Result<AttackResponse> {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: row,
+      column_index: column,
+    })
+    .await
+}
+
+/// A started game with adjacent attacks, where blue has just conquered (0, 0) with the last of the
+/// square's health after red and green wore it down.
+async fn blue_owns_a_corner() -> (Games, i32, [SenderDetails; 3]) {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Spy,
+      options: GameOptions {
+        attack_range: AttackRange::Adjacent,
+        ..Default::default()
+      },
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+
+  let mut teams = vec![SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key.clone(),
+  }];
+  for name in ["green", "blue"] {
+    let joined = games
+      .try_join_an_existing_game(JoinExistingRequest {
+        game_id,
+        display_name: name.to_string(),
+        team_role: TeamRole::Spy,
+      })
+      .await
+      .unwrap();
+    teams.push(SenderDetails {
+      team_id: joined.team_id,
+      team_key: joined.team_key,
+    });
+  }
+  start_game(&mut games, game_id, created.team_id, created.team_key).await;
+
+  let [red, green, blue]: [SenderDetails; 3] = teams.try_into().unwrap();
+  for _ in 0..30 {
+    attack(&mut games, game_id, &red, (0, 0)).await.unwrap();
+  }
+  for _ in 0..29 {
+    attack(&mut games, game_id, &green, (0, 0)).await.unwrap();
+  }
+  assert!(attack(&mut games, game_id, &blue, (0, 0)).await.unwrap().conquered);
+
+  (games, game_id, [red, green, blue])
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_teams_should_only_reach_squares_next_to_their_own() {
+  let (mut games, game_id, [_, green, blue]) = blue_owns_a_corner().await;
+
+  let result = attack(&mut games, game_id, &blue, (2, 2)).await;
+  assert_eq!(result.unwrap_err(), Error::SquareNotReachable { row: 2, column: 2 });
+  // diagonals aren't adjacent
+  let result = attack(&mut games, game_id, &blue, (1, 1)).await;
+  assert_eq!(result.unwrap_err(), Error::SquareNotReachable { row: 1, column: 1 });
+
+  attack(&mut games, game_id, &blue, (0, 1)).await.unwrap();
+  attack(&mut games, game_id, &blue, (1, 0)).await.unwrap();
+
+  // green owns nothing yet, so it can start anywhere
+  attack(&mut games, game_id, &green, (4, 4)).await.unwrap();
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.options.attack_range, AttackRange::Adjacent);
+  let blue_team = game.teams.iter().find(|team| team.id == blue.team_id).unwrap();
+  // unreachable squares don't cost a request
+  assert_eq!(blue_team.requests_left, 27);
+}
@@ -1,4 +1,7 @@
-use game_core::types::{AttackRequest, AttackResponse, Error, GameStatus, SenderDetails, TeamRole};
+use game_core::types::{
+  AttackRequest, AttackResponse, Error, ExportGameRequest, GameStatus, Mine, PlaceMineRequest, QueryGameRequest, SenderDetails,
+  TeamRole,
+};
 use rstest::*;
 use tests_integration::{setup_with_players, start_game, TestSetup};
 
@@ -180,3 +183,171 @@ async fn test_should_be_able_to_conquer_a_square(
     assert_eq!(square.health, 120);
   }
 }
+
+async fn attack(
+  games: &mut game_core::types::Games,
+  game_id: i32,
+  sender: &SenderDetails,
+  (row, column): (i32, i32),
+) -> AttackResponse {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: row,
+      column_index: column,
+    })
+    .await
+    .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_attacking_another_teams_mine_should_trigger_it_instead_of_damaging_the_square() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[
+    ("layer", TeamRole::Minelayer),
+    ("victim", TeamRole::Spy),
+    ("next", TeamRole::Spy),
+  ])
+  .await
+  .unwrap();
+  let [layer, victim, next] = [0, 1, 2].map(|index| SenderDetails {
+    team_id: added[index].0,
+    team_key: added[index].1.clone(),
+  });
+
+  start_game(&mut games, game_id, layer.team_id, layer.team_key.clone()).await;
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: layer.clone(),
+      row_index: 2,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+
+  // the mine takes the hit, and costs the attacker every request it had left
+  let triggered = attack(&mut games, game_id, &victim, (2, 1)).await;
+  assert!(!triggered.conquered);
+  assert_eq!(triggered.requests_left, 0);
+  assert_eq!((triggered.square.owner_id, triggered.square.health), (None, 60));
+  let Mine { placed_by, triggered_by } = triggered.square.mine.unwrap();
+  assert_eq!((placed_by, triggered_by), (layer.team_id, Some(victim.team_id)));
+
+  let error = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: victim.clone(),
+      row_index: 0,
+      column_index: 0,
+    })
+    .await
+    .unwrap_err();
+  assert_eq!(error, Error::NoMoreRequestsLeft);
+
+  // a triggered mine is spent, the next attack damages the square as usual
+  let after = attack(&mut games, game_id, &next, (2, 1)).await;
+  assert_eq!(after.requests_left, 29);
+  assert_eq!(after.square.health, 59);
+  assert!(after.square.mine.is_none());
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  assert_eq!(snapshot.mines[0].triggerer_id, Some(victim.team_id));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_attacking_your_own_mine_should_leave_it_armed() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("layer", TeamRole::Minelayer), ("other", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let [layer, other] = [0, 1].map(|index| SenderDetails {
+    team_id: added[index].0,
+    team_key: added[index].1.clone(),
+  });
+
+  start_game(&mut games, game_id, layer.team_id, layer.team_key.clone()).await;
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: layer.clone(),
+      row_index: 4,
+      column_index: 4,
+    })
+    .await
+    .unwrap();
+
+  let own = attack(&mut games, game_id, &layer, (4, 4)).await;
+  assert_eq!(own.requests_left, 28);
+  assert_eq!(own.square.health, 59);
+  assert!(own.square.mine.is_none());
+
+  let triggered = attack(&mut games, game_id, &other, (4, 4)).await;
+  assert_eq!(triggered.requests_left, 0);
+  assert_eq!(triggered.square.health, 59);
+  assert_eq!(triggered.square.mine.map(|mine| mine.triggered_by), Some(Some(other.team_id)));
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_attacks_should_never_overspend_or_lose_damage() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Spy), ("blue", TeamRole::Spy), ("green", TeamRole::Spy)])
+    .await
+    .unwrap();
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  // every team sends more attacks than it has requests, all at once and all on the same square
+  let attacks = added.iter().flat_map(|(team_id, team_key)| {
+    (0..40).map(|_| {
+      let mut games = games.clone();
+      let request = AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id: *team_id,
+          team_key: team_key.clone(),
+        },
+        row_index: 2,
+        column_index: 2,
+      };
+      tokio::spawn(async move { (request.sender.team_id, games.try_attack_a_square(request).await) })
+    })
+  });
+  let attacks = attacks.collect::<Vec<_>>();
+
+  let mut conquests = 0;
+  for attack in attacks {
+    let (team_id, result) = attack.await.unwrap();
+    match result {
+      Ok(response) => {
+        assert!(response.requests_left >= 0);
+        conquests += usize::from(response.conquered);
+        if response.conquered {
+          assert_eq!(response.square.owner_id, Some(team_id));
+        }
+      }
+      Err(error) => assert_eq!(error, Error::NoMoreRequestsLeft),
+    }
+  }
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert!(game.teams.iter().all(|team| team.requests_left == 0));
+
+  // 90 attacks: 59 take the square down to 1, the 60th conquers it at 120, and the last 30 take it to 90
+  let square = game.grid.iter().find(|square| square.row == 2 && square.column == 2).unwrap();
+  assert_eq!(conquests, 1);
+  assert_eq!(square.health, 90);
+  assert!(square.owner_id.is_some());
+}
@@ -0,0 +1,135 @@
+use game_core::types::{
+  AttackRequest, AttackResponse, CommandKind, Error, Games, LockoutPolicy, QueryAuthFailuresRequest, Result, SenderDetails,
+  TeamRole,
+};
+use rstest::*;
+use std::time::Duration;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+fn policy(team_threshold: i32, source_threshold: i32) -> LockoutPolicy {
+  LockoutPolicy {
+    team_threshold,
+    source_threshold,
+    base_lockout: Duration::from_millis(200),
+    max_lockout: Duration::from_millis(500),
+  }
+}
+
+async fn attack(games: &mut Games, game_id: i32, team_id: i32, team_key: &str) -> Result<AttackResponse> {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id,
+        team_key: team_key.to_string(),
+      },
+      row_index: 0,
+      column_index: 0,
+    })
+    .await
+}
+
+fn retry_after(result: Result<AttackResponse>) -> Duration {
+  match result {
+    Err(Error::TooManyFailedAttempts { retry_after_ms }) => Duration::from_millis(retry_after_ms),
+    other => panic!("expected to be locked out, got {other:?}"),
+  }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_lockout_should_double_until_the_right_key_is_used() {
+  let TestSetup { games, game_id, added } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+  let mut games = games.with_lockout_policy(policy(2, 100));
+  let (red_id, red_key) = added[0].clone();
+  start_game(&mut games, game_id, red_id, red_key.clone()).await;
+
+  for _ in 0..2 {
+    let result = attack(&mut games, game_id, red_id, "wrong").await;
+    assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  }
+
+  // even the right key is turned away during a lockout
+  let wait = retry_after(attack(&mut games, game_id, red_id, &red_key).await);
+  assert!(wait <= Duration::from_millis(200));
+  tokio::time::sleep(wait).await;
+
+  let result = attack(&mut games, game_id, red_id, "wrong").await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  let wait = retry_after(attack(&mut games, game_id, red_id, "wrong").await);
+  assert!(wait > Duration::from_millis(200) && wait <= Duration::from_millis(400));
+  tokio::time::sleep(wait).await;
+
+  // a command that goes through starts the count again
+  attack(&mut games, game_id, red_id, &red_key).await.unwrap();
+  let result = attack(&mut games, game_id, red_id, "wrong").await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  attack(&mut games, game_id, red_id, &red_key).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_lockout_should_follow_sources_across_teams() {
+  let TestSetup { games, game_id, added } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let mut games = games.with_lockout_policy(policy(100, 2));
+  let (red_id, red_key) = added[0].clone();
+  let (blue_id, blue_key) = added[1].clone();
+  start_game(&mut games, game_id, red_id, red_key.clone()).await;
+
+  let mut guesser = games.clone().with_source("10.0.0.1");
+  for team_id in [red_id, blue_id] {
+    let result = attack(&mut guesser, game_id, team_id, "wrong").await;
+    assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  }
+  retry_after(attack(&mut guesser, game_id, blue_id, &blue_key).await);
+
+  // the teams themselves haven't failed often enough to be locked out anywhere else
+  let mut elsewhere = games.clone().with_source("10.0.0.2");
+  attack(&mut elsewhere, game_id, blue_id, &blue_key).await.unwrap();
+  attack(&mut games, game_id, red_id, &red_key).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_host_should_be_able_to_query_auth_failures() {
+  let TestSetup { games, game_id, added } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let mut games = games.with_source("10.0.0.1");
+  let (red_id, red_key) = added[0].clone();
+  let (blue_id, blue_key) = added[1].clone();
+  start_game(&mut games, game_id, red_id, red_key.clone()).await;
+
+  let result = attack(&mut games, game_id, blue_id, "wrong").await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+
+  let query = |team_id: i32, team_key: &str| QueryAuthFailuresRequest {
+    game_id,
+    sender: SenderDetails {
+      team_id,
+      team_key: team_key.to_string(),
+    },
+  };
+
+  let result = games.try_query_auth_failures(query(blue_id, &blue_key)).await;
+  assert_eq!(result.unwrap_err(), Error::OnlyHostCanQueryAuthFailures { team_id: blue_id });
+
+  // guessing the host's key to read the log is a failure like any other
+  let result = games.try_query_auth_failures(query(red_id, "wrong")).await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+
+  let failures = games.try_query_auth_failures(query(red_id, &red_key)).await.unwrap().failures;
+  let summary = failures
+    .iter()
+    .map(|failure| (failure.game_id, failure.team_id, failure.source.as_deref(), failure.command))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    summary,
+    [
+      (game_id, blue_id, Some("10.0.0.1"), CommandKind::Attack),
+      (game_id, red_id, Some("10.0.0.1"), CommandKind::QueryAuthFailures),
+    ]
+  );
+}
@@ -0,0 +1,217 @@
+use game_core::types::{
+  BatchItemResult, BatchMode, BatchOperation, BatchRequest, Error, ErrorCode, EventKind, Games, PlaceMineRequest,
+  QueryEventsRequest, QueryGameRequest, SenderDetails, TeamRole,
+};
+use rstest::*;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+fn sender((team_id, team_key): &(i32, String)) -> SenderDetails {
+  SenderDetails {
+    team_id: *team_id,
+    team_key: team_key.clone(),
+  }
+}
+
+/// A started game where the first team, a minelayer, has mined (1, 1) and the second team is about to attack it.
+async fn mined_game() -> (Games, i32, SenderDetails) {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: sender(&added[0]),
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+
+  (games, game_id, sender(&added[1]))
+}
+
+fn into_the_mine() -> Vec<BatchOperation> {
+  vec![
+    BatchOperation::Defend {
+      row_index: 0,
+      column_index: 0,
+    },
+    BatchOperation::Attack {
+      row_index: 1,
+      column_index: 1,
+    },
+    BatchOperation::Attack {
+      row_index: 2,
+      column_index: 2,
+    },
+  ]
+}
+
+async fn requests_left(games: &Games, game_id: i32, team_id: i32) -> i32 {
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  game.teams.iter().find(|team| team.id == team_id).unwrap().requests_left
+}
+
+async fn last_event(games: &Games, game_id: i32) -> (EventKind, Option<i32>, Option<String>) {
+  let request = QueryEventsRequest {
+    game_id,
+    after_sequence: 0,
+    limit: None,
+  };
+  let event = games.try_query_events(request).await.unwrap().events.pop().unwrap();
+  (event.kind, event.team_id, event.error_code)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_batch_should_charge_a_request_per_operation() {
+  let (mut games, game_id, blue) = mined_game().await;
+
+  let response = games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender: blue.clone(),
+      mode: BatchMode::AllOrNothing,
+      operations: vec![
+        BatchOperation::Attack {
+          row_index: 2,
+          column_index: 2,
+        },
+        BatchOperation::Defend {
+          row_index: 2,
+          column_index: 2,
+        },
+        BatchOperation::Attack {
+          row_index: 3,
+          column_index: 3,
+        },
+      ],
+    })
+    .await
+    .unwrap();
+
+  let summary = response
+    .results
+    .iter()
+    .map(|result| match result {
+      BatchItemResult::Attacked(attacked) => ("attacked", attacked.square.health, attacked.requests_left),
+      BatchItemResult::Defended(defended) => ("defended", defended.square.health, defended.requests_left),
+      BatchItemResult::Failed { code, .. } => panic!("unexpected failure {code:?}"),
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(summary, [("attacked", 59, 29), ("defended", 60, 28), ("attacked", 59, 27)]);
+  assert_eq!(requests_left(&games, game_id, blue.team_id).await, 27);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_all_or_nothing_batch_should_roll_back_when_an_operation_fails() {
+  let (mut games, game_id, blue) = mined_game().await;
+
+  let result = games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender: blue.clone(),
+      mode: BatchMode::AllOrNothing,
+      operations: into_the_mine(),
+    })
+    .await;
+
+  // the mine takes every request left, so the attack after it has nothing to spend
+  assert_eq!(
+    result.unwrap_err(),
+    Error::BatchOperationFailed {
+      index: 2,
+      error: Box::new(Error::NoMoreRequestsLeft),
+    }
+  );
+  assert_eq!(requests_left(&games, game_id, blue.team_id).await, 30);
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let mined = game.grid.iter().find(|square| (square.row, square.column) == (1, 1)).unwrap();
+  assert_eq!(mined.health, 60);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_best_effort_batch_should_keep_the_operations_that_went_through() {
+  let (mut games, game_id, blue) = mined_game().await;
+
+  let response = games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender: blue.clone(),
+      mode: BatchMode::BestEffort,
+      operations: into_the_mine(),
+    })
+    .await
+    .unwrap();
+
+  assert!(matches!(response.results[0], BatchItemResult::Defended(_)));
+  assert!(matches!(&response.results[1], BatchItemResult::Attacked(attacked) if attacked.requests_left == 0));
+  assert!(matches!(
+    response.results[2],
+    BatchItemResult::Failed {
+      code: ErrorCode::NoMoreRequestsLeft,
+      ..
+    }
+  ));
+  assert_eq!(requests_left(&games, game_id, blue.team_id).await, 0);
+}
+
+#[rstest]
+#[case::empty(vec![], ErrorCode::InvalidRequest)]
+#[case::outside_grid(vec![BatchOperation::Defend { row_index: 0, column_index: 5 }], ErrorCode::InvalidCoordinates)]
+#[case::too_many(vec![BatchOperation::Defend { row_index: 0, column_index: 0 }; 31], ErrorCode::InvalidRequest)]
+#[tokio::test]
+async fn test_batch_should_be_validated_up_front(#[case] operations: Vec<BatchOperation>, #[case] expected: ErrorCode) {
+  let (mut games, game_id, blue) = mined_game().await;
+
+  let result = games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender: blue.clone(),
+      mode: BatchMode::BestEffort,
+      operations,
+    })
+    .await;
+
+  assert_eq!(result.unwrap_err().code(), expected);
+  assert_eq!(requests_left(&games, game_id, blue.team_id).await, 30);
+  assert_eq!(
+    last_event(&games, game_id).await,
+    (EventKind::BatchRun, Some(blue.team_id), Some(format!("{expected:?}")))
+  );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_batch_turned_away_by_the_game_should_be_recorded() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Spy)]).await.unwrap();
+  let red = sender(&added[0]);
+
+  let result = games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender: red.clone(),
+      mode: BatchMode::AllOrNothing,
+      operations: into_the_mine(),
+    })
+    .await;
+
+  assert_eq!(result.unwrap_err().code(), ErrorCode::InvalidGameStatus);
+  assert_eq!(
+    last_event(&games, game_id).await,
+    (EventKind::BatchRun, Some(red.team_id), Some("InvalidGameStatus".to_string()))
+  );
+}
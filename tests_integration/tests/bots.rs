@@ -0,0 +1,140 @@
+use bots::{
+  BoardView, Bot, BotReport, GreedyConqueror, Minelayer, Move, RandomAttacker, Schedule, SquareView, Strategy, TerritoryDefender,
+  TurnOutcome,
+};
+use game_core::types::{GameStatus, QueryGameRequest, TeamRole, VerifyGameRequest};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rstest::*;
+use std::time::Duration;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+fn view(role: TeamRole, role_used: bool, squares: &[(i32, i32, Option<i32>, i32)]) -> BoardView {
+  BoardView {
+    team_id: 1,
+    role,
+    role_used,
+    requests_left: 30,
+    status: GameStatus::Started,
+    squares: squares
+      .iter()
+      .map(|&(row, column, owner_id, health)| SquareView {
+        row,
+        column,
+        owner_id,
+        health,
+      })
+      .collect(),
+  }
+}
+
+#[rstest]
+fn test_strategies_should_pick_expected_moves() {
+  let mut rng = ChaCha8Rng::seed_from_u64(0);
+  let board = view(
+    TeamRole::Spy,
+    false,
+    &[(0, 0, Some(1), 100), (0, 1, None, 60), (1, 0, Some(2), 12), (1, 1, None, 40)],
+  );
+
+  assert_eq!(
+    GreedyConqueror.next_move(&board, &mut rng),
+    Some(Move::Attack { row: 1, column: 0 })
+  );
+  assert_eq!(
+    TerritoryDefender::default().next_move(&board, &mut rng),
+    Some(Move::Defend { row: 0, column: 0 })
+  );
+
+  for _ in 0..20 {
+    let action = RandomAttacker.next_move(&board, &mut rng);
+    assert!(matches!(action, Some(Move::Attack { row, column }) if (row, column) != (0, 0)));
+  }
+
+  let healthy = view(TeamRole::Spy, false, &[(0, 0, Some(1), 120), (0, 1, None, 60)]);
+  assert_eq!(
+    TerritoryDefender::default().next_move(&healthy, &mut rng),
+    Some(Move::Attack { row: 0, column: 1 })
+  );
+}
+
+#[rstest]
+fn test_minelayer_should_place_a_single_mine_on_a_neutral_square() {
+  let mut rng = ChaCha8Rng::seed_from_u64(0);
+  let squares = [(0, 0, Some(1), 120), (0, 1, None, 60), (1, 0, Some(2), 12)];
+
+  assert_eq!(
+    Minelayer.next_move(&view(TeamRole::Minelayer, false, &squares), &mut rng),
+    Some(Move::PlaceMine { row: 0, column: 1 })
+  );
+  assert_eq!(
+    Minelayer.next_move(&view(TeamRole::Minelayer, true, &squares), &mut rng),
+    Some(Move::Attack { row: 1, column: 0 })
+  );
+  assert_eq!(
+    Minelayer.next_move(&view(TeamRole::Spy, false, &squares), &mut rng),
+    Some(Move::Attack { row: 1, column: 0 })
+  );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_bots_should_play_until_out_of_requests() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("host", TeamRole::Spy)]).await.unwrap();
+
+  let strategies: Vec<(TeamRole, Box<dyn Strategy>)> = vec![
+    (TeamRole::Spy, Box::new(RandomAttacker)),
+    (TeamRole::Cloaker, Box::new(GreedyConqueror)),
+    (TeamRole::Spy, Box::new(TerritoryDefender::default())),
+    (TeamRole::Minelayer, Box::new(Minelayer)),
+  ];
+
+  let mut bots = Vec::new();
+  for (i, (role, strategy)) in strategies.into_iter().enumerate() {
+    let bot = Bot::try_join(games.clone(), game_id, format!("bot-{i}"), role, strategy, i as u64)
+      .await
+      .unwrap();
+    bots.push(bot);
+  }
+
+  assert_eq!(bots[0].take_turn().await.unwrap(), TurnOutcome::Waiting);
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  let schedule = Schedule {
+    interval: Duration::from_millis(1),
+    max_turns: Some(100),
+  };
+
+  let handles = bots
+    .into_iter()
+    .map(|bot| tokio::spawn(bot.run(schedule)))
+    .collect::<Vec<_>>();
+
+  for handle in handles {
+    let BotReport {
+      turns,
+      moves_played,
+      moves_rejected,
+    } = handle.await.unwrap().unwrap();
+
+    // triggering the minelayer's mine costs a bot the rest of its requests
+    assert_eq!(moves_rejected, 0);
+    assert!(moves_played <= 30);
+    assert_eq!(turns, moves_played + 1);
+  }
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let mut bot_teams = game.teams.iter().filter(|team| team.id != added[0].0);
+  assert!(bot_teams.all(|team| team.requests_left == 0));
+  assert!(game
+    .teams
+    .iter()
+    .any(|team| team.role == TeamRole::Minelayer && team.role_used));
+
+  games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+}
@@ -0,0 +1,304 @@
+use game_core::commands::GRID_SIZE;
+use game_core::types::{
+  AttackRequest, AttackResponse, CreateAndJoinRequest, Error, EventKind, ExportGameRequest, GameMode, GameOptions, GameStatus,
+  Games, ImportGameRequest, JoinExistingRequest, QueryEventsRequest, QueryFlagsRequest, QueryGameRequest, ReplayGameRequest,
+  Result, SenderDetails, TeamRole, VerifyGameRequest,
+};
+use rstest::*;
+use std::time::Duration;
+use tests_integration::{create_test_pool, start_game};
+
+async fn attack(games: &mut Games, game_id: i32, sender: &SenderDetails, (row, column): (i32, i32)) -> Result<AttackResponse> {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: row,
+      column_index: column,
+    })
+    .await
+}
+
+/// Creates a game with red, green and blue in it, and starts it.
+async fn setup(games: &mut Games, options: GameOptions) -> (i32, [SenderDetails; 3]) {
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Spy,
+      options,
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+
+  let mut teams = vec![SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key.clone(),
+  }];
+  for name in ["green", "blue"] {
+    let joined = games
+      .try_join_an_existing_game(JoinExistingRequest {
+        game_id,
+        display_name: name.to_string(),
+        team_role: TeamRole::Spy,
+      })
+      .await
+      .unwrap();
+    teams.push(SenderDetails {
+      team_id: joined.team_id,
+      team_key: joined.team_key,
+    });
+  }
+  start_game(games, game_id, created.team_id, created.team_key).await;
+
+  (game_id, teams.try_into().unwrap())
+}
+
+/// Conquers a neutral square for blue, using up all of red's requests and all but one of green's.
+async fn conquer_for_blue(games: &mut Games, game_id: i32, [red, green, blue]: &[SenderDetails; 3], square: (i32, i32)) {
+  for _ in 0..30 {
+    attack(games, game_id, red, square).await.unwrap();
+  }
+  for _ in 0..29 {
+    attack(games, game_id, green, square).await.unwrap();
+  }
+  assert!(attack(games, game_id, blue, square).await.unwrap().conquered);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_capture_the_flag_games_should_place_their_flags_away_from_home_squares() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+
+  let result = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Spy,
+      options: GameOptions {
+        flag_count: Some(2),
+        ..Default::default()
+      },
+    })
+    .await;
+  assert!(matches!(result.unwrap_err(), Error::InvalidRequest { .. }));
+
+  let options = || GameOptions {
+    mode: GameMode::CaptureTheFlag,
+    flag_count: Some(4),
+    home_squares_seed: Some(7),
+    flags_seed: Some(11),
+    ..Default::default()
+  };
+  let (game_id, _) = setup(&mut games, options()).await;
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.options.flag_hold_ms, Some(game_core::commands::DEFAULT_FLAG_HOLD_MS));
+  assert_eq!(game.options.flags_seed, Some(11));
+
+  let flags = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap();
+  assert_eq!(flags.flags.len(), 4);
+  assert!(flags
+    .flags
+    .iter()
+    .all(|flag| flag.owner_id.is_none() && flag.held_since.is_none()));
+  assert!(flags
+    .flags
+    .iter()
+    .all(|flag| (0..GRID_SIZE).contains(&flag.row_index) && (0..GRID_SIZE).contains(&flag.column_index)));
+  assert_eq!(flags.standings.len(), 3);
+  assert!(flags
+    .standings
+    .iter()
+    .all(|standing| (standing.flags_held, standing.held_ms) == (0, 0)));
+
+  for team in &game.teams {
+    let home = (team.home_row.unwrap(), team.home_column.unwrap());
+    assert!(!flags.flags.iter().any(|flag| (flag.row_index, flag.column_index) == home));
+  }
+
+  // the same seed places the same flags, and replaying the game puts them back where they were
+  let positions = |flags: &[game_core::types::Flag]| {
+    let mut positions = flags
+      .iter()
+      .map(|flag| (flag.row_index, flag.column_index))
+      .collect::<Vec<_>>();
+    positions.sort_unstable();
+    positions
+  };
+  let (same_seed_id, _) = setup(&mut games, options()).await;
+  let same_seed = games
+    .try_query_flags(QueryFlagsRequest { game_id: same_seed_id })
+    .await
+    .unwrap();
+  assert_eq!(positions(&same_seed.flags), positions(&flags.flags));
+
+  let replayed = games
+    .try_replay_game(ReplayGameRequest {
+      game_id,
+      until_sequence: None,
+    })
+    .await
+    .unwrap()
+    .state;
+  let replayed_flags = replayed
+    .grid
+    .iter()
+    .filter(|square| square.flag)
+    .map(|square| (square.row, square.column))
+    .collect::<Vec<_>>();
+  assert_eq!(replayed_flags, positions(&flags.flags));
+  games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+
+  // as many flags somewhere else don't match
+  let mut moved = positions(&flags.flags);
+  let elsewhere = (0..GRID_SIZE)
+    .flat_map(|row| (0..GRID_SIZE).map(move |column| (row, column)))
+    .find(|square| !moved.contains(square))
+    .unwrap();
+  moved[0] = elsewhere;
+  assert!(matches!(replayed.verify(&game, &moved), Err(Error::ReplayMismatch { .. })));
+
+  let (conquest_id, _) = setup(&mut games, GameOptions::default()).await;
+  let result = games.try_query_flags(QueryFlagsRequest { game_id: conquest_id }).await;
+  assert!(matches!(result.unwrap_err(), Error::InvalidRequest { .. }));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_holding_every_flag_should_win_the_game() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let (game_id, teams) = setup(
+    &mut games,
+    GameOptions {
+      mode: GameMode::CaptureTheFlag,
+      flag_count: Some(1),
+      ..Default::default()
+    },
+  )
+  .await;
+
+  let flags = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap().flags;
+  let flag = (flags[0].row_index, flags[0].column_index);
+  conquer_for_blue(&mut games, game_id, &teams, flag).await;
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.status, GameStatus::Ended);
+  let [_, green, blue] = &teams;
+  assert_eq!(game.winner_id, Some(blue.team_id));
+
+  let result = attack(&mut games, game_id, green, flag).await;
+  assert!(matches!(
+    result.unwrap_err(),
+    Error::InvalidGameStatus {
+      current: GameStatus::Ended,
+      ..
+    }
+  ));
+
+  let events = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap()
+    .events;
+  let ended = events
+    .iter()
+    .filter(|event| event.kind == EventKind::GameEnded)
+    .map(|event| event.team_id)
+    .collect::<Vec<_>>();
+  assert_eq!(ended, [Some(blue.team_id)]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_holding_flags_long_enough_should_win_the_game() {
+  const HOLD_MS: u32 = 300;
+
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let (game_id, teams) = setup(
+    &mut games,
+    GameOptions {
+      mode: GameMode::CaptureTheFlag,
+      flag_count: Some(2),
+      flag_hold_ms: Some(HOLD_MS),
+      ..Default::default()
+    },
+  )
+  .await;
+
+  let flags = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap().flags;
+  let flag = (flags[0].row_index, flags[0].column_index);
+  conquer_for_blue(&mut games, game_id, &teams, flag).await;
+
+  let flags = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap();
+  let [_, green, blue] = &teams;
+  assert_eq!(flags.flags[0].owner_id, Some(blue.team_id));
+  assert!(flags.flags[0].held_since.is_some());
+  assert!(flags.standings.iter().all(|standing| standing.held_ms < i64::from(HOLD_MS)));
+
+  tokio::time::sleep(Duration::from_millis(u64::from(HOLD_MS) + 100)).await;
+
+  // nobody sends a command, the periodic sweep declares the winner
+  games.try_settle_started_games().await.unwrap();
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.status, GameStatus::Ended);
+  assert_eq!(game.winner_id, Some(blue.team_id));
+
+  let result = attack(&mut games, game_id, green, flag).await;
+  assert!(matches!(
+    result.unwrap_err(),
+    Error::InvalidGameStatus {
+      current: GameStatus::Ended,
+      ..
+    }
+  ));
+
+  let ended = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap();
+  assert!(ended.flags.iter().all(|flag| flag.held_since.is_none()));
+  let held_ms = |standings: &[game_core::types::FlagStanding], team_id: i32| {
+    standings.iter().find(|standing| standing.team_id == team_id).unwrap().held_ms
+  };
+  let blue_held_ms = held_ms(&ended.standings, blue.team_id);
+  assert!(blue_held_ms >= i64::from(HOLD_MS));
+  assert_eq!(held_ms(&ended.standings, green.team_id), 0);
+
+  // the clocks stay stopped, and the banked time survives an export
+  tokio::time::sleep(Duration::from_millis(50)).await;
+  let later = games.try_query_flags(QueryFlagsRequest { game_id }).await.unwrap();
+  assert_eq!(held_ms(&later.standings, blue.team_id), blue_held_ms);
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  assert_eq!(snapshot.game.options.mode, GameMode::CaptureTheFlag);
+  let imported = games.try_import_game(ImportGameRequest { snapshot }).await.unwrap();
+  let imported_blue = imported
+    .teams
+    .iter()
+    .find(|team| team.previous_team_id == blue.team_id)
+    .unwrap()
+    .team_id;
+  let imported_flags = games
+    .try_query_flags(QueryFlagsRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap();
+  assert_eq!(held_ms(&imported_flags.standings, imported_blue), blue_held_ms);
+  assert_eq!(imported_flags.flags.len(), 2);
+  let imported_game = games
+    .try_query_game(QueryGameRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap()
+    .game;
+  assert_eq!(imported_game.winner_id, Some(imported_blue));
+  games
+    .try_verify_game(VerifyGameRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,90 @@
+use client::Credentials;
+use code_and_conquer::credentials::CredentialsStore;
+use code_and_conquer::render::render_grid;
+use game_core::types::{GridSquare, Mine};
+use rstest::*;
+
+fn credentials(game_id: i32, team_id: i32) -> Credentials {
+  Credentials {
+    game_id,
+    team_id,
+    team_key: format!("key-{team_id}"),
+  }
+}
+
+#[rstest]
+fn test_credentials_should_be_saved_per_game() {
+  let path = std::env::temp_dir()
+    .join(format!("cnc-test-{}", std::process::id()))
+    .join("credentials.json");
+
+  let mut store = CredentialsStore::load(&path).unwrap();
+  assert_eq!(store, CredentialsStore::default());
+  assert_eq!(store.get(None), None);
+
+  store.insert(credentials(1, 10));
+  store.insert(credentials(2, 20));
+  store.save(&path).unwrap();
+
+  let loaded = CredentialsStore::load(&path).unwrap();
+  assert_eq!(loaded, store);
+  assert_eq!(loaded.get(None), Some(&credentials(2, 20)));
+  assert_eq!(loaded.get(Some(1)), Some(&credentials(1, 10)));
+  assert_eq!(loaded.get(Some(3)), None);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+  }
+
+  std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[rstest]
+fn test_grid_should_render_owners_health_and_mines() {
+  let square = |row: i32, column: i32, owner_id: Option<i32>, health: i32, mine: Option<Mine>| GridSquare {
+    id: row * 2 + column,
+    game_id: 1,
+    owner_id,
+    row,
+    column,
+    created_at: chrono::Utc::now(),
+    bonus: 0,
+    health,
+    mine,
+  };
+
+  let grid = [
+    square(0, 0, None, 60, None),
+    square(0, 1, Some(3), 120, None),
+    square(
+      1,
+      0,
+      None,
+      59,
+      Some(Mine {
+        placed_by: 3,
+        triggered_by: None,
+      }),
+    ),
+    square(
+      1,
+      1,
+      Some(12),
+      7,
+      Some(Mine {
+        placed_by: 3,
+        triggered_by: Some(12),
+      }),
+    ),
+  ];
+
+  let rendered = render_grid(&grid);
+  let lines = rendered.lines().collect::<Vec<_>>();
+
+  assert_eq!(lines.len(), 3);
+  assert_eq!(lines[1], "  0    -:60      3:120 ");
+  assert_eq!(lines[2], "  1    -:59 *   12:7  x");
+}
@@ -0,0 +1,163 @@
+use client::{Client, ClientError, Credentials, ReconnectPolicy};
+use game_core::protocol::{ResponseEnvelope, MAX_LINE_BYTES};
+use game_core::types::{ErrorCode, Games, TeamRole};
+use rstest::*;
+use server::TcpServer;
+use std::time::Duration;
+use tests_integration::create_test_pool;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn spawn_server() -> String {
+  let games = Games::try_new(create_test_pool().await).await.unwrap();
+  let server = TcpServer::bind("127.0.0.1:0", games).await.unwrap();
+  let address = server.local_addr().unwrap().to_string();
+  tokio::spawn(server.run());
+  address
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_client_should_play_a_game_over_tcp() {
+  let address = spawn_server().await;
+
+  let mut host = Client::connect(&address).await.unwrap();
+  let created = host.create_and_join("host", TeamRole::Minelayer).await.unwrap();
+
+  let mut guest = Client::connect(&address).await.unwrap();
+  let joined = guest.join(created.game_id, "guest", TeamRole::Spy).await.unwrap();
+  assert_eq!(guest.credentials().unwrap().team_id, joined.team_id);
+
+  let error = guest.attack(0, 0).await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::InvalidGameStatus));
+
+  let error = guest.start().await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::OnlyHostCanStartGame));
+
+  host.start().await.unwrap();
+
+  let attacked = guest.attack(0, 0).await.unwrap();
+  assert_eq!((attacked.square.health, attacked.requests_left), (59, 29));
+
+  let defended = host.defend(0, 0).await.unwrap();
+  assert_eq!(defended.square.health, 60);
+
+  let mined = host.place_mine(4, 4).await.unwrap();
+  assert_eq!(mined.square.mine.unwrap().placed_by, created.team_id);
+
+  let error = guest.place_mine(3, 3).await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::OnlyMinelayersCanPlaceMines));
+
+  let grid = guest.query_grid().await.unwrap().grid;
+  assert_eq!(grid.len(), 25);
+
+  let square = guest.query_square(0, 0).await.unwrap().square;
+  assert_eq!(square.health, 60);
+
+  // teams can see each other, but not each other's keys
+  let game = guest.query_game().await.unwrap().game;
+  assert_eq!(game.teams.len(), 2);
+  assert!(game.teams.iter().all(|team| team.key.is_empty()));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_client_should_require_credentials() {
+  let address = spawn_server().await;
+  let mut client = Client::connect(&address).await.unwrap();
+
+  assert!(matches!(client.attack(0, 0).await, Err(ClientError::MissingCredentials)));
+
+  let created = client.create_and_join("host", TeamRole::Spy).await.unwrap();
+
+  let mut impostor = Client::connect(&address).await.unwrap().with_credentials(Credentials {
+    game_id: created.game_id,
+    team_id: created.team_id,
+    team_key: "not the key".to_string(),
+  });
+  let error = impostor.attack(0, 0).await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::InvalidCredentials));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_should_reject_malformed_requests() {
+  let address = spawn_server().await;
+  let (reader, mut writer) = TcpStream::connect(&address).await.unwrap().into_split();
+  let mut lines = BufReader::new(reader).lines();
+
+  for (request, id) in [
+    ("not json", None),
+    (r#"{"id": 7, "command": {"type": "fly"}}"#, Some(7)),
+    (r#"{"command": {"type": "query_grid", "game_id": 1}}"#, None),
+  ] {
+    writer.write_all(format!("{request}\n").as_bytes()).await.unwrap();
+    let response: ResponseEnvelope = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+    assert_eq!(response.id, id);
+    assert_eq!(response.result.unwrap_err().code, ErrorCode::InvalidRequest);
+  }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_should_close_connections_sending_overlong_lines() {
+  let address = spawn_server().await;
+  let (reader, mut writer) = TcpStream::connect(&address).await.unwrap().into_split();
+  let mut lines = BufReader::new(reader).lines();
+
+  // no newline ever comes, the server has to give up on its own
+  let request = vec![b'x'; MAX_LINE_BYTES + 1];
+  writer.write_all(&request).await.unwrap();
+  let response: ResponseEnvelope = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+  assert_eq!(response.id, None);
+  assert_eq!(response.result.unwrap_err().code, ErrorCode::InvalidRequest);
+  assert_eq!(lines.next_line().await.unwrap(), None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_client_should_reconnect_and_match_responses_by_id() {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let address = listener.local_addr().unwrap().to_string();
+
+  tokio::spawn(async move {
+    // the first connection drops without answering
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    lines.next_line().await.unwrap();
+    drop(lines);
+
+    // the second one answers with a stale response first
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let request: serde_json::Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    let id = request["id"].as_u64().unwrap();
+
+    for response_id in [id - 1, id] {
+      let response = serde_json::json!({
+        "id": response_id,
+        "result": { "Ok": { "type": "query_grid", "grid": [] } }
+      });
+      writer.write_all(format!("{response}\n").as_bytes()).await.unwrap();
+    }
+  });
+
+  let mut client = Client::connect(&address)
+    .await
+    .unwrap()
+    .with_reconnect_policy(ReconnectPolicy {
+      attempts: 5,
+      delay: Duration::from_millis(10),
+    })
+    .with_credentials(Credentials {
+      game_id: 1,
+      team_id: 1,
+      team_key: "key".to_string(),
+    });
+
+  assert!(matches!(client.query_grid().await, Err(ClientError::Disconnected)));
+  assert!(client.query_grid().await.unwrap().grid.is_empty());
+}
@@ -0,0 +1,113 @@
+use game_core::types::{
+  AttackRequest, AttackResponse, CreateAndJoinRequest, DefendRequest, Error, GameOptions, Games, JoinExistingRequest,
+  QueryGameRequest, Result, SenderDetails, TeamRole,
+};
+use rstest::*;
+use std::time::Duration;
+use tests_integration::{create_test_pool, start_game};
+
+async fn attack(games: &mut Games, game_id: i32, sender: &SenderDetails) -> Result<AttackResponse> {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_commands_sent_within_the_interval_should_be_turned_away() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Minelayer,
+      options: GameOptions {
+        command_interval_ms: Some(300),
+        ..Default::default()
+      },
+    })
+    .await
+    .unwrap();
+  let joined = games
+    .try_join_an_existing_game(JoinExistingRequest {
+      game_id: created.game_id,
+      display_name: "blue".to_string(),
+      team_role: TeamRole::Spy,
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+  start_game(&mut games, game_id, created.team_id, created.team_key.clone()).await;
+
+  let red = SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key,
+  };
+  let blue = SenderDetails {
+    team_id: joined.team_id,
+    team_key: joined.team_key,
+  };
+
+  assert_eq!(attack(&mut games, game_id, &red).await.unwrap().requests_left, 29);
+
+  let result = games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: red.clone(),
+      row_index: 1,
+      column_index: 1,
+    })
+    .await;
+  let retry_after = match result {
+    Err(Error::CommandTooSoon { retry_after_ms }) => Duration::from_millis(retry_after_ms),
+    other => panic!("expected the defend to be too soon, got {other:?}"),
+  };
+  assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(300));
+
+  // other teams keep their own pace
+  attack(&mut games, game_id, &blue).await.unwrap();
+
+  tokio::time::sleep(retry_after).await;
+  assert_eq!(attack(&mut games, game_id, &red).await.unwrap().requests_left, 28);
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.options.command_interval_ms, Some(300));
+  let team = game.teams.iter().find(|team| team.id == red.team_id).unwrap();
+  assert_eq!(team.requests_left, 28);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_attack_should_record_the_time_of_the_last_command() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Minelayer,
+      options: GameOptions::default(),
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+  start_game(&mut games, game_id, created.team_id, created.team_key.clone()).await;
+
+  let red = SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key,
+  };
+
+  // without an interval commands can be sent back to back
+  attack(&mut games, game_id, &red).await.unwrap();
+  attack(&mut games, game_id, &red).await.unwrap();
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let team = game.teams.iter().find(|team| team.id == red.team_id).unwrap();
+  let elapsed = (chrono::Utc::now() - team.time_of_last_command.unwrap())
+    .to_std()
+    .unwrap_or_default();
+  assert!(elapsed < Duration::from_secs(1), "elapsed {elapsed:?}");
+}
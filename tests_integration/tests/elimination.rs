@@ -0,0 +1,227 @@
+use game_core::replay::ReplayState;
+use game_core::rules::Rules;
+use game_core::types::{
+  AttackRequest, AttackResponse, CreateAndJoinRequest, Error, EventKind, ExportGameRequest, GameOptions, GameStatus, Games,
+  ImportGameRequest, JoinExistingRequest, QueryEventsRequest, QueryGameRequest, Result, SenderDetails, TeamRole,
+};
+use rstest::*;
+use spectator::{SpectatorState, Update};
+use std::time::Duration;
+use tests_integration::{create_test_pool, start_game};
+
+const GRACE_MS: u32 = 500;
+
+async fn attack(games: &mut Games, game_id: i32, sender: &SenderDetails, (row, column): (i32, i32)) -> Result<AttackResponse> {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: row,
+      column_index: column,
+    })
+    .await
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_teams_owning_nothing_after_their_grace_period_should_be_eliminated() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Spy,
+      options: GameOptions {
+        elimination_grace_ms: Some(GRACE_MS),
+        ..Default::default()
+      },
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+
+  let mut teams = vec![SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key.clone(),
+  }];
+  for name in ["green", "blue"] {
+    let joined = games
+      .try_join_an_existing_game(JoinExistingRequest {
+        game_id,
+        display_name: name.to_string(),
+        team_role: TeamRole::Spy,
+      })
+      .await
+      .unwrap();
+    teams.push(SenderDetails {
+      team_id: joined.team_id,
+      team_key: joined.team_key,
+    });
+  }
+  start_game(&mut games, game_id, created.team_id, created.team_key).await;
+
+  let [red, green, blue]: [SenderDetails; 3] = teams.try_into().unwrap();
+  for _ in 0..30 {
+    attack(&mut games, game_id, &red, (0, 0)).await.unwrap();
+  }
+  for _ in 0..29 {
+    attack(&mut games, game_id, &green, (0, 0)).await.unwrap();
+  }
+  assert!(attack(&mut games, game_id, &blue, (0, 0)).await.unwrap().conquered);
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let first_conquests = game
+    .teams
+    .iter()
+    .map(|team| team.first_conquest_at.is_some())
+    .collect::<Vec<_>>();
+  assert_eq!(first_conquests, [false, false, true]);
+
+  // the game carries on as it is, so it's set up again where blue has since lost its last square long
+  // ago, red has just conquered one that's about to fall and green has requests to spare
+  let mut snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  let now = chrono::Utc::now();
+  let blue_lost_at = now - chrono::Duration::minutes(30);
+  for team in &mut snapshot.teams {
+    team.requests_left = 30;
+    if team.id == red.team_id {
+      team.first_conquest_at = Some(now);
+    } else if team.id == blue.team_id {
+      team.first_conquest_at = Some(now - chrono::Duration::hours(1));
+      team.last_square_lost_at = Some(blue_lost_at);
+    }
+  }
+  for square in &mut snapshot.grid {
+    let red_keeps = (square.row_index, square.column_index) == (4, 4);
+    square.owner_id = Some(red.team_id).filter(|_| red_keeps);
+    square.health = if red_keeps { 1 } else { 60 };
+  }
+
+  let imported = games.try_import_game(ImportGameRequest { snapshot }).await.unwrap();
+  let game_id = imported.game_id;
+  let sender = |previous_team_id: i32| {
+    let team = imported
+      .teams
+      .iter()
+      .find(|team| team.previous_team_id == previous_team_id)
+      .unwrap();
+    SenderDetails {
+      team_id: team.team_id,
+      team_key: team.team_key.clone(),
+    }
+  };
+  let (red, green, blue) = (sender(red.team_id), sender(green.team_id), sender(blue.team_id));
+
+  attack(&mut games, game_id, &green, (2, 2)).await.unwrap();
+  let result = attack(&mut games, game_id, &blue, (2, 2)).await;
+  assert_eq!(result.unwrap_err(), Error::TeamEliminated { team_id: blue.team_id });
+  // red is still within its grace period, and losing its square doesn't change that
+  attack(&mut games, game_id, &red, (2, 2)).await.unwrap();
+  let conquering = chrono::Utc::now();
+  assert!(attack(&mut games, game_id, &green, (4, 4)).await.unwrap().conquered);
+  let conquered = chrono::Utc::now();
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  let red_lost_at = snapshot
+    .teams
+    .iter()
+    .find(|team| team.id == red.team_id)
+    .unwrap()
+    .last_square_lost_at
+    .unwrap();
+  assert!(red_lost_at >= conquering - chrono::Duration::milliseconds(1) && red_lost_at <= conquered);
+
+  // nobody sends a command for a while, which doesn't push red's elimination back
+  tokio::time::sleep(Duration::from_millis(u64::from(GRACE_MS) + 100)).await;
+
+  // commands with a bad key are turned away without settling the game
+  let impostor = SenderDetails {
+    team_id: green.team_id,
+    team_key: "wrong".to_string(),
+  };
+  let result = attack(&mut games, game_id, &impostor, (2, 2)).await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.status, GameStatus::Started);
+
+  // the periodic sweep ends the game without anyone sending a command
+  games.try_settle_started_games().await.unwrap();
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.status, GameStatus::Ended);
+  assert_eq!(game.winner_id, Some(green.team_id));
+
+  let result = attack(&mut games, game_id, &green, (2, 2)).await;
+  assert!(matches!(
+    result.unwrap_err(),
+    Error::InvalidGameStatus {
+      current: GameStatus::Ended,
+      ..
+    }
+  ));
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.status, GameStatus::Ended);
+  let eliminated = |team_id: i32| game.teams.iter().find(|team| team.id == team_id).unwrap().eliminated_at;
+  assert!(eliminated(red.team_id).unwrap() > eliminated(blue.team_id).unwrap());
+  assert_eq!(eliminated(green.team_id), None);
+  // teams are eliminated as of when they lost their last square, not when the game next got settled
+  let blue_eliminated_at = eliminated(blue.team_id).unwrap();
+  assert!((blue_eliminated_at - blue_lost_at).num_milliseconds().abs() < 1);
+  let red_grace_over = now + chrono::Duration::milliseconds(i64::from(GRACE_MS));
+  assert!((eliminated(red.team_id).unwrap() - red_grace_over).num_milliseconds().abs() < 1);
+
+  let events = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap()
+    .events;
+  let outcomes = events
+    .iter()
+    .filter(|event| matches!(event.kind, EventKind::TeamEliminated | EventKind::GameEnded))
+    .map(|event| (event.kind, event.team_id))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    outcomes,
+    [
+      (EventKind::TeamEliminated, Some(blue.team_id)),
+      (EventKind::TeamEliminated, Some(red.team_id)),
+      (EventKind::GameEnded, Some(green.team_id)),
+    ]
+  );
+
+  let mut state = SpectatorState::default();
+  state.apply(Update { game, events: None });
+  let leaderboard = state
+    .leaderboard()
+    .into_iter()
+    .map(|entry| (entry.display_name, entry.eliminated_at.is_some()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    leaderboard,
+    [
+      ("green".to_string(), false),
+      ("red".to_string(), true),
+      ("blue".to_string(), true)
+    ]
+  );
+}
+
+#[rstest]
+fn test_replayed_eliminations_should_block_the_team() {
+  let mut state = ReplayState::new(1, Rules::default());
+  state.join(1).unwrap();
+  state.join(2).unwrap();
+  assert!(state.eliminate(2).is_err());
+  state.start().unwrap();
+
+  state.eliminate(2).unwrap();
+  assert_eq!(state.attack(2, 0, 0).unwrap_err(), Error::TeamEliminated { team_id: 2 });
+  assert_eq!(state.eliminate(2).unwrap_err(), Error::TeamEliminated { team_id: 2 });
+  state.attack(1, 0, 0).unwrap();
+
+  state.end(None).unwrap();
+  assert_eq!(state.status, GameStatus::Ended);
+  assert!(state.attack(1, 0, 0).is_err());
+}
@@ -0,0 +1,261 @@
+use game_core::types::{
+  AttackRequest, DefendRequest, EventBus, GameEvent, Games, PlaceMineRequest, SenderDetails, StartRequest, TeamRole,
+};
+use rstest::*;
+use std::time::Duration;
+use tests_integration::{setup_with_players, TestSetup};
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::sync::broadcast::Receiver;
+
+fn sender((team_id, team_key): &(i32, String)) -> SenderDetails {
+  SenderDetails {
+    team_id: *team_id,
+    team_key: team_key.clone(),
+  }
+}
+
+async fn attack(games: &mut Games, game_id: i32, team: &(i32, String), row_index: i32, column_index: i32) {
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender(team),
+      row_index,
+      column_index,
+    })
+    .await
+    .unwrap();
+}
+
+async fn next_event(receiver: &mut Receiver<GameEvent>) -> GameEvent {
+  tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+    .await
+    .expect("no event within 5 seconds")
+    .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_event_bus_should_publish_committed_commands() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let (red, blue) = (&added[0], &added[1]);
+  let mut events = games.event_bus().subscribe(game_id);
+
+  games
+    .try_start(StartRequest {
+      game_id,
+      sender: sender(red),
+    })
+    .await
+    .unwrap();
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: sender(red),
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+  attack(&mut games, game_id, blue, 0, 0).await;
+  games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: sender(red),
+      row_index: 0,
+      column_index: 0,
+    })
+    .await
+    .unwrap();
+  attack(&mut games, game_id, blue, 1, 1).await;
+
+  let expected = [
+    GameEvent::GameStarted { game_id },
+    GameEvent::MinePlaced {
+      game_id,
+      team_id: red.0,
+      row: 1,
+      column: 1,
+    },
+    GameEvent::SquareAttacked {
+      game_id,
+      team_id: blue.0,
+      row: 0,
+      column: 0,
+      health: 59,
+      owner_id: None,
+    },
+    GameEvent::SquareDefended {
+      game_id,
+      team_id: red.0,
+      row: 0,
+      column: 0,
+      health: 60,
+      owner_id: None,
+    },
+    GameEvent::MineTriggered {
+      game_id,
+      team_id: blue.0,
+      row: 1,
+      column: 1,
+      placed_by: red.0,
+      requests_left: 0,
+    },
+  ];
+  for event in expected {
+    assert_eq!(events.try_recv().unwrap(), event);
+  }
+
+  // rejected commands aren't published
+  let rejected = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender(blue),
+      row_index: 0,
+      column_index: 0,
+    })
+    .await;
+  assert!(rejected.is_err());
+  assert_eq!(events.try_recv(), Err(TryRecvError::Empty));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_event_bus_should_publish_conquests() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let (red, blue) = (&added[0], &added[1]);
+
+  games
+    .try_start(StartRequest {
+      game_id,
+      sender: sender(red),
+    })
+    .await
+    .unwrap();
+  for _ in 0..30 {
+    attack(&mut games, game_id, red, 2, 2).await;
+  }
+  for _ in 0..29 {
+    attack(&mut games, game_id, blue, 2, 2).await;
+  }
+
+  let mut events = games.event_bus().subscribe(game_id);
+  attack(&mut games, game_id, blue, 2, 2).await;
+
+  assert_eq!(
+    events.try_recv().unwrap(),
+    GameEvent::SquareConquered {
+      game_id,
+      team_id: blue.0,
+      row: 2,
+      column: 2,
+      health: 120,
+    }
+  );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_event_bus_should_keep_games_apart() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+
+  // clones of `Games` share the bus
+  let mut other_game = games.event_bus().subscribe(game_id + 1);
+  let mut this_game = games.clone().event_bus().subscribe(game_id);
+
+  games
+    .try_start(StartRequest {
+      game_id,
+      sender: sender(&added[0]),
+    })
+    .await
+    .unwrap();
+
+  assert_eq!(this_game.try_recv().unwrap(), GameEvent::GameStarted { game_id });
+  assert_eq!(other_game.try_recv(), Err(TryRecvError::Empty));
+}
+
+#[rstest]
+fn test_event_bus_should_drop_channels_nobody_listens_to() {
+  let bus = EventBus::new(4);
+  assert_eq!(bus.publish(GameEvent::GameStarted { game_id: 1 }), 0);
+
+  let first = bus.subscribe(1);
+  let mut second = bus.subscribe(1);
+  assert_eq!(bus.publish(GameEvent::GameStarted { game_id: 1 }), 2);
+
+  drop(first);
+  assert_eq!(bus.publish(GameEvent::GameEnded { game_id: 1 }), 1);
+  assert_eq!(second.try_recv().unwrap(), GameEvent::GameStarted { game_id: 1 });
+  assert_eq!(second.try_recv().unwrap(), GameEvent::GameEnded { game_id: 1 });
+
+  drop(second);
+  assert_eq!(bus.publish(GameEvent::GameEnded { game_id: 1 }), 0);
+
+  // subscribing again starts a fresh channel
+  let mut third = bus.subscribe(1);
+  assert_eq!(bus.publish(GameEvent::GameEnded { game_id: 1 }), 1);
+  assert_eq!(third.try_recv().unwrap(), GameEvent::GameEnded { game_id: 1 });
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_event_relay_should_share_events_between_instances() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  // another instance against the same database, with its own bus
+  let other = games.clone().with_event_bus(EventBus::default());
+  tokio::spawn(other.try_connect_relay().await.unwrap().run());
+  tokio::spawn(games.try_connect_relay().await.unwrap().run());
+
+  let mut local = games.event_bus().subscribe(game_id);
+  let mut remote = other.event_bus().subscribe(game_id);
+
+  games
+    .try_start(StartRequest {
+      game_id,
+      sender: sender(&added[0]),
+    })
+    .await
+    .unwrap();
+  attack(&mut games, game_id, &added[1], 3, 3).await;
+
+  for receiver in [&mut local, &mut remote] {
+    assert_eq!(next_event(receiver).await, GameEvent::GameStarted { game_id });
+    assert!(matches!(
+      next_event(receiver).await,
+      GameEvent::SquareAttacked {
+        row: 3,
+        column: 3,
+        health: 59,
+        ..
+      }
+    ));
+  }
+
+  // the local bus doesn't get its own events a second time through the relay
+  tokio::time::sleep(Duration::from_millis(200)).await;
+  assert_eq!(local.try_recv(), Err(TryRecvError::Empty));
+  assert_eq!(remote.try_recv(), Err(TryRecvError::Empty));
+}
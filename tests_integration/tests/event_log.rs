@@ -0,0 +1,193 @@
+use game_core::types::{
+  AttackRequest, DefendRequest, Error, EventKind, PlaceMineRequest, QueryEventsRequest, QueryEventsResponse, SenderDetails,
+  TeamRole,
+};
+use rstest::*;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+#[rstest]
+#[tokio::test]
+async fn test_should_record_successful_and_rejected_commands() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("host", TeamRole::Spy), ("guest", TeamRole::Minelayer)])
+    .await
+    .unwrap();
+
+  let (host_id, host_key) = added[0].clone();
+  let (guest_id, guest_key) = added[1].clone();
+
+  // rejected, game has not started yet
+  let error = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: host_id,
+        team_key: host_key.clone(),
+      },
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap_err();
+  assert!(matches!(error, Error::InvalidGameStatus { .. }));
+
+  start_game(&mut games, game_id, host_id, host_key.clone()).await;
+
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: host_id,
+        team_key: host_key.clone(),
+      },
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+
+  // rejected, wrong key
+  games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: guest_id,
+        team_key: host_key.clone(),
+      },
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap_err();
+
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: guest_id,
+        team_key: guest_key.clone(),
+      },
+      row_index: 2,
+      column_index: 3,
+    })
+    .await
+    .unwrap();
+
+  let QueryEventsResponse { events, last_sequence } = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap();
+
+  let summary = events
+    .iter()
+    .map(|event| (event.kind, event.team_id, event.error_code.as_deref()))
+    .collect::<Vec<_>>();
+
+  assert_eq!(
+    summary,
+    vec![
+      (EventKind::GameCreated, Some(host_id), None),
+      (EventKind::TeamJoined, Some(guest_id), None),
+      (EventKind::SquareAttacked, Some(host_id), Some("InvalidGameStatus")),
+      (EventKind::GameStarted, Some(host_id), None),
+      (EventKind::SquareAttacked, Some(host_id), None),
+      (EventKind::SquareDefended, Some(guest_id), Some("InvalidCredentials")),
+      (EventKind::MinePlaced, Some(guest_id), None),
+    ]
+  );
+
+  assert!(events.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+  assert_eq!(last_sequence, events.last().unwrap().sequence);
+
+  let attacked = &events[4];
+  assert_eq!((attacked.row, attacked.column), (Some(1), Some(1)));
+  assert_eq!(attacked.health, Some(59));
+  assert_eq!(attacked.owner_id, None);
+  assert_eq!(attacked.requests_left, Some(29));
+
+  let rejected = &events[2];
+  assert_eq!((rejected.row, rejected.column), (Some(1), Some(1)));
+  assert_eq!(rejected.health, None);
+  assert_eq!(rejected.requests_left, None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_should_paginate_events_by_sequence(#[values(1, 3, 7)] limit: i64) {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("a", TeamRole::Spy), ("b", TeamRole::Cloaker)])
+    .await
+    .unwrap();
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  for column_index in 0..5 {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id: added[1].0,
+          team_key: added[1].1.clone(),
+        },
+        row_index: 0,
+        column_index,
+      })
+      .await
+      .unwrap();
+  }
+
+  let mut after_sequence = 0;
+  let mut collected = Vec::new();
+
+  loop {
+    let QueryEventsResponse { events, last_sequence } = games
+      .try_query_events(QueryEventsRequest {
+        game_id,
+        after_sequence,
+        limit: Some(limit),
+      })
+      .await
+      .unwrap();
+
+    assert!(events.len() as i64 <= limit);
+
+    if events.is_empty() {
+      assert_eq!(last_sequence, after_sequence);
+      break;
+    }
+
+    after_sequence = last_sequence;
+    collected.extend(events);
+  }
+
+  // created, joined, started and five attacks
+  assert_eq!(collected.len(), 8);
+  assert!(collected.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_should_not_query_events_of_unknown_game() {
+  let TestSetup { games, game_id, .. } = setup_with_players(&[("a", TeamRole::Spy)]).await.unwrap();
+
+  let error = games
+    .try_query_events(QueryEventsRequest {
+      game_id: game_id + 1,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap_err();
+
+  assert_eq!(error, Error::InvalidGameId { game_id: game_id + 1 });
+}
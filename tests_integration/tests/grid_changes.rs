@@ -0,0 +1,144 @@
+use game_core::types::{
+  AttackRequest, BatchMode, BatchOperation, BatchRequest, Error, Games, QueryGameRequest, QueryGridChangesRequest,
+  QueryGridChangesResponse, SenderDetails, TeamRole,
+};
+use rstest::*;
+use std::collections::HashMap;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+async fn changes(games: &Games, game_id: i32, since_version: i64) -> QueryGridChangesResponse {
+  games
+    .try_query_grid_changes(QueryGridChangesRequest { game_id, since_version })
+    .await
+    .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_grid_changes_should_only_return_squares_changed_since_a_version() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Spy)]).await.unwrap();
+  let (team_id, team_key) = added[0].clone();
+  start_game(&mut games, game_id, team_id, team_key.clone()).await;
+  let sender = SenderDetails { team_id, team_key };
+
+  let everything = changes(&games, game_id, 0).await;
+  assert_eq!(everything.squares.len(), 25);
+  assert_eq!(changes(&games, game_id, everything.version).await.squares.len(), 0);
+
+  games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender.clone(),
+      row_index: 2,
+      column_index: 3,
+    })
+    .await
+    .unwrap();
+
+  let attacked = changes(&games, game_id, everything.version).await;
+  assert_eq!(attacked.version, everything.version + 1);
+  let summary = attacked
+    .squares
+    .iter()
+    .map(|square| (square.row, square.column, square.health))
+    .collect::<Vec<_>>();
+  assert_eq!(summary, [(2, 3, 59)]);
+
+  // a square changed several times in one go is returned once, as it ended up
+  games
+    .try_run_batch(BatchRequest {
+      game_id,
+      sender,
+      mode: BatchMode::AllOrNothing,
+      operations: vec![
+        BatchOperation::Attack {
+          row_index: 0,
+          column_index: 0,
+        },
+        BatchOperation::Attack {
+          row_index: 0,
+          column_index: 0,
+        },
+      ],
+    })
+    .await
+    .unwrap();
+
+  let batched = changes(&games, game_id, attacked.version).await;
+  let summary = batched
+    .squares
+    .iter()
+    .map(|square| (square.row, square.column, square.health))
+    .collect::<Vec<_>>();
+  assert_eq!(summary, [(0, 0, 58)]);
+  assert!(batched.version > attacked.version);
+
+  let result = games
+    .try_query_grid_changes(QueryGridChangesRequest {
+      game_id: -1,
+      since_version: 0,
+    })
+    .await;
+  assert_eq!(result.unwrap_err(), Error::InvalidGameId { game_id: -1 });
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_polling_grid_changes_should_never_miss_a_concurrent_change() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Spy), ("blue", TeamRole::Spy), ("green", TeamRole::Spy)])
+    .await
+    .unwrap();
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  let attacks = added
+    .iter()
+    .flat_map(|(team_id, team_key)| {
+      (0..20).map(|i| {
+        let mut games = games.clone();
+        let request = AttackRequest {
+          game_id,
+          sender: SenderDetails {
+            team_id: *team_id,
+            team_key: team_key.clone(),
+          },
+          row_index: i % 5,
+          column_index: (i / 5) % 5,
+        };
+        tokio::spawn(async move { games.try_attack_a_square(request).await.unwrap() })
+      })
+    })
+    .collect::<Vec<_>>();
+
+  // keeps its own copy of the grid up to date from the changes alone, while the attacks go on
+  let mut seen = HashMap::new();
+  let mut version = 0;
+  let mut polling = true;
+  while polling {
+    polling = attacks.iter().any(|attack| !attack.is_finished());
+    let response = changes(&games, game_id, version).await;
+    assert!(response.version >= version);
+    version = response.version;
+    for square in response.squares {
+      seen.insert((square.row, square.column), square.health);
+    }
+  }
+  for attack in attacks {
+    attack.await.unwrap();
+  }
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let actual = game
+    .grid
+    .iter()
+    .map(|square| ((square.row, square.column), square.health))
+    .collect::<HashMap<_, _>>();
+  assert_eq!(seen, actual);
+}
@@ -0,0 +1,143 @@
+use game_core::commands::{CONQUERED_SQUARE_HEALTH, GRID_SIZE, HOME_SQUARE_HEALTH};
+use game_core::home_squares;
+use game_core::types::{
+  AttackRange, AttackRequest, CreateAndJoinRequest, DefendRequest, Error, GameOptions, Games, JoinExistingRequest,
+  QueryGameRequest, SenderDetails, TeamRole, VerifyGameRequest,
+};
+use rstest::*;
+use tests_integration::{create_test_pool, start_game};
+
+#[rstest]
+fn test_home_squares_should_be_spread_out_and_follow_the_seed() {
+  let team_ids = [3, 5, 8, 13];
+
+  for seed in 0..50 {
+    let homes = home_squares::allocate(GRID_SIZE, &team_ids, seed, &[]).unwrap();
+    assert_eq!(homes, home_squares::allocate(GRID_SIZE, &team_ids, seed, &[]).unwrap());
+
+    let mut assigned = homes.iter().map(|home| home.team_id).collect::<Vec<_>>();
+    assigned.sort_unstable();
+    assert_eq!(assigned, team_ids);
+
+    for (index, home) in homes.iter().enumerate() {
+      assert!((0..GRID_SIZE).contains(&home.row) && (0..GRID_SIZE).contains(&home.column));
+      for other in &homes[index + 1..] {
+        let distance = (home.row - other.row).abs() + (home.column - other.column).abs();
+        assert!(distance >= 3, "seed {seed} put {home:?} and {other:?} too close");
+      }
+    }
+  }
+
+  assert_ne!(
+    home_squares::allocate(GRID_SIZE, &team_ids, 1, &[]).unwrap(),
+    home_squares::allocate(GRID_SIZE, &team_ids, 2, &[]).unwrap()
+  );
+
+  let crowded = (1..=26).collect::<Vec<_>>();
+  assert!(matches!(
+    home_squares::allocate(GRID_SIZE, &crowded, 1, &[]),
+    Err(Error::InvalidRequest { .. })
+  ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_starting_should_give_every_team_its_home_square() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Spy,
+      options: GameOptions {
+        attack_range: AttackRange::Adjacent,
+        home_squares_seed: Some(42),
+        ..Default::default()
+      },
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+
+  let mut team_ids = vec![created.team_id];
+  for name in ["green", "blue"] {
+    let joined = games
+      .try_join_an_existing_game(JoinExistingRequest {
+        game_id,
+        display_name: name.to_string(),
+        team_role: TeamRole::Spy,
+      })
+      .await
+      .unwrap();
+    team_ids.push(joined.team_id);
+  }
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert!(game.teams.iter().all(|team| team.home_row.is_none()));
+  assert!(game.grid.iter().all(|square| square.owner_id.is_none()));
+
+  start_game(&mut games, game_id, created.team_id, created.team_key.clone()).await;
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.options.home_squares_seed, Some(42));
+  for home in home_squares::allocate(GRID_SIZE, &team_ids, 42, &[]).unwrap() {
+    let team = game.teams.iter().find(|team| team.id == home.team_id).unwrap();
+    assert_eq!((team.home_row, team.home_column), (Some(home.row), Some(home.column)));
+    // placing the home squares doesn't cost any requests
+    assert_eq!(team.requests_left, 30);
+
+    let square = game
+      .grid
+      .iter()
+      .find(|square| (square.row, square.column) == (home.row, home.column))
+      .unwrap();
+    assert_eq!((square.owner_id, square.health), (Some(home.team_id), HOME_SQUARE_HEALTH));
+    // home squares start out sturdier than conquered ones
+    assert!(square.health > CONQUERED_SQUARE_HEALTH);
+  }
+  assert_eq!(game.grid.iter().filter(|square| square.owner_id.is_some()).count(), 3);
+
+  // with adjacent attacks, a team starts out only reaching the squares around its home
+  let red = game.teams.iter().find(|team| team.id == created.team_id).unwrap();
+  let (home_row, home_column) = (red.home_row.unwrap(), red.home_column.unwrap());
+  let far = game
+    .grid
+    .iter()
+    .find(|square| (square.row - home_row).abs() + (square.column - home_column).abs() > 1 && square.owner_id.is_none())
+    .unwrap();
+  let result = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: created.team_id,
+        team_key: created.team_key.clone(),
+      },
+      row_index: far.row,
+      column_index: far.column,
+    })
+    .await;
+  assert_eq!(
+    result.unwrap_err(),
+    Error::SquareNotReachable {
+      row: far.row,
+      column: far.column
+    }
+  );
+
+  // and defending its home doesn't cap it at the conquered health
+  let defended = games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: created.team_id,
+        team_key: created.team_key,
+      },
+      row_index: home_row,
+      column_index: home_column,
+    })
+    .await
+    .unwrap();
+  assert_eq!(defended.square.health, HOME_SQUARE_HEALTH);
+
+  let verified = games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+  assert_eq!(verified.state.teams[&created.team_id].home, Some((home_row, home_column)));
+}
@@ -0,0 +1,229 @@
+use game_core::types::{
+  AttackRequest, Command, CommandResponse, CreateAndJoinRequest, DefendRequest, Error, ErrorCode, Games, JoinExistingRequest,
+  QueryGameRequest, SenderDetails, TeamRole, IDEMPOTENCY_CLAIM_TIMEOUT,
+};
+use rstest::*;
+use server::protocol::handle_line;
+use tests_integration::{create_test_pool, setup_with_players, start_game, TestSetup};
+
+fn attack(game_id: i32, sender: &SenderDetails, row_index: i32) -> Command {
+  Command::Attack(AttackRequest {
+    game_id,
+    sender: sender.clone(),
+    row_index,
+    column_index: 0,
+  })
+}
+
+fn requests_left(response: CommandResponse) -> i32 {
+  match response {
+    CommandResponse::Attack(response) => response.requests_left,
+    other => panic!("expected an attack response, got {other:?}"),
+  }
+}
+
+async fn team_requests_left(games: &Games, game_id: i32, team_id: i32) -> i32 {
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  game.teams.iter().find(|team| team.id == team_id).unwrap().requests_left
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_retried_commands_should_only_be_applied_once() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let (red_id, red_key) = added[0].clone();
+  start_game(&mut games, game_id, red_id, red_key.clone()).await;
+  let red = SenderDetails {
+    team_id: red_id,
+    team_key: red_key,
+  };
+
+  for _ in 0..3 {
+    let response = games
+      .try_execute_idempotent(attack(game_id, &red, 0), Some("first"))
+      .await
+      .unwrap();
+    assert_eq!(requests_left(response), 29);
+  }
+  assert_eq!(team_requests_left(&games, game_id, red_id).await, 29);
+
+  let response = games
+    .try_execute_idempotent(attack(game_id, &red, 1), Some("second"))
+    .await
+    .unwrap();
+  assert_eq!(requests_left(response), 28);
+
+  // a key belongs to a single command
+  let defend = Command::Defend(DefendRequest {
+    game_id,
+    sender: red.clone(),
+    row_index: 0,
+    column_index: 0,
+  });
+  let result = games.try_execute_idempotent(defend, Some("first")).await;
+  assert_eq!(result.unwrap_err().code(), ErrorCode::InvalidRequest);
+
+  // and to a single team, other teams using it get their own response
+  let (blue_id, blue_key) = added[1].clone();
+  let blue = SenderDetails {
+    team_id: blue_id,
+    team_key: blue_key,
+  };
+  let response = games
+    .try_execute_idempotent(attack(game_id, &blue, 2), Some("first"))
+    .await
+    .unwrap();
+  assert_eq!(requests_left(response), 29);
+
+  // the stored response is only handed to the team's rightful owner
+  let impostor = SenderDetails {
+    team_id: red_id,
+    team_key: "wrong".to_string(),
+  };
+  let result = games
+    .try_execute_idempotent(attack(game_id, &impostor, 0), Some("first"))
+    .await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+  assert_eq!(team_requests_left(&games, game_id, red_id).await, 28);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_failed_commands_should_run_again_when_retried() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+  let (red_id, red_key) = added[0].clone();
+  let red = SenderDetails {
+    team_id: red_id,
+    team_key: red_key.clone(),
+  };
+
+  let result = games.try_execute_idempotent(attack(game_id, &red, 0), Some("early")).await;
+  assert_eq!(result.unwrap_err().code(), ErrorCode::InvalidGameStatus);
+
+  start_game(&mut games, game_id, red_id, red_key).await;
+  let response = games
+    .try_execute_idempotent(attack(game_id, &red, 0), Some("early"))
+    .await
+    .unwrap();
+  assert_eq!(requests_left(response), 29);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_claims_left_by_commands_that_never_finished_should_expire() {
+  let pool = create_test_pool().await;
+  let mut games = Games::try_new(pool.clone()).await.unwrap();
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "red".to_string(),
+      team_role: TeamRole::Minelayer,
+      options: Default::default(),
+    })
+    .await
+    .unwrap();
+  let game_id = created.game_id;
+  start_game(&mut games, game_id, created.team_id, created.team_key.clone()).await;
+  let red = SenderDetails {
+    team_id: created.team_id,
+    team_key: created.team_key,
+  };
+
+  // the process claimed the key, then went away before the command committed
+  sqlx::query("INSERT INTO idempotency_key (team_id, key, command) VALUES ($1, 'lost', 'Attack');")
+    .bind(red.team_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+  let result = games.try_execute_idempotent(attack(game_id, &red, 0), Some("lost")).await;
+  assert_eq!(result.unwrap_err(), Error::IdempotentCommandInProgress);
+
+  sqlx::query("UPDATE idempotency_key SET created_at = NOW() - $1 * INTERVAL '1 second' WHERE key = 'lost';")
+    .bind(IDEMPOTENCY_CLAIM_TIMEOUT.as_secs() as i64 + 1)
+    .execute(&pool)
+    .await
+    .unwrap();
+  for _ in 0..2 {
+    let response = games
+      .try_execute_idempotent(attack(game_id, &red, 0), Some("lost"))
+      .await
+      .unwrap();
+    assert_eq!(requests_left(response), 29);
+  }
+  assert_eq!(team_requests_left(&games, game_id, red.team_id).await, 29);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_creating_a_game_should_turn_idempotency_keys_away() {
+  let mut games = Games::try_new(create_test_pool().await).await.unwrap();
+
+  let create = Command::CreateAndJoin(CreateAndJoinRequest {
+    display_name: "red".to_string(),
+    team_role: TeamRole::Spy,
+    options: Default::default(),
+  });
+  let result = games.try_execute_idempotent(create, Some("first")).await;
+  assert!(matches!(result.unwrap_err(), Error::InvalidRequest { .. }));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_joining_a_game_should_turn_idempotency_keys_away() {
+  let TestSetup { mut games, game_id, .. } = setup_with_players(&[("red", TeamRole::Spy)]).await.unwrap();
+
+  let join = || {
+    Command::JoinExisting(JoinExistingRequest {
+      game_id,
+      display_name: "blue".to_string(),
+      team_role: TeamRole::Spy,
+    })
+  };
+  for _ in 0..2 {
+    let result = games.try_execute_idempotent(join(), Some("first")).await;
+    assert!(matches!(result.unwrap_err(), Error::InvalidRequest { .. }));
+  }
+
+  let game = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  assert_eq!(game.teams.len(), 1);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_should_read_idempotency_keys_from_the_envelope() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+  let (red_id, red_key) = added[0].clone();
+  start_game(&mut games, game_id, red_id, red_key.clone()).await;
+
+  let line = serde_json::json!({
+    "id": 1,
+    "idempotency_key": "7b0c2a52-6f4e-4f43-9a65-0d3f1b6f8e21",
+    "command": {
+      "type": "attack",
+      "game_id": game_id,
+      "sender": { "team_id": red_id, "team_key": red_key },
+      "row_index": 0,
+      "column_index": 0,
+    },
+  })
+  .to_string();
+
+  for _ in 0..2 {
+    let response = handle_line(&mut games, &line).await;
+    assert_eq!(requests_left(response.result.unwrap()), 29);
+  }
+  assert_eq!(team_requests_left(&games, game_id, red_id).await, 29);
+}
@@ -0,0 +1,93 @@
+use game_core::types::{
+  CreateAndJoinRequest, Error, Games, JoinExistingRequest, KeyFormat, KeyGenerator, KeyOptions, OsKeyGenerator,
+  SeededKeyGenerator, TeamRole,
+};
+use rstest::*;
+use tests_integration::create_test_pool;
+
+#[rstest]
+#[case::hex(KeyFormat::Hex, "0123456789abcdef")]
+#[case::alphanumeric(KeyFormat::Alphanumeric, "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz")]
+fn test_keys_should_have_requested_length_and_format(
+  #[case] format: KeyFormat,
+  #[case] alphabet: &str,
+  #[values(1, 7, 16, 30)] length: usize,
+) {
+  let options = KeyOptions { length, format };
+  let generator = OsKeyGenerator::try_new(options).unwrap();
+
+  for _ in 0..20 {
+    let key = generator.generate_key().unwrap();
+    assert_eq!(key.len(), length);
+    assert!(key.chars().all(|c| alphabet.contains(c)), "unexpected character in {key}");
+  }
+}
+
+#[rstest]
+#[case::empty(0)]
+#[case::too_long(31)]
+fn test_should_reject_invalid_key_length(#[case] length: usize) {
+  let options = KeyOptions {
+    length,
+    format: KeyFormat::Hex,
+  };
+
+  assert_eq!(
+    OsKeyGenerator::try_new(options).unwrap_err(),
+    Error::InvalidKeyLength { length, max: 30 }
+  );
+  assert_eq!(
+    SeededKeyGenerator::try_new(0, options).unwrap_err(),
+    Error::InvalidKeyLength { length, max: 30 }
+  );
+}
+
+#[rstest]
+fn test_seeded_generator_should_be_deterministic() {
+  let options = KeyOptions::default();
+  let first = SeededKeyGenerator::try_new(42, options).unwrap();
+  let second = SeededKeyGenerator::try_new(42, options).unwrap();
+  let other = SeededKeyGenerator::try_new(43, options).unwrap();
+
+  let first_keys = (0..5).map(|_| first.generate_key().unwrap()).collect::<Vec<_>>();
+  let second_keys = (0..5).map(|_| second.generate_key().unwrap()).collect::<Vec<_>>();
+  let other_keys = (0..5).map(|_| other.generate_key().unwrap()).collect::<Vec<_>>();
+
+  assert_eq!(first_keys, second_keys);
+  assert_ne!(first_keys, other_keys);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_teams_should_receive_keys_from_injected_generator() {
+  let options = KeyOptions {
+    length: 12,
+    format: KeyFormat::Alphanumeric,
+  };
+  let expected = SeededKeyGenerator::try_new(7, options).unwrap();
+  let mut games = Games::try_new(create_test_pool().await)
+    .await
+    .unwrap()
+    .with_key_generator(SeededKeyGenerator::try_new(7, options).unwrap());
+
+  let created = games
+    .try_create_and_join_a_game(CreateAndJoinRequest {
+      display_name: "host".to_string(),
+      team_role: TeamRole::Spy,
+      options: Default::default(),
+    })
+    .await
+    .unwrap();
+
+  let joined = games
+    .try_join_an_existing_game(JoinExistingRequest {
+      game_id: created.game_id,
+      display_name: "guest".to_string(),
+      team_role: TeamRole::Cloaker,
+    })
+    .await
+    .unwrap();
+
+  assert_eq!(created.team_key, expected.generate_key().unwrap());
+  assert_eq!(joined.team_key, expected.generate_key().unwrap());
+}
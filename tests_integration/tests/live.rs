@@ -0,0 +1,257 @@
+use game_core::types::{AttackRequest, DefendRequest, GameStatus, Games, PlaceMineRequest, SenderDetails, TeamRole};
+use rstest::*;
+use server::live::{LiveFeed, LiveUpdate};
+use server::HttpServer;
+use std::time::Duration;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn sender((team_id, team_key): &(i32, String)) -> SenderDetails {
+  SenderDetails {
+    team_id: *team_id,
+    team_key: team_key.clone(),
+  }
+}
+
+async fn attack(games: &mut Games, game_id: i32, team: &(i32, String), (row, column): (i32, i32), times: usize) {
+  for _ in 0..times {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: sender(team),
+        row_index: row,
+        column_index: column,
+      })
+      .await
+      .unwrap();
+  }
+}
+
+async fn spawn_http_server(games: Games) -> String {
+  // long enough that the tests only pass if the event bus wakes the streams up
+  let server = HttpServer::bind("127.0.0.1:0", games)
+    .await
+    .unwrap()
+    .with_poll_interval(Duration::from_secs(60));
+  let address = server.local_addr().unwrap().to_string();
+  tokio::spawn(server.run());
+  address
+}
+
+async fn open_stream(address: &str, game_id: i32, last_event_id: Option<i64>) -> TcpStream {
+  let mut stream = TcpStream::connect(address).await.unwrap();
+  let resume = last_event_id.map_or_else(String::new, |id| format!("Last-Event-ID: {id}\r\n"));
+  let request = format!("GET /games/{game_id}/events HTTP/1.1\r\nHost: {address}\r\nAccept: text/event-stream\r\n{resume}\r\n");
+  stream.write_all(request.as_bytes()).await.unwrap();
+  stream
+}
+
+/// Reads until `needle` shows up, returning everything read so far.
+async fn read_until(stream: &mut TcpStream, received: &mut String, needle: &str) {
+  let mut buffer = [0; 4096];
+
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while !received.contains(needle) {
+      let read = stream.read(&mut buffer).await.unwrap();
+      assert!(read > 0, "stream closed before {needle:?} showed up in {received:?}");
+      received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+    }
+  })
+  .await
+  .unwrap_or_else(|_| panic!("{needle:?} never showed up in {received:?}"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_live_feed_should_describe_changes_from_the_event_log() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[
+    ("red", TeamRole::Minelayer),
+    ("blue", TeamRole::Spy),
+    ("green", TeamRole::Cloaker),
+  ])
+  .await
+  .unwrap();
+  let (red, blue, green) = (&added[0], &added[1], &added[2]);
+
+  let mut feed = LiveFeed::follow(games.clone(), game_id, None).await.unwrap();
+  assert!(feed.poll().await.unwrap().is_empty());
+
+  start_game(&mut games, game_id, red.0, red.1.clone()).await;
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: sender(red),
+      row_index: 1,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+
+  let updates = feed.poll().await.unwrap();
+  // the mine stays a secret
+  assert_eq!(
+    updates.iter().map(|(_, update)| update.clone()).collect::<Vec<_>>(),
+    [LiveUpdate::StatusChanged {
+      status: GameStatus::Started
+    }]
+  );
+
+  attack(&mut games, game_id, green, (0, 0), 30).await;
+  attack(&mut games, game_id, red, (0, 0), 28).await;
+  games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: sender(red),
+      row_index: 0,
+      column_index: 0,
+    })
+    .await
+    .unwrap();
+  attack(&mut games, game_id, blue, (0, 0), 3).await;
+  attack(&mut games, game_id, blue, (1, 1), 1).await;
+
+  // rejected commands don't change anything
+  let error = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: sender(blue),
+      row_index: 2,
+      column_index: 2,
+    })
+    .await;
+  assert!(error.is_err());
+
+  let updates = feed
+    .poll()
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|(_, update)| update)
+    .collect::<Vec<_>>();
+  assert_eq!(updates.len(), 58 + 1 + 2 + 1 + 1);
+  assert_eq!(
+    updates[0],
+    LiveUpdate::SquareChanged {
+      row: 0,
+      column: 0,
+      health: 59,
+      owner_id: None
+    }
+  );
+  assert_eq!(
+    updates[58],
+    LiveUpdate::SquareChanged {
+      row: 0,
+      column: 0,
+      health: 3,
+      owner_id: None
+    }
+  );
+  assert_eq!(
+    updates[61],
+    LiveUpdate::SquareConquered {
+      row: 0,
+      column: 0,
+      health: 120,
+      owner_id: blue.0,
+      previous_owner_id: None
+    }
+  );
+  assert_eq!(
+    updates[62],
+    LiveUpdate::MineDetonated {
+      row: 1,
+      column: 1,
+      placed_by: red.0,
+      triggered_by: blue.0
+    }
+  );
+
+  assert!(feed.poll().await.unwrap().is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_live_feed_should_resume_after_a_sequence() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+  let started_at = LiveFeed::follow(games.clone(), game_id, None).await.unwrap().last_sequence();
+  attack(&mut games, game_id, &added[1], (2, 3), 2).await;
+
+  let mut feed = LiveFeed::follow(games.clone(), game_id, Some(started_at)).await.unwrap();
+  let updates = feed.poll().await.unwrap();
+
+  assert_eq!(updates.len(), 2);
+  assert!(updates[0].0 > started_at);
+  assert_eq!(
+    updates[1].1,
+    LiveUpdate::SquareChanged {
+      row: 2,
+      column: 3,
+      health: 58,
+      owner_id: None
+    }
+  );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_http_server_should_stream_game_events() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let address = spawn_http_server(games.clone()).await;
+
+  let mut stream = open_stream(&address, game_id, None).await;
+  let mut received = String::new();
+  read_until(&mut stream, &mut received, "event: snapshot").await;
+  assert!(received.to_lowercase().contains("content-type: text/event-stream"));
+  read_until(&mut stream, &mut received, "\"teams\"").await;
+  // keys never leave the server
+  assert!(!received.contains(&added[0].1));
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+  read_until(&mut stream, &mut received, "event: status_changed").await;
+  read_until(&mut stream, &mut received, r#""status":"Started""#).await;
+
+  attack(&mut games, game_id, &added[1], (4, 4), 1).await;
+  read_until(&mut stream, &mut received, "event: square_changed").await;
+  read_until(&mut stream, &mut received, r#""health":59"#).await;
+
+  // picking up from the attack doesn't send the snapshot or the attack again
+  let attack_sequence = LiveFeed::follow(games.clone(), game_id, None).await.unwrap().last_sequence();
+  let mut resumed = open_stream(&address, game_id, Some(attack_sequence)).await;
+  let mut resumed_received = String::new();
+  attack(&mut games, game_id, &added[1], (4, 4), 1).await;
+  read_until(&mut resumed, &mut resumed_received, r#""health":58"#).await;
+  assert!(!resumed_received.contains("snapshot"));
+  assert!(!resumed_received.contains(r#""health":59"#));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_http_server_should_return_not_found_for_unknown_games() {
+  let TestSetup { games, .. } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+  let address = spawn_http_server(games).await;
+
+  let mut stream = open_stream(&address, i32::MAX, None).await;
+  let mut received = String::new();
+  read_until(&mut stream, &mut received, "InvalidGameId").await;
+  assert!(received.starts_with("HTTP/1.1 404"));
+}
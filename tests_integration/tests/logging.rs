@@ -0,0 +1,90 @@
+use game_core::types::{AttackRequest, Error, ErrorCode, SenderDetails, TeamRole};
+use rstest::*;
+use server::logging::{self, LogFormat};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+#[derive(Debug, Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.lock().unwrap().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Captured {
+  fn lines(&self) -> Vec<serde_json::Value> {
+    let bytes = self.0.lock().unwrap();
+    String::from_utf8_lossy(&bytes)
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect()
+  }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_json_logs_should_carry_the_command_span() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Minelayer)]).await.unwrap();
+  let (team_id, team_key) = added[0].clone();
+  start_game(&mut games, game_id, team_id, team_key).await;
+
+  let captured = Captured::default();
+  let writer = captured.clone();
+  let subscriber = logging::subscriber(LogFormat::Json, "game_core=debug", move || writer.clone());
+  let _guard = tracing::subscriber::set_default(subscriber);
+
+  let result = games
+    .try_attack_a_square(AttackRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id,
+        team_key: "wrong".to_string(),
+      },
+      row_index: 0,
+      column_index: 0,
+    })
+    .await;
+  assert_eq!(result.unwrap_err(), Error::InvalidCredentials);
+
+  let lines = captured.lines();
+  let rejected = lines
+    .iter()
+    .find(|line| line["fields"]["error"].as_str() == Some(Error::InvalidCredentials.to_string().as_str()))
+    .unwrap_or_else(|| panic!("no log line for the rejected attack in {lines:?}"));
+
+  assert_eq!(rejected["level"], "DEBUG");
+  assert_eq!(rejected["span"]["command"], "Attack");
+  assert_eq!(rejected["span"]["game_id"], game_id);
+  assert_eq!(rejected["span"]["team_id"], team_id);
+}
+
+#[rstest]
+fn test_database_errors_should_keep_their_source() {
+  let error = Error::from(sqlx::Error::RowNotFound);
+
+  assert_eq!(error.code(), ErrorCode::DatabaseError);
+  assert!(error.is_internal());
+  let source = std::error::Error::source(&error).unwrap();
+  assert_eq!(source.source().unwrap().to_string(), sqlx::Error::RowNotFound.to_string());
+
+  assert!(!Error::InvalidCredentials.is_internal());
+}
+
+#[rstest]
+fn test_log_format_should_parse() {
+  assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+  assert_eq!("Text".parse::<LogFormat>(), Ok(LogFormat::Text));
+  assert!("yaml".parse::<LogFormat>().is_err());
+}
@@ -0,0 +1,68 @@
+use client::Client;
+use game_core::types::{ErrorCode, Games, TeamRole};
+use rstest::*;
+use server::metrics::Metrics;
+use server::rate_limit::RateLimitConfig;
+use server::{HttpServer, TcpServer};
+use tests_integration::create_test_pool;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn fetch_metrics(address: &str) -> String {
+  let mut stream = TcpStream::connect(address).await.unwrap();
+  let request = "GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+  stream.write_all(request.as_bytes()).await.unwrap();
+
+  let mut response = String::new();
+  stream.read_to_string(&mut response).await.unwrap();
+  assert!(response.starts_with("HTTP/1.1 200"), "unexpected response {response:?}");
+  response
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_http_server_should_expose_metrics_recorded_by_the_tcp_server() {
+  let games = Games::try_new(create_test_pool().await).await.unwrap();
+  let metrics = Metrics::new();
+
+  let server = TcpServer::bind("127.0.0.1:0", games.clone())
+    .await
+    .unwrap()
+    .with_rate_limits(RateLimitConfig::unlimited())
+    .with_metrics(metrics.clone());
+  let address = server.local_addr().unwrap().to_string();
+  tokio::spawn(server.run());
+
+  let http_server = HttpServer::bind("127.0.0.1:0", games).await.unwrap().with_metrics(metrics);
+  let http_address = http_server.local_addr().unwrap().to_string();
+  tokio::spawn(http_server.run());
+
+  let mut host = Client::connect(&address).await.unwrap();
+  host.create_and_join("host", TeamRole::Minelayer).await.unwrap();
+  let mut guest = Client::connect(&address).await.unwrap();
+  guest
+    .join(host.credentials().unwrap().game_id, "guest", TeamRole::Spy)
+    .await
+    .unwrap();
+  host.query_grid().await.unwrap();
+  host.query_grid().await.unwrap();
+
+  // attacking before the game has started fails
+  let error = guest.attack(0, 0).await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::InvalidGameStatus));
+
+  let metrics = fetch_metrics(&http_address).await;
+  for expected in [
+    r#"cnc_commands_total{command="CreateAndJoin"} 1"#,
+    r#"cnc_commands_total{command="JoinExisting"} 1"#,
+    r#"cnc_commands_total{command="QueryGrid"} 2"#,
+    r#"cnc_commands_total{command="Attack"} 1"#,
+    r#"cnc_errors_total{code="InvalidGameStatus"} 1"#,
+    r#"cnc_command_duration_seconds_count{command="QueryGrid"} 2"#,
+    "cnc_active_games 1",
+    "cnc_active_teams 2",
+    "cnc_db_max_connections 5",
+  ] {
+    assert!(metrics.contains(expected), "{expected:?} missing from {metrics}");
+  }
+}
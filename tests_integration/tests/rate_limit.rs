@@ -0,0 +1,158 @@
+use client::{Client, Credentials};
+use game_core::types::{
+  AttackRequest, Command, CommandKind, Error, ErrorCode, Games, QueryGameRequest, QueryGridRequest, SenderDetails, TeamRole,
+};
+use rstest::*;
+use server::rate_limit::{RateLimit, RateLimitConfig, RateLimiter, Scope};
+use server::TcpServer;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use tests_integration::create_test_pool;
+
+const HOME: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+const AWAY: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+fn query_game() -> Command {
+  Command::QueryGame(QueryGameRequest { game_id: 1 })
+}
+
+fn attack(team_id: i32) -> Command {
+  Command::Attack(AttackRequest {
+    game_id: 1,
+    sender: SenderDetails {
+      team_id,
+      team_key: "wrong".to_string(),
+    },
+    row_index: 0,
+    column_index: 0,
+  })
+}
+
+fn retry_after(result: Result<(), Error>) -> u64 {
+  match result {
+    Err(Error::RateLimited { retry_after_ms }) => retry_after_ms,
+    other => panic!("expected to be rate limited, got {other:?}"),
+  }
+}
+
+#[rstest]
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_should_refill_buckets_over_time() {
+  let limiter = RateLimiter::new(RateLimitConfig::unlimited().with_limit(Scope::Connection, RateLimit::new(2, 4.0)));
+
+  limiter.check(1, HOME, &query_game()).unwrap();
+  limiter.check(1, HOME, &query_game()).unwrap();
+  assert_eq!(retry_after(limiter.check(1, HOME, &query_game())), 250);
+
+  // other connections have their own bucket
+  limiter.check(2, HOME, &query_game()).unwrap();
+
+  tokio::time::advance(Duration::from_millis(100)).await;
+  assert_eq!(retry_after(limiter.check(1, HOME, &query_game())), 150);
+
+  tokio::time::advance(Duration::from_millis(150)).await;
+  limiter.check(1, HOME, &query_game()).unwrap();
+  assert!(limiter.check(1, HOME, &query_game()).is_err());
+}
+
+#[rstest]
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_should_keep_command_limits_apart() {
+  let config = RateLimitConfig::unlimited()
+    .with_limit(Scope::Ip, RateLimit::new(1, 1.0))
+    .with_command_limit(Scope::Ip, CommandKind::QueryGame, RateLimit::new(3, 1.0));
+  let limiter = RateLimiter::new(config);
+
+  for _ in 0..3 {
+    limiter.check(1, HOME, &query_game()).unwrap();
+  }
+  assert!(limiter.check(2, HOME, &query_game()).is_err());
+
+  // every other command shares the general bucket, which queries haven't touched
+  limiter
+    .check(3, HOME, &Command::QueryGrid(QueryGridRequest { game_id: 1 }))
+    .unwrap();
+  assert!(limiter.check(3, HOME, &attack(7)).is_err());
+
+  limiter.check(1, AWAY, &attack(7)).unwrap();
+}
+
+#[rstest]
+#[tokio::test(start_paused = true)]
+async fn test_rate_limiter_should_follow_teams_across_connections() {
+  let config = RateLimitConfig::unlimited()
+    .with_limit(Scope::Team, RateLimit::new(2, 0.5))
+    .with_limit(Scope::Connection, RateLimit::new(2, 0.5));
+  let limiter = RateLimiter::new(config);
+
+  limiter.check(1, HOME, &attack(7)).unwrap();
+  limiter.check_team(7, CommandKind::Attack).unwrap();
+  limiter.check(2, AWAY, &attack(7)).unwrap();
+  limiter.check_team(7, CommandKind::Attack).unwrap();
+  assert_eq!(retry_after(limiter.check_team(7, CommandKind::Attack)), 2000);
+
+  // until their key checks out, commands only count against the connection, and a rejected command
+  // takes no tokens
+  limiter.check(3, HOME, &attack(7)).unwrap();
+  limiter.check(3, HOME, &attack(8)).unwrap();
+  assert!(limiter.check(3, HOME, &attack(8)).is_err());
+
+  limiter.forget_connection(3);
+  limiter.check(3, HOME, &attack(8)).unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_should_rate_limit_clients() {
+  let games = Games::try_new(create_test_pool().await).await.unwrap();
+  let config = RateLimitConfig::unlimited().with_limit(Scope::Connection, RateLimit::new(2, 4.0));
+  let server = TcpServer::bind("127.0.0.1:0", games).await.unwrap().with_rate_limits(config);
+  let address = server.local_addr().unwrap().to_string();
+  tokio::spawn(server.run());
+
+  let mut client = Client::connect(&address).await.unwrap();
+  client.create_and_join("host", TeamRole::Minelayer).await.unwrap();
+  client.query_game().await.unwrap();
+
+  let error = client.query_game().await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::RateLimited));
+  let retry_after = error.retry_after().unwrap();
+  assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(250));
+
+  // a new connection starts with a full bucket
+  let mut other = Client::connect(&address).await.unwrap();
+  other = other.with_credentials(client.credentials().unwrap().clone());
+  other.query_game().await.unwrap();
+
+  tokio::time::sleep(retry_after).await;
+  client.query_game().await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_should_only_count_correctly_keyed_commands_against_a_team() {
+  let games = Games::try_new(create_test_pool().await).await.unwrap();
+  let config = RateLimitConfig::unlimited().with_limit(Scope::Team, RateLimit::new(2, 0.01));
+  let server = TcpServer::bind("127.0.0.1:0", games).await.unwrap().with_rate_limits(config);
+  let address = server.local_addr().unwrap().to_string();
+  tokio::spawn(server.run());
+
+  let mut client = Client::connect(&address).await.unwrap();
+  client.create_and_join("host", TeamRole::Minelayer).await.unwrap();
+  let credentials = client.credentials().unwrap().clone();
+
+  // fewer wrong keys than it takes to be locked out, but more than the team's burst
+  let mut guesser = Client::connect(&address).await.unwrap().with_credentials(Credentials {
+    team_key: "wrong".to_string(),
+    ..credentials
+  });
+  for _ in 0..3 {
+    let error = guesser.query_auth_failures().await.unwrap_err();
+    assert_eq!(error.code(), Some(ErrorCode::InvalidCredentials));
+  }
+
+  client.query_auth_failures().await.unwrap();
+  client.query_auth_failures().await.unwrap();
+  let error = client.query_auth_failures().await.unwrap_err();
+  assert_eq!(error.code(), Some(ErrorCode::RateLimited));
+}
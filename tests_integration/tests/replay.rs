@@ -0,0 +1,198 @@
+use game_core::replay::Replay;
+use game_core::types::{
+  AttackRequest, DefendRequest, Error, GameStatus, PlaceMineRequest, QueryEventsRequest, ReplayGameRequest, SenderDetails,
+  TeamRole, VerifyGameRequest,
+};
+use rstest::*;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+#[rstest]
+#[tokio::test]
+async fn test_replay_should_match_stored_game() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("a", TeamRole::Minelayer), ("b", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  let (a_id, a_key) = added[0].clone();
+  let (b_id, b_key) = added[1].clone();
+
+  start_game(&mut games, game_id, a_id, a_key.clone()).await;
+
+  // both teams together conquer (2, 2), the last attack is b's
+  for i in 0..60 {
+    let (team_id, team_key) = if i < 30 { (a_id, &a_key) } else { (b_id, &b_key) };
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id,
+          team_key: team_key.clone(),
+        },
+        row_index: 2,
+        column_index: 2,
+      })
+      .await
+      .unwrap();
+  }
+
+  // a has no requests left, so this gets rejected
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: a_id,
+        team_key: a_key.clone(),
+      },
+      row_index: 0,
+      column_index: 0,
+    })
+    .await
+    .unwrap_err();
+
+  let verified = games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+
+  // created, joined, started, 60 attacks and a rejected mine
+  assert_eq!(verified.events_replayed, 64);
+  assert_eq!(verified.state.status, GameStatus::Started);
+
+  let square = verified.state.square(2, 2).unwrap();
+  assert_eq!(square.owner_id, Some(b_id));
+  assert_eq!(square.health, 120);
+  assert_eq!(verified.state.teams[&a_id].requests_left, 0);
+  assert_eq!(verified.state.teams[&b_id].requests_left, 0);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_replay_should_render_state_at_any_sequence(#[values(1, 5, 12)] attacks: i32) {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("a", TeamRole::Spy), ("b", TeamRole::Cloaker)])
+    .await
+    .unwrap();
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  let sender = || SenderDetails {
+    team_id: added[1].0,
+    team_key: added[1].1.clone(),
+  };
+
+  for _ in 0..12 {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: sender(),
+        row_index: 4,
+        column_index: 1,
+      })
+      .await
+      .unwrap();
+  }
+
+  games
+    .try_defend_a_square(DefendRequest {
+      game_id,
+      sender: sender(),
+      row_index: 4,
+      column_index: 1,
+    })
+    .await
+    .unwrap();
+
+  let events = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap()
+    .events;
+
+  // created, joined, started, then the attacks
+  let sequence = events[2 + attacks as usize].sequence;
+
+  let state = games
+    .try_replay_game(ReplayGameRequest {
+      game_id,
+      until_sequence: Some(sequence),
+    })
+    .await
+    .unwrap()
+    .state;
+
+  assert_eq!(state.sequence, sequence);
+  assert_eq!(state.square(4, 1).unwrap().health, 60 - attacks);
+  assert_eq!(state.teams[&added[1].0].requests_left, 30 - attacks);
+  assert!(state.to_string().contains(&format!("-:{:<3}", 60 - attacks)));
+
+  let latest = games
+    .try_replay_game(ReplayGameRequest {
+      game_id,
+      until_sequence: None,
+    })
+    .await
+    .unwrap()
+    .state;
+
+  assert_eq!(latest.square(4, 1).unwrap().health, 49);
+  assert_eq!(latest.teams[&added[1].0].requests_left, 17);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_replay_should_detect_tampered_events() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("a", TeamRole::Spy)]).await.unwrap();
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+
+  for _ in 0..3 {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id: added[0].0,
+          team_key: added[0].1.clone(),
+        },
+        row_index: 0,
+        column_index: 0,
+      })
+      .await
+      .unwrap();
+  }
+
+  let mut events = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap()
+    .events;
+
+  assert_eq!(Replay::new(game_id, Vec::new()).final_state().unwrap().teams.len(), 0);
+
+  let tampered_sequence = events[3].sequence;
+  events[3].health = Some(10);
+
+  let steps = Replay::new(game_id, events).steps().collect::<Vec<_>>();
+
+  assert_eq!(steps.len(), 4);
+  assert!(steps[..3].iter().all(|step| step.is_ok()));
+  assert!(matches!(
+    steps[3],
+    Err(Error::ReplayMismatch { sequence, .. }) if sequence == tampered_sequence
+  ));
+}
@@ -0,0 +1,197 @@
+use game_core::replay::{AttackOutcome, ReplayState};
+use game_core::types::{AttackRequest, Error, MinePenalty, PlaceMineRequest, Rules, SenderDetails, TeamRole, VerifyGameRequest};
+use rstest::*;
+use simulator::{simulate, Report, SimulationConfig, StrategyKind};
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+fn config(games: usize, seed: u64) -> SimulationConfig {
+  SimulationConfig {
+    games,
+    seed,
+    ..SimulationConfig::default()
+  }
+}
+
+#[rstest]
+fn test_simulations_should_be_reproducible_from_their_seed() {
+  let first = simulate(&config(50, 7)).unwrap();
+  let second = simulate(&config(50, 7)).unwrap();
+  let other = simulate(&config(50, 8)).unwrap();
+
+  assert_eq!(first, second);
+  assert_ne!(first, other);
+  assert_eq!(Report::new(&config(50, 7), &first), Report::new(&config(50, 7), &second));
+}
+
+#[rstest]
+fn test_simulated_games_should_last_until_requests_run_out(#[values(1, 10, 30)] requests_count: i32) {
+  let config = SimulationConfig {
+    rules: Rules {
+      requests_count,
+      ..Rules::default()
+    },
+    strategies: vec![StrategyKind::GreedyConqueror, StrategyKind::TerritoryDefender],
+    ..config(20, 0)
+  };
+
+  let results = simulate(&config).unwrap();
+
+  for result in &results {
+    assert_eq!(result.rounds, requests_count as usize);
+    assert_eq!(result.moves, config.teams * requests_count as usize);
+    assert!(result.teams.iter().all(|team| team.requests_left == 0));
+
+    let owned = result.teams.iter().map(|team| team.squares_owned).sum::<usize>();
+    assert_eq!(owned + result.neutral_squares, 25);
+  }
+}
+
+#[rstest]
+fn test_report_should_aggregate_results() {
+  let config = SimulationConfig {
+    rules: Rules {
+      square_health: 5,
+      ..Rules::default()
+    },
+    ..config(200, 1)
+  };
+
+  let results = simulate(&config).unwrap();
+  let report = Report::new(&config, &results);
+
+  assert_eq!(report.games, 200);
+  assert_eq!(report.average_rounds, 30.0);
+  assert_eq!(report.roles.iter().map(|role| role.appearances).sum::<usize>(), 800);
+  assert_eq!(
+    report.strategies.iter().map(|strategy| strategy.appearances).sum::<usize>(),
+    800
+  );
+
+  let wins = report.roles.iter().map(|role| role.wins).sum::<usize>();
+  assert_eq!(wins + report.draws, 200);
+  assert_eq!(report.winning_territory.values().sum::<usize>(), wins);
+  assert!(report.average_neutral_squares < 25.0);
+
+  let csv = report.to_csv();
+  let mut lines = csv.lines();
+  assert_eq!(lines.next(), Some("group,name,appearances,wins,win_rate,average_squares"));
+  assert!(lines.next().unwrap().starts_with("overall,all,200,"));
+  assert_eq!(lines.count(), report.roles.len() + report.strategies.len());
+
+  let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+  assert_eq!(json["rules"]["mine_penalty"], "all_requests");
+}
+
+#[rstest]
+fn test_invalid_simulation_configs_should_be_rejected() {
+  let invalid = [
+    SimulationConfig {
+      teams: 0,
+      ..config(1, 0)
+    },
+    SimulationConfig {
+      strategies: vec![],
+      ..config(1, 0)
+    },
+    SimulationConfig {
+      rules: Rules {
+        requests_count: 0,
+        ..Rules::default()
+      },
+      ..config(1, 0)
+    },
+  ];
+
+  for config in invalid {
+    assert!(matches!(simulate(&config).unwrap_err(), Error::InvalidRules { .. }));
+  }
+}
+
+#[rstest]
+#[case(MinePenalty::AllRequests, 0)]
+#[case(MinePenalty::Requests(5), 24)]
+fn test_triggering_a_mine_in_memory_should_cost_requests(#[case] mine_penalty: MinePenalty, #[case] requests_left: i32) {
+  let mut state = ReplayState::new(
+    1,
+    Rules {
+      mine_penalty,
+      ..Rules::default()
+    },
+  );
+  state.join(1).unwrap();
+  state.join(2).unwrap();
+  state.start().unwrap();
+
+  state.place_mine(1, 2, 2).unwrap();
+  assert_eq!(state.place_mine(1, 0, 0).unwrap_err(), Error::RoleAlreadyUsed);
+
+  // mines don't go off for the team that placed them
+  assert_eq!(state.attack(1, 2, 2).unwrap(), AttackOutcome::Damaged);
+  assert_eq!(state.attack(2, 2, 2).unwrap(), AttackOutcome::MineTriggered { placed_by: 1 });
+  assert_eq!(state.teams[&2].requests_left, requests_left);
+
+  let square = state.square(2, 2).unwrap();
+  assert_eq!((square.health, square.mine_triggered_by), (59, Some(2)));
+
+  // a triggered mine only goes off once
+  if requests_left > 0 {
+    assert_eq!(state.attack(2, 2, 2).unwrap(), AttackOutcome::Damaged);
+  }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_attacking_a_mined_square_should_trigger_the_mine() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("host", TeamRole::Minelayer), ("guest", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  let (host_id, host_key) = added[0].clone();
+  let (guest_id, guest_key) = added[1].clone();
+
+  start_game(&mut games, game_id, host_id, host_key.clone()).await;
+
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: host_id,
+        team_key: host_key,
+      },
+      row_index: 1,
+      column_index: 3,
+    })
+    .await
+    .unwrap();
+
+  let attack = |team_key: &String| AttackRequest {
+    game_id,
+    sender: SenderDetails {
+      team_id: guest_id,
+      team_key: team_key.clone(),
+    },
+    row_index: 1,
+    column_index: 3,
+  };
+
+  let response = games.try_attack_a_square(attack(&guest_key)).await.unwrap();
+
+  assert!(!response.conquered);
+  assert_eq!(response.requests_left, 0);
+  assert_eq!(response.square.health, 60);
+  let mine = response.square.mine.unwrap();
+  assert_eq!((mine.placed_by, mine.triggered_by), (host_id, Some(guest_id)));
+
+  assert_eq!(
+    games.try_attack_a_square(attack(&guest_key)).await.unwrap_err(),
+    Error::NoMoreRequestsLeft
+  );
+
+  let verified = games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+  assert_eq!(verified.state.teams[&guest_id].requests_left, 0);
+  assert_eq!(verified.state.square(1, 3).unwrap().mine_triggered_by, Some(guest_id));
+}
@@ -0,0 +1,299 @@
+use game_core::commands::GRID_SIZE;
+use game_core::snapshot::SNAPSHOT_VERSION;
+use game_core::types::{
+  AttackRequest, Error, ExportGameRequest, GameSnapshot, ImportGameRequest, PlaceMineRequest, QueryGameRequest, SenderDetails,
+  StartRequest, TeamRole, VerifyGameRequest,
+};
+use rstest::*;
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+#[rstest]
+#[tokio::test]
+async fn test_should_export_and_import_a_game() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("host", TeamRole::Minelayer), ("guest", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  let (host_id, host_key) = added[0].clone();
+  let (guest_id, guest_key) = added[1].clone();
+
+  start_game(&mut games, game_id, host_id, host_key.clone()).await;
+
+  for _ in 0..30 {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id: guest_id,
+          team_key: guest_key.clone(),
+        },
+        row_index: 3,
+        column_index: 2,
+      })
+      .await
+      .unwrap();
+  }
+
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: host_id,
+        team_key: host_key.clone(),
+      },
+      row_index: 1,
+      column_index: 4,
+    })
+    .await
+    .unwrap();
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+
+  assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+  assert_eq!(snapshot.game.id, game_id);
+  assert_eq!(snapshot.teams.len(), 2);
+  assert_eq!(snapshot.grid.len(), 25);
+  assert_eq!(snapshot.mines.len(), 1);
+  assert_eq!(snapshot.mines[0].owner_id, host_id);
+
+  let json = snapshot.to_json().unwrap();
+  assert!(!json.contains(&host_key) && !json.contains(&guest_key));
+  assert!(!json.contains("\"key\""));
+
+  let parsed = GameSnapshot::from_json(&json).unwrap();
+  assert_eq!(parsed, snapshot);
+
+  let imported = games.try_import_game(ImportGameRequest { snapshot: parsed }).await.unwrap();
+  assert_ne!(imported.game_id, game_id);
+
+  let original = games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game;
+  let copy = games
+    .try_query_game(QueryGameRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap()
+    .game;
+
+  assert_eq!(copy.status, original.status);
+  assert_eq!(copy.created_at, original.created_at);
+
+  let new_team_id = |previous_team_id: i32| {
+    imported
+      .teams
+      .iter()
+      .find(|team| team.previous_team_id == previous_team_id)
+      .unwrap()
+      .team_id
+  };
+
+  for team in &original.teams {
+    let copied = copy.teams.iter().find(|t| t.id == new_team_id(team.id)).unwrap();
+    assert_eq!(copied.display_name, team.display_name);
+    assert_eq!(copied.role, team.role);
+    assert_eq!(copied.role_used, team.role_used);
+    assert_eq!(copied.requests_left, team.requests_left);
+    assert_ne!(copied.key, team.key);
+  }
+
+  for square in &original.grid {
+    let copied = copy
+      .grid
+      .iter()
+      .find(|s| (s.row, s.column) == (square.row, square.column))
+      .unwrap();
+    assert_eq!(copied.health, square.health);
+    assert_eq!(copied.owner_id, square.owner_id.map(new_team_id));
+  }
+
+  let exported_copy = games
+    .try_export_game(ExportGameRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap()
+    .snapshot;
+  assert_eq!(exported_copy.mines.len(), 1);
+  assert_eq!(exported_copy.mines[0].owner_id, new_team_id(host_id));
+  assert_eq!(
+    (exported_copy.mines[0].row_index, exported_copy.mines[0].column_index),
+    (1, 4)
+  );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_imported_games_should_replay_and_verify() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("host", TeamRole::Minelayer), ("guest", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  let host = SenderDetails {
+    team_id: added[0].0,
+    team_key: added[0].1.clone(),
+  };
+  let guest = SenderDetails {
+    team_id: added[1].0,
+    team_key: added[1].1.clone(),
+  };
+  start_game(&mut games, game_id, host.team_id, host.team_key.clone()).await;
+
+  let attack = |sender: &SenderDetails, game_id: i32, (row_index, column_index): (i32, i32)| AttackRequest {
+    game_id,
+    sender: sender.clone(),
+    row_index,
+    column_index,
+  };
+  for _ in 0..10 {
+    games.try_attack_a_square(attack(&guest, game_id, (3, 2))).await.unwrap();
+  }
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: host.clone(),
+      row_index: 1,
+      column_index: 4,
+    })
+    .await
+    .unwrap();
+  games.try_attack_a_square(attack(&guest, game_id, (1, 4))).await.unwrap();
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  let imported = games.try_import_game(ImportGameRequest { snapshot }).await.unwrap();
+  let game_id = imported.game_id;
+
+  let verified = games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+  let mine = verified.state.square(1, 4).unwrap();
+  assert_eq!(mine.mine_placed_by, Some(imported.teams[0].team_id));
+  assert_eq!(mine.mine_triggered_by, Some(imported.teams[1].team_id));
+
+  // the imported history carries on like any other game's
+  let host = SenderDetails {
+    team_id: imported.teams[0].team_id,
+    team_key: imported.teams[0].team_key.clone(),
+  };
+  games.try_attack_a_square(attack(&host, game_id, (3, 2))).await.unwrap();
+  games.try_verify_game(VerifyGameRequest { game_id }).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_imported_teams_should_be_able_to_use_their_new_keys() {
+  let TestSetup { mut games, game_id, .. } = setup_with_players(&[("a", TeamRole::Spy), ("b", TeamRole::Cloaker)])
+    .await
+    .unwrap();
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  let imported = games.try_import_game(ImportGameRequest { snapshot }).await.unwrap();
+  let host = &imported.teams[0];
+
+  let response = games
+    .try_start(StartRequest {
+      game_id: imported.game_id,
+      sender: SenderDetails {
+        team_id: host.team_id,
+        team_key: host.team_key.clone(),
+      },
+    })
+    .await
+    .unwrap();
+
+  assert_eq!(response.game_id, imported.game_id);
+  games
+    .try_verify_game(VerifyGameRequest {
+      game_id: imported.game_id,
+    })
+    .await
+    .unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_should_reject_invalid_snapshots() {
+  let TestSetup { mut games, game_id, .. } = setup_with_players(&[("a", TeamRole::Spy)]).await.unwrap();
+
+  let snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+
+  let mut future = snapshot.clone();
+  future.version = SNAPSHOT_VERSION + 1;
+  let json = future.to_json().unwrap();
+  assert_eq!(
+    GameSnapshot::from_json(&json).unwrap_err(),
+    Error::UnsupportedSnapshotVersion {
+      version: SNAPSHOT_VERSION + 1,
+      supported: SNAPSHOT_VERSION
+    }
+  );
+
+  let mut orphaned = snapshot.clone();
+  orphaned.grid[0].owner_id = Some(-1);
+  let error = games
+    .try_import_game(ImportGameRequest { snapshot: orphaned })
+    .await
+    .unwrap_err();
+  assert!(matches!(error, Error::InvalidSnapshot { .. }));
+
+  assert!(matches!(
+    GameSnapshot::from_json("{\"version\": 1}").unwrap_err(),
+    Error::InvalidSnapshot { .. }
+  ));
+
+  let error = games
+    .try_export_game(ExportGameRequest { game_id: game_id + 1 })
+    .await
+    .unwrap_err();
+  assert_eq!(error, Error::InvalidGameId { game_id: game_id + 1 });
+}
+
+fn duplicate_square(snapshot: &mut GameSnapshot) {
+  snapshot.grid.push(snapshot.grid[0].clone());
+}
+
+fn square_outside_the_grid(snapshot: &mut GameSnapshot) {
+  let last = snapshot.grid.len() - 1;
+  snapshot.grid[last].row_index = GRID_SIZE;
+}
+
+fn home_outside_the_grid(snapshot: &mut GameSnapshot) {
+  snapshot.teams[0].home_row = Some(GRID_SIZE);
+  snapshot.teams[0].home_column = Some(0);
+}
+
+fn home_without_a_column(snapshot: &mut GameSnapshot) {
+  snapshot.teams[0].home_row = Some(0);
+  snapshot.teams[0].home_column = None;
+}
+
+fn shared_home(snapshot: &mut GameSnapshot) {
+  for team in &mut snapshot.teams {
+    team.home_row = Some(2);
+    team.home_column = Some(2);
+  }
+}
+
+#[rstest]
+#[case::duplicate_square(duplicate_square)]
+#[case::square_outside_the_grid(square_outside_the_grid)]
+#[case::home_outside_the_grid(home_outside_the_grid)]
+#[case::home_without_a_column(home_without_a_column)]
+#[case::shared_home(shared_home)]
+#[tokio::test]
+async fn test_should_reject_snapshots_with_misplaced_squares(#[case] tamper: fn(&mut GameSnapshot)) {
+  let TestSetup { mut games, game_id, .. } = setup_with_players(&[("a", TeamRole::Spy), ("b", TeamRole::Spy)])
+    .await
+    .unwrap();
+  let mut snapshot = games.try_export_game(ExportGameRequest { game_id }).await.unwrap().snapshot;
+  tamper(&mut snapshot);
+
+  assert!(matches!(snapshot.validate(), Err(Error::InvalidSnapshot { .. })));
+  let error = games.try_import_game(ImportGameRequest { snapshot }).await.unwrap_err();
+  assert!(matches!(error, Error::InvalidSnapshot { .. }));
+}
@@ -0,0 +1,153 @@
+use game_core::types::{AttackRequest, Games, PlaceMineRequest, QueryEventsRequest, QueryGameRequest, SenderDetails, TeamRole};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use rstest::*;
+use spectator::{draw, Source, SpectatorState, Update};
+use tests_integration::{setup_with_players, start_game, TestSetup};
+
+async fn attack(games: &mut Games, game_id: i32, (team_id, team_key): &(i32, String), times: usize) {
+  for _ in 0..times {
+    games
+      .try_attack_a_square(AttackRequest {
+        game_id,
+        sender: SenderDetails {
+          team_id: *team_id,
+          team_key: team_key.clone(),
+        },
+        row_index: 0,
+        column_index: 0,
+      })
+      .await
+      .unwrap();
+  }
+}
+
+fn rendered(state: &SpectatorState) -> String {
+  let mut terminal = Terminal::new(TestBackend::new(160, 50)).unwrap();
+  terminal.draw(|frame| draw(frame, state)).unwrap();
+
+  let buffer = terminal.backend().buffer();
+  (0..buffer.area.height)
+    .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_spectator_should_build_feed_from_the_event_log() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[
+    ("red", TeamRole::Minelayer),
+    ("blue", TeamRole::Spy),
+    ("green", TeamRole::Cloaker),
+  ])
+  .await
+  .unwrap();
+
+  let mut source = Source::Database(games.clone());
+  let mut state = SpectatorState::default();
+  state.apply(source.poll(game_id, state.last_sequence).await.unwrap());
+
+  assert_eq!(state.feed, ["red created the game", "blue joined", "green joined"]);
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+  attack(&mut games, game_id, &added[1], 30).await;
+  attack(&mut games, game_id, &added[2], 30).await;
+  games
+    .try_place_a_mine(PlaceMineRequest {
+      game_id,
+      sender: SenderDetails {
+        team_id: added[0].0,
+        team_key: added[0].1.clone(),
+      },
+      row_index: 3,
+      column_index: 4,
+    })
+    .await
+    .unwrap();
+
+  state.apply(source.poll(game_id, state.last_sequence).await.unwrap());
+
+  assert_eq!(state.feed.len(), 3 + 1 + 60 + 1);
+  assert_eq!(state.feed[3], "the game has started");
+  assert_eq!(state.feed[4], "blue attacked (0, 0), health 59");
+  assert_eq!(state.feed[63], "green conquered (0, 0)");
+  // mine positions stay off the big screen
+  assert_eq!(state.feed[64], "red placed a mine");
+
+  let last_sequence = games
+    .try_query_events(QueryEventsRequest {
+      game_id,
+      after_sequence: 0,
+      limit: None,
+    })
+    .await
+    .unwrap()
+    .last_sequence;
+  assert!(state.last_sequence >= last_sequence);
+
+  let leaderboard = state.leaderboard();
+  assert_eq!(leaderboard[0].display_name, "green");
+  assert_eq!((leaderboard[0].squares, leaderboard[0].total_health), (1, 120));
+  assert_eq!(leaderboard[1].squares, 0);
+
+  let screen = rendered(&state);
+  assert!(screen.contains("leaderboard"));
+  assert!(screen.contains("green conquered (0, 0)"));
+  assert!(screen.contains("████"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_spectator_should_diff_games_without_an_event_log() {
+  let TestSetup {
+    mut games,
+    game_id,
+    added,
+  } = setup_with_players(&[("red", TeamRole::Spy), ("blue", TeamRole::Spy)])
+    .await
+    .unwrap();
+
+  let query = |games: Games| async move { games.try_query_game(QueryGameRequest { game_id }).await.unwrap().game };
+
+  let mut state = SpectatorState::default();
+  state.apply(Update {
+    game: query(games.clone()).await,
+    events: None,
+  });
+  assert_eq!(state.feed, [format!("watching game {game_id}")]);
+
+  start_game(&mut games, game_id, added[0].0, added[0].1.clone()).await;
+  attack(&mut games, game_id, &added[0], 2).await;
+
+  state.apply(Update {
+    game: query(games.clone()).await,
+    events: None,
+  });
+  assert_eq!(
+    state.feed.iter().skip(1).collect::<Vec<_>>(),
+    ["the game has started", "(0, 0) was attacked, health 58"]
+  );
+
+  attack(&mut games, game_id, &added[0], 28).await;
+  attack(&mut games, game_id, &added[1], 30).await;
+  state.apply(Update {
+    game: query(games.clone()).await,
+    events: None,
+  });
+  assert_eq!(state.feed.back().unwrap(), "blue conquered (0, 0)");
+}
+
+#[rstest]
+fn test_spectator_should_render_before_the_first_update() {
+  let state = SpectatorState {
+    last_error: Some("connection refused".to_string()),
+    ..SpectatorState::default()
+  };
+
+  assert!(rendered(&state).contains("connection refused"));
+}